@@ -1,10 +1,25 @@
-use std::net::TcpListener;
+use std::{net::TcpListener, sync::Arc, time::Duration};
 
 use crate::{
-    config::config::GeneralConfig,
+    config::config::{GeneralConfig, QueueConfig},
     db::syclla::ScyllaService,
+    domain::{
+        consumer::Consumer, deletion::DeletionRegistry, job::JobRegistry,
+        s3::source::SourceRegistry, upload, watcher::Watcher, webhook::Notifier,
+    },
+    queue::redis_streams::RedisStreamQueue,
     routes::{
-        fetch_node::get_node_by_id, health_check::health_check, ingest::ingest,
+        export_graph::export_graphml,
+        fetch_node::get_node_by_id,
+        health_check::health_check,
+        ingest::{
+            get_ingestion_job, ingest, ingest_upload, ingestion_events, preview_ingestion,
+            resume_ingestion,
+        },
+        ingestions::{
+            delete_ingestion, get_deletion_job, get_ingestion, list_ingestions,
+            plan_path_migration, replay_failed_rows,
+        },
         traverse_node::traverse_node_by_id,
     },
 };
@@ -17,17 +32,40 @@ use tracing_actix_web::TracingLogger;
 pub struct Application {
     server: Server,
     port: u16,
+    state: web::Data<AppState>,
 }
 
 #[derive(Debug)]
 pub struct AppState {
     pub db: ScyllaService,
     pub semaphore: Semaphore,
+    pub sources: SourceRegistry,
+    pub jobs: JobRegistry,
+    pub deletions: DeletionRegistry,
+    pub notifier: Notifier,
+    pub chunk_size: usize,
+    pub upload_max_bytes: u64,
 }
 
 impl AppState {
-    pub fn new(db: ScyllaService, semaphore: Semaphore) -> Self {
-        Self { db, semaphore }
+    pub fn new(
+        db: ScyllaService,
+        semaphore: Semaphore,
+        sources: SourceRegistry,
+        notifier: Notifier,
+        chunk_size: usize,
+        upload_max_bytes: u64,
+    ) -> Self {
+        Self {
+            db,
+            semaphore,
+            sources,
+            jobs: JobRegistry::default(),
+            deletions: DeletionRegistry::default(),
+            notifier,
+            chunk_size,
+            upload_max_bytes,
+        }
     }
 }
 
@@ -39,14 +77,57 @@ impl Application {
         let port = listener.local_addr()?.port();
         let db = ScyllaService::init(&config.db).await?;
         let semaphore = Semaphore::new(config.app.parallel_files);
-        let app_state = AppState::new(db, semaphore);
-        let server = Self::create_server(listener, app_state)?;
+        let sources = SourceRegistry::new(&config.app, &config.source)?;
+        let notifier = Notifier::new(&config.webhook)?;
+        let app_state = AppState::new(
+            db,
+            semaphore,
+            sources,
+            notifier,
+            config.app.chunk_size,
+            config.upload.upload_max_bytes,
+        );
+        let app_state = web::Data::new(app_state);
+        let retention = Duration::from_secs(config.upload.upload_retention_secs);
+        drop(upload::spawn_cleanup(app_state.clone(), retention));
+        if let Some(watcher) = Watcher::from_config(&config.watcher)? {
+            drop(watcher.spawn(app_state.clone()));
+        }
+        if let Some(consumer) = Self::create_consumer(&config.queue).await? {
+            drop(consumer.spawn(app_state.clone()));
+        }
+        let server = Self::create_server(listener, app_state.clone())?;
         Ok(Application {
-            server: server,
-            port: port,
+            server,
+            port,
+            state: app_state,
         })
     }
 
+    async fn create_consumer(config: &QueueConfig) -> Result<Option<Consumer>> {
+        let Some(url) = config.queue_url.as_deref().filter(|url| !url.is_empty()) else {
+            return Ok(None);
+        };
+        let consumer = config
+            .queue_consumer
+            .clone()
+            .unwrap_or_else(|| format!("rustfastingest-{}", uuid::Uuid::new_v4()));
+        let queue = RedisStreamQueue::connect(
+            url,
+            &config.queue_stream,
+            &config.queue_group,
+            &consumer,
+            Duration::from_secs(config.queue_claim_idle_secs),
+            config.queue_max_deliveries,
+        )
+        .await?;
+        Ok(Some(Consumer::new(
+            Arc::new(queue),
+            config.queue_concurrency,
+            Duration::from_millis(config.queue_block_ms),
+        )))
+    }
+
     pub async fn run(self) -> Result<()> {
         let _ = self.server.await;
         Ok(())
@@ -56,15 +137,30 @@ impl Application {
         self.port
     }
 
-    fn create_server(listener: TcpListener, state: AppState) -> Result<Server> {
-        let state = web::Data::new(state);
+    pub fn state(&self) -> web::Data<AppState> {
+        self.state.clone()
+    }
+
+    fn create_server(listener: TcpListener, state: web::Data<AppState>) -> Result<Server> {
         let server = HttpServer::new(move || {
             App::new()
                 .wrap(TracingLogger::default())
                 .service(health_check)
                 .service(ingest)
+                .service(preview_ingestion)
+                .service(resume_ingestion)
+                .service(ingestion_events)
+                .service(ingest_upload)
+                .service(get_ingestion_job)
                 .service(get_node_by_id)
                 .service(traverse_node_by_id)
+                .service(export_graphml)
+                .service(list_ingestions)
+                .service(get_ingestion)
+                .service(plan_path_migration)
+                .service(replay_failed_rows)
+                .service(delete_ingestion)
+                .service(get_deletion_job)
                 .app_data(state.clone())
         })
         .listen(listener)?
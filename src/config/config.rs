@@ -8,6 +8,9 @@ pub struct AppConfig {
     pub host: String,
     pub port: u16,
     pub parallel_files: usize,
+    /// Rows parsed from a file before they are written out.
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
     pub region: String,
     pub rust_log: String,
 }
@@ -18,6 +21,47 @@ pub struct DatabaseConfig {
     pub datacenter: String,
     pub concurrency_limit: usize,
     pub schema_file: String,
+    /// Upper bound on the rows of one partition sent in a single batch.
+    #[serde(default = "default_max_batch_rows")]
+    pub max_batch_rows: usize,
+    #[serde(default = "default_retry_max_attempts")]
+    pub retry_max_attempts: usize,
+    #[serde(default = "default_retry_base_delay_ms")]
+    pub retry_base_delay_ms: u64,
+    #[serde(default = "default_retry_max_delay_ms")]
+    pub retry_max_delay_ms: u64,
+    /// Comma separated error kinds worth retrying: overloaded, timeout,
+    /// unavailable, io, other.
+    #[serde(default = "default_retry_on")]
+    pub retry_on: String,
+    /// Where rows that exhausted their retries go: `table` for
+    /// `graph.failed_rows`, or the path of an NDJSON file.
+    #[serde(default)]
+    pub dead_letter: Option<String>,
+}
+
+fn default_chunk_size() -> usize {
+    1000
+}
+
+fn default_max_batch_rows() -> usize {
+    100
+}
+
+fn default_retry_max_attempts() -> usize {
+    3
+}
+
+fn default_retry_base_delay_ms() -> u64 {
+    100
+}
+
+fn default_retry_max_delay_ms() -> u64 {
+    5000
+}
+
+fn default_retry_on() -> String {
+    "overloaded,timeout,unavailable,io".to_string()
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -34,11 +78,165 @@ pub struct ElasticSearchConfig {
     pub source_enabled: bool,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct SourceConfig {
+    pub s3_endpoint: Option<String>,
+    pub s3_path_style: Option<bool>,
+    /// Comma separated directories `file://` sources and bare paths may be
+    /// read from. Local sources are rejected when it is empty.
+    #[serde(default)]
+    pub source_file_roots: String,
+    /// Comma separated hosts `http(s)://` sources may be fetched from.
+    /// HTTP sources are rejected when it is empty.
+    #[serde(default)]
+    pub source_http_hosts: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookConfig {
+    /// Key of the HMAC-SHA256 signature sent with job callbacks. Requests
+    /// with a `callback_url` are refused while it is not set.
+    #[serde(default)]
+    pub webhook_secret: Option<String>,
+    /// Comma separated hosts callbacks may be sent to. Requests with a
+    /// `callback_url` elsewhere are refused.
+    #[serde(default)]
+    pub webhook_hosts: String,
+    #[serde(default = "default_webhook_max_attempts")]
+    pub webhook_max_attempts: usize,
+    #[serde(default = "default_webhook_base_delay_ms")]
+    pub webhook_base_delay_ms: u64,
+    #[serde(default = "default_webhook_max_delay_ms")]
+    pub webhook_max_delay_ms: u64,
+    #[serde(default = "default_webhook_timeout_ms")]
+    pub webhook_timeout_ms: u64,
+}
+
+fn default_webhook_max_attempts() -> usize {
+    5
+}
+
+fn default_webhook_base_delay_ms() -> u64 {
+    500
+}
+
+fn default_webhook_max_delay_ms() -> u64 {
+    30000
+}
+
+fn default_webhook_timeout_ms() -> u64 {
+    10000
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WatcherConfig {
+    /// Bucket prefix to poll for new objects, e.g. `s3://bucket/incoming/`.
+    /// The watcher is off when it is not set.
+    #[serde(default)]
+    pub watch_source: Option<String>,
+    /// Glob the keys must match, relative to the prefix.
+    #[serde(default = "default_watch_pattern")]
+    pub watch_pattern: String,
+    /// Template of the ingestion id, from `{key}`, `{dir}`, `{name}` and
+    /// `{stem}` of the relative key.
+    #[serde(default = "default_watch_ingestion_id")]
+    pub watch_ingestion_id: String,
+    #[serde(default = "default_watch_interval_secs")]
+    pub watch_interval_secs: u64,
+}
+
+fn default_watch_pattern() -> String {
+    "**".to_string()
+}
+
+fn default_watch_ingestion_id() -> String {
+    "{stem}".to_string()
+}
+
+fn default_watch_interval_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct QueueConfig {
+    /// Redis url of the stream to consume ingestion requests from, e.g.
+    /// `redis://localhost:6379`. The consumer is off when it is not set.
+    #[serde(default)]
+    pub queue_url: Option<String>,
+    #[serde(default = "default_queue_stream")]
+    pub queue_stream: String,
+    #[serde(default = "default_queue_group")]
+    pub queue_group: String,
+    /// Name of this instance in the group; a random one when not set.
+    #[serde(default)]
+    pub queue_consumer: Option<String>,
+    /// Messages handled at the same time.
+    #[serde(default = "default_queue_concurrency")]
+    pub queue_concurrency: usize,
+    #[serde(default = "default_queue_block_ms")]
+    pub queue_block_ms: u64,
+    /// How long a message stays pending before it is delivered again.
+    /// Consumers touch the messages they handle every third of it, so a
+    /// running job is only taken over once its instance is gone.
+    #[serde(default = "default_queue_claim_idle_secs")]
+    pub queue_claim_idle_secs: u64,
+    /// Deliveries of a message before it is moved to `<stream>:dead`.
+    #[serde(default = "default_queue_max_deliveries")]
+    pub queue_max_deliveries: usize,
+}
+
+fn default_queue_stream() -> String {
+    "ingestion-requests".to_string()
+}
+
+fn default_queue_group() -> String {
+    "rustfastingest".to_string()
+}
+
+fn default_queue_concurrency() -> usize {
+    2
+}
+
+fn default_queue_block_ms() -> u64 {
+    5000
+}
+
+fn default_queue_claim_idle_secs() -> u64 {
+    300
+}
+
+fn default_queue_max_deliveries() -> usize {
+    5
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UploadConfig {
+    /// Upper bound on the size of one `/ingest/upload` form.
+    #[serde(default = "default_upload_max_bytes")]
+    pub upload_max_bytes: u64,
+    /// How long uploads of failed jobs are kept for a resume.
+    #[serde(default = "default_upload_retention_secs")]
+    pub upload_retention_secs: u64,
+}
+
+fn default_upload_max_bytes() -> u64 {
+    1024 * 1024 * 1024
+}
+
+fn default_upload_retention_secs() -> u64 {
+    86400
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct GeneralConfig {
     pub app: AppConfig,
     pub db: DatabaseConfig,
     pub es: ElasticSearchConfig,
+    pub source: SourceConfig,
+    pub webhook: WebhookConfig,
+    pub watcher: WatcherConfig,
+    pub queue: QueueConfig,
+    pub upload: UploadConfig,
 }
 
 impl GeneralConfig {
@@ -50,10 +248,20 @@ impl GeneralConfig {
         let app_config: AppConfig = c.clone().try_into()?;
         let db_config: DatabaseConfig = c.clone().try_into()?;
         let es_config: ElasticSearchConfig = c.clone().try_into()?;
+        let source_config: SourceConfig = c.clone().try_into()?;
+        let webhook_config: WebhookConfig = c.clone().try_into()?;
+        let watcher_config: WatcherConfig = c.clone().try_into()?;
+        let queue_config: QueueConfig = c.clone().try_into()?;
+        let upload_config: UploadConfig = c.clone().try_into()?;
         let config = GeneralConfig {
             app: app_config,
             db: db_config,
             es: es_config,
+            source: source_config,
+            webhook: webhook_config,
+            watcher: watcher_config,
+            queue: queue_config,
+            upload: upload_config,
         };
         Ok(config)
     }
@@ -66,11 +274,20 @@ mod tests {
     #[test]
     fn test_from_env() {
         let result = GeneralConfig::from_env();
-        assert_eq!(result.is_ok(), true);
+        assert!(result.is_ok());
         let config = result.unwrap();
         println!("{:?}", config);
         assert_eq!(config.es.refresh_interval, "20s".to_string());
         assert_eq!(config.app.host, "127.0.0.1");
+        assert_eq!(config.app.chunk_size, 1000);
         assert_eq!(config.db.concurrency_limit, 10);
+        assert_eq!(config.db.max_batch_rows, 100);
+        assert_eq!(config.db.retry_max_attempts, 3);
+        assert_eq!(config.webhook.webhook_max_attempts, 5);
+        assert_eq!(config.watcher.watch_pattern, "**");
+        assert_eq!(config.queue.queue_stream, "ingestion-requests");
+        assert_eq!(config.queue.queue_max_deliveries, 5);
+        assert!(config.source.source_http_hosts.is_empty());
+        assert_eq!(config.upload.upload_retention_secs, 86400);
     }
 }
@@ -0,0 +1,167 @@
+use super::model::NodeModel;
+use chrono::{DateTime, Utc};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::{io::AsyncWriteExt, sync::Mutex};
+
+/// A row that could not be written after all retries, kept with enough
+/// context to replay it through `insert_nodes`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedRow {
+    pub row: NodeModel,
+    pub error: String,
+    pub attempts: usize,
+    pub failed_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub enum DeadLetterSink {
+    Discard,
+    /// Appends one JSON object per line. Writes are serialised so lines from
+    /// concurrent batches never interleave.
+    File {
+        path: PathBuf,
+        lock: Mutex<()>,
+    },
+    /// Inserts into `graph.failed_rows`.
+    Table,
+}
+
+impl DeadLetterSink {
+    /// `table` selects the Scylla table, any other non-empty value is taken
+    /// as the path of the NDJSON file.
+    pub fn parse(value: Option<&str>) -> DeadLetterSink {
+        match value.map(str::trim) {
+            None | Some("") => DeadLetterSink::Discard,
+            Some("table") => DeadLetterSink::Table,
+            Some(path) => DeadLetterSink::File {
+                path: PathBuf::from(path),
+                lock: Mutex::new(()),
+            },
+        }
+    }
+}
+
+pub async fn append_file(path: &Path, lock: &Mutex<()>, rows: &[FailedRow]) -> Result<()> {
+    let mut lines = Vec::new();
+    for row in rows {
+        serde_json::to_writer(&mut lines, row)?;
+        lines.push(b'\n');
+    }
+    let _guard = lock.lock().await;
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(&lines).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+pub async fn read_file(path: &Path) -> Result<Vec<FailedRow>> {
+    let contents = match tokio::fs::read_to_string(path).await {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+/// Removes the rows of `ingestion_id` from the file and returns them, for
+/// replay. The other rows are written back in place.
+pub async fn take_file(
+    path: &Path,
+    lock: &Mutex<()>,
+    ingestion_id: &str,
+) -> Result<Vec<FailedRow>> {
+    let _guard = lock.lock().await;
+    let (taken, kept): (Vec<_>, Vec<_>) = read_file(path)
+        .await?
+        .into_iter()
+        .partition(|failed| failed.row.ingestion_id == ingestion_id);
+    if taken.is_empty() {
+        return Ok(taken);
+    }
+    let mut lines = Vec::new();
+    for row in &kept {
+        serde_json::to_writer(&mut lines, row)?;
+        lines.push(b'\n');
+    }
+    let temp = path.with_extension("replay");
+    tokio::fs::write(&temp, &lines).await?;
+    tokio::fs::rename(&temp, path).await?;
+    Ok(taken)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dead_letter_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("failed.ndjson");
+        let sink = DeadLetterSink::parse(Some(path.to_str().unwrap()));
+        let DeadLetterSink::File { path, lock } = &sink else {
+            panic!("expected a file sink");
+        };
+        let row = FailedRow {
+            row: NodeModel {
+                name: "node".to_string(),
+                ..NodeModel::default()
+            },
+            error: "timeout".to_string(),
+            attempts: 3,
+            failed_at: Utc::now(),
+        };
+        let rows = [row.clone(), row];
+        append_file(path, lock, &rows[..1]).await.unwrap();
+        append_file(path, lock, &rows[1..]).await.unwrap();
+
+        let rows = read_file(path).await.unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].row.name, "node");
+        assert_eq!(rows[1].attempts, 3);
+        let missing = path.with_extension("missing");
+        assert!(read_file(&missing).await.unwrap().is_empty());
+        assert!(matches!(
+            DeadLetterSink::parse(None),
+            DeadLetterSink::Discard
+        ));
+        assert!(matches!(
+            DeadLetterSink::parse(Some("table")),
+            DeadLetterSink::Table
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_take_file_keeps_other_ingestions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("failed.ndjson");
+        let lock = Mutex::new(());
+        let failed = |ingestion_id: &str| FailedRow {
+            row: NodeModel {
+                ingestion_id: ingestion_id.to_string(),
+                ..NodeModel::default()
+            },
+            error: "timeout".to_string(),
+            attempts: 3,
+            failed_at: Utc::now(),
+        };
+        let rows = [failed("a"), failed("b"), failed("a")];
+        append_file(&path, &lock, &rows).await.unwrap();
+
+        let taken = take_file(&path, &lock, "a").await.unwrap();
+        assert_eq!(taken.len(), 2);
+        let kept = read_file(&path).await.unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].row.ingestion_id, "b");
+        assert!(take_file(&path, &lock, "a").await.unwrap().is_empty());
+    }
+}
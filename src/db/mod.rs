@@ -1,2 +1,5 @@
+pub mod dead_letter;
 pub mod model;
+pub mod retry;
 pub mod syclla;
+pub mod throttle;
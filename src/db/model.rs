@@ -1,18 +1,26 @@
 use crate::domain::{
     node::Node,
+    path,
     relation::Relation,
     s3::data::{RawNode, RawTag},
 };
-use scylla::FromRow;
-use std::collections::HashMap;
+use scylla::{frame::value::Timestamp, FromRow};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
 const NAMESPACE_UUID: Uuid = Uuid::from_bytes([
     0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
 ]);
 
-pub fn path_from_name(path: &str, name: &str) -> String {
-    format!("{}/{}", path, name)
+pub fn path_from_name(parent: &str, name: &str) -> String {
+    path::join(parent, name)
+}
+
+/// Joins the segments of a path, as relation endpoints and continuation
+/// parents carry them, the way nested nodes are joined.
+pub fn path_from_segments(segments: &[String]) -> String {
+    path::from_segments(segments)
 }
 
 pub fn path_to_uuid(ingestion_id: &str, path: &str) -> Uuid {
@@ -56,7 +64,7 @@ impl Role {
     }
 }
 
-#[derive(Default, Debug, Clone, FromRow)]
+#[derive(Default, Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct NodeModel {
     pub uuid: Uuid,
     pub direction: Option<String>,
@@ -67,6 +75,10 @@ pub struct NodeModel {
     pub path: String,
     pub node_type: String,
     pub tags: Option<Vec<(String, String)>>,
+    /// Children the node has at the source, of which a file may only carry
+    /// some. Only set on node rows.
+    #[serde(default)]
+    pub total_children: Option<i64>,
 }
 
 impl From<NodeModel> for Node {
@@ -79,6 +91,7 @@ impl From<NodeModel> for Node {
             node_type: node_model.node_type,
             tags: node_model.tags.unwrap_or_default(),
             relations: vec![],
+            total_children: node_model.total_children,
         }
     }
 }
@@ -90,6 +103,7 @@ impl NodeModel {
         name: String,
         node_type: String,
         raw_tags: Vec<RawTag>,
+        total_children: Option<i64>,
     ) -> Self {
         let id = path_to_uuid(&ingestion_id, &path);
         let tags: Vec<(String, String)> = extract_tag_pairs(raw_tags);
@@ -98,11 +112,12 @@ impl NodeModel {
             direction: None,
             relation: None,
             relates_to: None,
-            name: name,
-            ingestion_id: ingestion_id,
-            path: path,
-            node_type: node_type,
+            name,
+            ingestion_id,
+            path,
+            node_type,
             tags: Some(tags),
+            total_children,
         }
     }
 
@@ -124,6 +139,7 @@ impl NodeModel {
             path: "".to_owned(),
             node_type: "".to_owned(),
             tags: None,
+            total_children: None,
         }
     }
 
@@ -143,7 +159,12 @@ impl NodeModel {
             ingestion_id,
             path: "".to_string(),
             node_type: "".to_string(),
-            tags: None,
+            tags: if relation.tags.is_empty() {
+                None
+            } else {
+                Some(relation.tags.clone())
+            },
+            total_children: None,
         }
     }
 }
@@ -156,16 +177,70 @@ pub struct RelationModel {
     pub relates_to: Option<String>,
     pub name: String,
     pub node_type: String,
+    pub tags: Option<Vec<(String, String)>>,
+    pub total_children: Option<i64>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct IngestionModel {
+    pub id: String,
+    pub job_id: Uuid,
+    pub files: Vec<String>,
+    pub started_at: Timestamp,
+    pub finished_at: Option<Timestamp>,
+    pub status: String,
+    pub nodes: i64,
+    pub relations: i64,
+    pub rows_written: i64,
+    pub rows_failed: i64,
+    pub service_version: String,
 }
 
+/// One file of `graph.ingestion_checkpoints`. The request is a static
+/// column, so a job that has not checkpointed any file yet comes back as a
+/// single row without a file index.
+#[derive(Debug, Clone, FromRow)]
+pub struct CheckpointModel {
+    pub request: Option<String>,
+    pub file_index: Option<i32>,
+    pub file: Option<String>,
+    pub rows_committed: Option<i64>,
+    pub completed: Option<bool>,
+}
+
+/// An object of `graph.watched_objects`, as it was when the watcher
+/// ingested it.
+#[derive(Debug, Clone, FromRow)]
+pub struct WatchedObjectModel {
+    pub key: String,
+    pub etag: Option<String>,
+}
+
+/// Flattens `raw_nodes` into rows. With a `parent` path (see
+/// `GraphData::parent`) the nodes become children of that node, which is
+/// expected to be written by another file: only its child edges are emitted.
 pub async fn process_nodes(
     ingestion_id: &str,
+    parent: &[String],
     raw_nodes: Vec<RawNode>,
     relations: HashMap<String, Vec<Relation>>,
 ) -> eyre::Result<Vec<NodeModel>> {
     let mut db_nodes: Vec<NodeModel> = Vec::new();
-    let parent = None;
-    let path: String = String::new();
+    let path = path_from_segments(parent);
+    let parent = parent
+        .last()
+        .map(|name| (path_to_uuid(ingestion_id, &path), name.clone()));
+    if let Some((parent_id, _)) = &parent {
+        for raw_node in &raw_nodes {
+            let child_id = path_to_uuid(ingestion_id, &path_from_name(&path, &raw_node.name));
+            db_nodes.push(child_row(
+                ingestion_id,
+                *parent_id,
+                child_id,
+                &raw_node.name,
+            ));
+        }
+    }
     let _ = flatten_nodes(
         ingestion_id,
         &raw_nodes,
@@ -177,42 +252,105 @@ pub async fn process_nodes(
     Ok(db_nodes)
 }
 
+pub fn node_rows(
+    ingestion_id: &str,
+    path: &str,
+    raw_node: &RawNode,
+    parent: &Option<(Uuid, String)>,
+) -> Vec<NodeModel> {
+    let tags = raw_node.tags.clone().unwrap_or_default();
+    let root = NodeModel::root(
+        ingestion_id.to_owned(),
+        path.to_owned(),
+        raw_node.name.clone(),
+        raw_node.type_field.clone(),
+        tags,
+        raw_node.total_children,
+    );
+    let id = root.uuid;
+    let mut rows = vec![root];
+
+    if let Some((parent_id, parent_name)) = parent {
+        rows.push(NodeModel::relation(
+            id,
+            ingestion_id.to_owned(),
+            Direction::In.as_string(),
+            Role::Parent.as_string(),
+            parent_id.to_string(),
+            parent_name.to_owned(),
+        ));
+    }
+    rows
+}
+
+pub fn child_row(
+    ingestion_id: &str,
+    parent_id: Uuid,
+    child_id: Uuid,
+    child_name: &str,
+) -> NodeModel {
+    NodeModel::relation(
+        parent_id,
+        ingestion_id.to_owned(),
+        Direction::Out.as_string(),
+        Role::Child.as_string(),
+        child_id.to_string(),
+        child_name.to_owned(),
+    )
+}
+
+pub fn relation_rows(
+    ingestion_id: &str,
+    relations: &HashMap<String, Vec<Relation>>,
+) -> Vec<NodeModel> {
+    // Sorted so the rows of a file always come out in the same order, which
+    // resuming from a row offset relies on.
+    let mut paths: Vec<&String> = relations.keys().collect();
+    paths.sort();
+    let mut rows = Vec::new();
+    for path in paths {
+        let rels = &relations[path];
+        let id = path_to_uuid(ingestion_id, path);
+        for r in rels {
+            rows.push(NodeModel::from_relation(id, ingestion_id.to_owned(), r));
+        }
+    }
+    rows
+}
+
+/// Builds the relation rows whose endpoint is not one of the flattened
+/// `nodes`, e.g. edges shipped in a separate file from the nodes they connect.
+pub fn detached_relation_rows(
+    ingestion_id: &str,
+    nodes: &[NodeModel],
+    relations: &HashMap<String, Vec<Relation>>,
+) -> Vec<NodeModel> {
+    let paths: HashSet<&str> = nodes
+        .iter()
+        .filter(|node| node.direction.is_none())
+        .map(|node| node.path.as_str())
+        .collect();
+    let detached: HashMap<String, Vec<Relation>> = relations
+        .iter()
+        .filter(|(path, _)| !paths.contains(path.as_str()))
+        .map(|(path, rels)| (path.clone(), rels.clone()))
+        .collect();
+    relation_rows(ingestion_id, &detached)
+}
+
 fn flatten_nodes(
     ingestion_id: &str,
     raw_nodes: &Vec<RawNode>,
-    path: &String,
+    path: &str,
     parent: &Option<(Uuid, String)>,
     nodes: &mut Vec<NodeModel>,
     relations: &HashMap<String, Vec<Relation>>,
 ) -> eyre::Result<()> {
     for raw_node in raw_nodes {
-        let empty = Vec::new();
-        let tags = raw_node.tags.as_ref().get_or_insert(&empty).clone();
         let path = path_from_name(path, &raw_node.name);
-        let root = NodeModel::root(
-            ingestion_id.to_owned(),
-            path.clone(),
-            raw_node.name.clone(),
-            raw_node.type_field.clone(),
-            tags.to_vec(),
-        );
-
-        let id = root.uuid;
-        let name = root.name.clone();
-        nodes.push(root);
-
-        if let Some(parent) = parent {
-            let (parent_id, parent_name) = parent;
-            let relation = NodeModel::relation(
-                id,
-                ingestion_id.to_owned(),
-                Direction::In.as_string(),
-                Role::Parent.as_string(),
-                parent_id.to_string(),
-                parent_name.to_owned(),
-            );
-            nodes.push(relation);
-        }
+        let id = path_to_uuid(ingestion_id, &path);
+        let name = raw_node.name.clone();
+        nodes.extend(node_rows(ingestion_id, &path, raw_node, parent));
 
         let empty_rel = &mut Vec::new();
         for r in relations.get(&path).get_or_insert(empty_rel).iter() {
@@ -221,16 +359,8 @@ fn flatten_nodes(
 
         for c in &raw_node.children {
             let child_path = path_from_name(&path, &c.name);
-            let child_id = path_to_uuid(&ingestion_id, &child_path);
-            let rel = NodeModel::relation(
-                id,
-                ingestion_id.to_owned(),
-                Direction::Out.as_string(),
-                Role::Child.as_string(),
-                child_id.to_string(),
-                c.name.clone(),
-            );
-            nodes.push(rel);
+            let child_id = path_to_uuid(ingestion_id, &child_path);
+            nodes.push(child_row(ingestion_id, id, child_id, &c.name));
         }
 
         if !raw_node.children.is_empty() {
@@ -251,8 +381,13 @@ fn flatten_nodes(
 
 #[cfg(test)]
 mod tests {
-    use super::{extract_tag_pairs, flatten_nodes};
-    use crate::domain::s3::data::{RawNode, RawTag};
+    use super::{
+        detached_relation_rows, extract_tag_pairs, flatten_nodes, path_to_uuid, process_nodes,
+    };
+    use crate::domain::{
+        relation::{process_relations, Relation},
+        s3::data::{RawNode, RawRelation, RawTag},
+    };
     use std::collections::HashMap;
 
     #[test]
@@ -314,4 +449,89 @@ mod tests {
         println!("nodes={:#?}", nodes);
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_process_nodes_continuation() {
+        let raw_nodes = vec![RawNode {
+            name: "b".to_string(),
+            type_field: "type1".to_string(),
+            children: vec![],
+            tags: None,
+            total_children: Some(4),
+        }];
+        let parent = vec!["root".to_string(), "a".to_string()];
+        let rows = process_nodes("test", &parent, raw_nodes, HashMap::new())
+            .await
+            .unwrap();
+
+        let parent_id = path_to_uuid("test", "/root/a");
+        let child_id = path_to_uuid("test", "/root/a/b");
+        assert_eq!(rows.len(), 3);
+        assert!(rows
+            .iter()
+            .all(|row| row.uuid != parent_id || row.direction.is_some()));
+        let child_edge = rows.iter().find(|row| row.uuid == parent_id).unwrap();
+        assert_eq!(child_edge.relation.as_deref(), Some("Child"));
+        assert_eq!(child_edge.relates_to, Some(child_id.to_string()));
+        let node = rows
+            .iter()
+            .find(|row| row.uuid == child_id && row.direction.is_none())
+            .unwrap();
+        assert_eq!(node.path, "/root/a/b");
+        assert_eq!(node.total_children, Some(4));
+    }
+
+    #[test]
+    fn test_relations_attach_to_their_nodes() {
+        let raw_nodes = vec![RawNode {
+            name: "a/b".to_string(),
+            type_field: "type1".to_string(),
+            children: vec![],
+            tags: None,
+            total_children: None,
+        }];
+        let relations = process_relations(
+            "test",
+            vec![RawRelation {
+                type_field: "uses".to_string(),
+                source: vec!["a/b".to_string()],
+                target: vec!["a/b".to_string()],
+                tags: None,
+            }],
+        );
+        let mut nodes = vec![];
+        flatten_nodes("test", &raw_nodes, "", &None, &mut nodes, &relations).unwrap();
+
+        assert!(detached_relation_rows("test", &nodes, &relations).is_empty());
+        let id = path_to_uuid("test", "/a\\/b");
+        assert!(nodes.iter().all(|row| row.uuid == id));
+        assert_eq!(nodes.len(), 3);
+    }
+
+    #[test]
+    fn test_detached_relation_rows() {
+        let raw_nodes = vec![RawNode {
+            name: "node1".to_string(),
+            type_field: "type1".to_string(),
+            children: vec![],
+            tags: None,
+            total_children: None,
+        }];
+        let mut nodes = vec![];
+        flatten_nodes("test", &raw_nodes, "", &None, &mut nodes, &HashMap::new()).unwrap();
+
+        let relation = Relation::from(
+            "other".to_string(),
+            "uses".to_string(),
+            "id".to_string(),
+            true,
+        );
+        let mut relations = HashMap::new();
+        relations.insert("/node1".to_string(), vec![relation.clone()]);
+        relations.insert("/node2".to_string(), vec![relation]);
+
+        let rows = detached_relation_rows("test", &nodes, &relations);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].uuid, path_to_uuid("test", "/node2"));
+    }
 }
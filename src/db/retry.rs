@@ -0,0 +1,130 @@
+use crate::config::config::DatabaseConfig;
+use eyre::{eyre, Result};
+use rand::Rng;
+use scylla::transport::errors::{DbError, QueryError};
+use std::{collections::HashSet, time::Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    Overloaded,
+    Timeout,
+    Unavailable,
+    Io,
+    Other,
+}
+
+impl ErrorKind {
+    pub fn of(err: &QueryError) -> ErrorKind {
+        match err {
+            QueryError::DbError(DbError::Overloaded, _) => ErrorKind::Overloaded,
+            QueryError::DbError(DbError::WriteTimeout { .. } | DbError::ReadTimeout { .. }, _)
+            | QueryError::TimeoutError
+            | QueryError::RequestTimeout(_) => ErrorKind::Timeout,
+            QueryError::DbError(DbError::Unavailable { .. }, _) => ErrorKind::Unavailable,
+            QueryError::IoError(_)
+            | QueryError::TooManyOrphanedStreamIds(_)
+            | QueryError::UnableToAllocStreamId => ErrorKind::Io,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    pub fn parse(kind: &str) -> Result<ErrorKind> {
+        match kind.trim().to_lowercase().as_str() {
+            "overloaded" => Ok(ErrorKind::Overloaded),
+            "timeout" => Ok(ErrorKind::Timeout),
+            "unavailable" => Ok(ErrorKind::Unavailable),
+            "io" => Ok(ErrorKind::Io),
+            "other" => Ok(ErrorKind::Other),
+            other => Err(eyre!("Unknown retryable error kind '{}'", other)),
+        }
+    }
+}
+
+/// How failed writes are retried: up to `max_attempts` tries in total, with
+/// an exponential backoff capped at `max_delay` and full jitter so that
+/// concurrent writers do not retry in lockstep.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: usize,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub retryable: HashSet<ErrorKind>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            retryable: HashSet::from([
+                ErrorKind::Overloaded,
+                ErrorKind::Timeout,
+                ErrorKind::Unavailable,
+                ErrorKind::Io,
+            ]),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &DatabaseConfig) -> Result<RetryPolicy> {
+        let retryable = config
+            .retry_on
+            .split(',')
+            .filter(|kind| !kind.trim().is_empty())
+            .map(ErrorKind::parse)
+            .collect::<Result<HashSet<_>>>()?;
+        Ok(RetryPolicy {
+            max_attempts: config.retry_max_attempts.max(1),
+            base_delay: Duration::from_millis(config.retry_base_delay_ms),
+            max_delay: Duration::from_millis(config.retry_max_delay_ms),
+            retryable,
+        })
+    }
+
+    /// Whether a write that failed on its `attempt`-th try (starting at 1)
+    /// should be tried again.
+    pub fn should_retry(&self, attempt: usize, err: &QueryError) -> bool {
+        attempt < self.max_attempts && self.retryable.contains(&ErrorKind::of(err))
+    }
+
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        backoff(self.base_delay, self.max_delay, attempt)
+    }
+}
+
+/// Delay before retrying after the `attempt`-th try (starting at 1): a random
+/// share of `base * 2^(attempt - 1)`, capped at `max`.
+pub fn backoff(base: Duration, max: Duration, attempt: usize) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16) as u32;
+    let ceiling = base.saturating_mul(1 << exponent).min(max);
+    ceiling.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::default();
+        let timeout = QueryError::TimeoutError;
+        let syntax = QueryError::DbError(DbError::SyntaxError, String::new());
+        assert!(policy.should_retry(1, &timeout));
+        assert!(policy.should_retry(2, &timeout));
+        assert!(!policy.should_retry(3, &timeout));
+        assert!(!policy.should_retry(1, &syntax));
+
+        for attempt in 1..10 {
+            assert!(policy.backoff(attempt) <= policy.max_delay);
+        }
+        assert!(policy.backoff(1) <= policy.base_delay);
+    }
+
+    #[test]
+    fn test_parse_error_kind() {
+        assert_eq!(ErrorKind::parse(" Timeout ").unwrap(), ErrorKind::Timeout);
+        assert!(ErrorKind::parse("syntax").is_err());
+    }
+}
@@ -1,26 +1,174 @@
-use super::model::{NodeModel, RelationModel};
+use super::dead_letter::{self, DeadLetterSink, FailedRow};
+use super::model::{CheckpointModel, IngestionModel, NodeModel, RelationModel, WatchedObjectModel};
+use super::retry::RetryPolicy;
+use super::throttle::Throttle;
 use crate::config::config::DatabaseConfig;
+use crate::domain::diff::RowKey;
+use chrono::{TimeZone, Utc};
 use eyre::{eyre, Result};
+use futures::{stream, Stream, StreamExt, TryStreamExt};
 use scylla::{
-    frame::Compression, prepared_statement::PreparedStatement,
-    transport::query_result::SingleRowTypedError, Session, SessionBuilder,
+    batch::{Batch, BatchType},
+    frame::{
+        value::{MaybeUnset, Timestamp, ValueList},
+        Compression,
+    },
+    prepared_statement::PreparedStatement,
+    transport::{
+        errors::{DbError, QueryError},
+        query_result::SingleRowTypedError,
+    },
+    QueryResult, Session, SessionBuilder,
 };
-use std::{collections::HashMap, fs, path::Path, sync::Arc, time::Duration};
-use tracing::{error, info};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    future::Future,
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
-const INSERT_NODE_QUERY: &str = "INSERT INTO graph.nodes (id, direction, relation, relates_to, name, ingestion_id, url, item_type, tags) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)";
+const INSERT_NODE_QUERY: &str = "INSERT INTO graph.nodes (id, direction, relation, relates_to, name, ingestion_id, url, item_type, tags, total_children) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+const INSERT_INGESTION_NODE_QUERY: &str =
+    "INSERT INTO graph.ingestion_nodes (ingestion_id, id) VALUES (?, ?)";
+const GET_INGESTION_NODES: &str = "SELECT id FROM graph.ingestion_nodes WHERE ingestion_id = ?";
+const DELETE_NODE_QUERY: &str = "DELETE FROM graph.nodes WHERE id = ?";
+const NODE_EXISTS_QUERY: &str = "SELECT id FROM graph.nodes WHERE id = ? LIMIT 1";
+const DELETE_ROW_QUERY: &str =
+    "DELETE FROM graph.nodes WHERE id = ? AND direction = ? AND relation = ? AND relates_to = ?";
+const DELETE_INGESTION_NODE_QUERY: &str =
+    "DELETE FROM graph.ingestion_nodes WHERE ingestion_id = ? AND id = ?";
+const DELETE_INGESTION_NODES_QUERY: &str =
+    "DELETE FROM graph.ingestion_nodes WHERE ingestion_id = ?";
+const UPSERT_INGESTION_QUERY: &str = "INSERT INTO graph.ingestions (id, job_id, files, started_at, finished_at, status, nodes, relations, rows_written, rows_failed, service_version) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+const GET_INGESTION: &str = "SELECT id, job_id, files, started_at, finished_at, status, nodes, relations, rows_written, rows_failed, service_version FROM graph.ingestions WHERE id = ?";
+const LIST_INGESTIONS: &str = "SELECT id, job_id, files, started_at, finished_at, status, nodes, relations, rows_written, rows_failed, service_version FROM graph.ingestions";
+const INSERT_FAILED_ROW_QUERY: &str = "INSERT INTO graph.failed_rows (ingestion_id, id, node_id, row, error, attempts, failed_at) VALUES (?, ?, ?, ?, ?, ?, ?)";
+const GET_FAILED_ROWS: &str =
+    "SELECT id, row, error, attempts, failed_at FROM graph.failed_rows WHERE ingestion_id = ?";
+const DELETE_FAILED_ROW_QUERY: &str =
+    "DELETE FROM graph.failed_rows WHERE ingestion_id = ? AND id = ?";
+const SAVE_CHECKPOINT_REQUEST_QUERY: &str =
+    "INSERT INTO graph.ingestion_checkpoints (job_id, request) VALUES (?, ?)";
+const SAVE_CHECKPOINT_QUERY: &str = "INSERT INTO graph.ingestion_checkpoints (job_id, file_index, file, rows_committed, completed, updated_at) VALUES (?, ?, ?, ?, ?, ?)";
+const GET_CHECKPOINTS: &str = "SELECT request, file_index, file, rows_committed, completed FROM graph.ingestion_checkpoints WHERE job_id = ?";
+const SAVE_WATCHED_OBJECT_QUERY: &str = "INSERT INTO graph.watched_objects (source, key, etag, ingestion_id, job_id, processed_at) VALUES (?, ?, ?, ?, ?, ?)";
+const GET_WATCHED_OBJECTS: &str = "SELECT key, etag FROM graph.watched_objects WHERE source = ?";
+const INSERT_INGESTION_JOB_QUERY: &str =
+    "INSERT INTO graph.ingestion_jobs (ingestion_id, job_id) VALUES (?, ?)";
+const GET_INGESTION_JOBS: &str = "SELECT job_id FROM graph.ingestion_jobs WHERE ingestion_id = ?";
+const DELETE_INGESTION_JOBS_QUERY: &str = "DELETE FROM graph.ingestion_jobs WHERE ingestion_id = ?";
+const DELETE_CHECKPOINTS_QUERY: &str = "DELETE FROM graph.ingestion_checkpoints WHERE job_id = ?";
+const GET_INGESTION_WATCHED_OBJECTS: &str =
+    "SELECT source, key FROM graph.watched_objects WHERE ingestion_id = ?";
+const DELETE_WATCHED_OBJECT_QUERY: &str =
+    "DELETE FROM graph.watched_objects WHERE source = ? AND key = ?";
+const DELETE_FAILED_ROWS_QUERY: &str = "DELETE FROM graph.failed_rows WHERE ingestion_id = ?";
+const DELETE_INGESTION_QUERY: &str = "DELETE FROM graph.ingestions WHERE id = ?";
 const GET_NODE_BY_ID: &str = "SELECT id, name, item_type, url, ingestion_id FROM graph.nodes WHERE id = ? AND direction = '' AND relation = ''";
-const GET_NODE_BY_ID_WITH_TAGS: &str = "SELECT id, direction, relation, relates_to, name, ingestion_id, url, item_type, tags FROM graph.nodes WHERE id = ? AND direction = '' AND relation = ''";
-const GET_NODE_BY_ID_WITH_ALL: &str = "SELECT id, direction, relation, relates_to, name, ingestion_id, url, item_type, tags FROM graph.nodes WHERE id = ?";
-const GET_NODE_BY_ID_AND_DIRECTION: &str = "SELECT id, direction, relation, relates_to, name, item_type FROM graph.nodes WHERE id = ? AND direction IN ('', ?)";
-const GET_NODE_BY_ID_DIRECTION_AND_RELATION: &str = "SELECT id, direction, relation, relates_to, name, item_type FROM graph.nodes WHERE id = ? AND direction IN ('', ?) AND relation IN ('', ?)";
+const GET_NODE_BY_ID_WITH_TAGS: &str = "SELECT id, direction, relation, relates_to, name, ingestion_id, url, item_type, tags, total_children FROM graph.nodes WHERE id = ? AND direction = '' AND relation = ''";
+const GET_NODE_BY_ID_WITH_ALL: &str = "SELECT id, direction, relation, relates_to, name, ingestion_id, url, item_type, tags, total_children FROM graph.nodes WHERE id = ?";
+const GET_NODE_BY_ID_AND_DIRECTION: &str = "SELECT id, direction, relation, relates_to, name, item_type, tags, total_children FROM graph.nodes WHERE id = ? AND direction IN ('', ?)";
+const GET_NODE_BY_ID_DIRECTION_AND_RELATION: &str = "SELECT id, direction, relation, relates_to, name, item_type, tags, total_children FROM graph.nodes WHERE id = ? AND direction IN ('', ?) AND relation IN ('', ?)";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InsertSummary {
+    pub rows_written: usize,
+    pub rows_failed: usize,
+    /// Failed rows the dead-letter sink kept for replay.
+    pub rows_dead_lettered: usize,
+    /// Stored nodes whose `graph.ingestion_nodes` entry failed, so deleting
+    /// the ingestion would miss them. Their rows are not counted as failed.
+    pub nodes_unindexed: usize,
+    pub first_error: Option<String>,
+}
+
+impl InsertSummary {
+    fn record_failures(&mut self, rows: usize, error: String) {
+        self.rows_failed += rows;
+        self.first_error.get_or_insert(error);
+    }
+}
+
+/// Bind values of `INSERT_NODE_QUERY`.
+type NodeValues = (
+    Uuid,
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    Vec<(String, String)>,
+    MaybeUnset<Option<i64>>,
+);
+
+fn node_values(node: &NodeModel) -> NodeValues {
+    (
+        node.uuid,
+        node.direction.clone().unwrap_or_default(),
+        node.relation.clone().unwrap_or_default(),
+        node.relates_to.clone().unwrap_or_default(),
+        node.name.clone(),
+        node.ingestion_id.clone(),
+        node.path.clone(),
+        node.node_type.clone(),
+        node.tags.clone().unwrap_or_default(),
+        // Left unset on edge rows so they do not write a tombstone each.
+        if node.direction.as_deref().unwrap_or_default().is_empty() {
+            MaybeUnset::Set(node.total_children)
+        } else {
+            MaybeUnset::Unset
+        },
+    )
+}
+
+/// Groups rows by partition key, keeping their order, and splits partitions
+/// larger than `max_rows`. Each group can go out as one unlogged batch, which
+/// Scylla applies on a single replica set without coordination overhead.
+pub fn partition_batches<T, K, F>(rows: Vec<T>, max_rows: usize, key: F) -> Vec<Vec<T>>
+where
+    K: Eq + std::hash::Hash,
+    F: Fn(&T) -> K,
+{
+    let max_rows = max_rows.max(1);
+    let mut positions: HashMap<K, usize> = HashMap::new();
+    let mut batches: Vec<Vec<T>> = Vec::new();
+    for row in rows {
+        let position = positions.entry(key(&row)).or_insert(usize::MAX);
+        if *position == usize::MAX || batches[*position].len() >= max_rows {
+            *position = batches.len();
+            batches.push(Vec::new());
+        }
+        batches[*position].push(row);
+    }
+    batches
+}
+
+/// The schema upgrades tables created by older versions with
+/// `ALTER TABLE ... ADD`, which Scylla rejects once the column exists.
+fn is_existing_column(query: &str, err: &QueryError) -> bool {
+    query.starts_with("ALTER TABLE")
+        && matches!(
+            err,
+            QueryError::DbError(DbError::Invalid, message)
+                if message.contains("already exist") || message.contains("conflicts with an existing column")
+        )
+}
 
 #[derive(Debug)]
 pub struct ScyllaService {
     pub client: Arc<Session>,
     pub prepared_statements: HashMap<String, PreparedStatement>,
     pub concurrency_limit: usize,
+    pub throttle: Throttle,
+    pub max_batch_rows: usize,
+    pub retry: RetryPolicy,
+    pub dead_letter: DeadLetterSink,
 }
 
 impl ScyllaService {
@@ -39,11 +187,19 @@ impl ScyllaService {
         info!("Scylla service: Loading initial schema successfully.");
         let prepared_statements = ScyllaService::create_prepared_statements(&session).await?;
         info!("Scylla service: All prepared statements are created.");
-        Ok(ScyllaService {
+        let service = ScyllaService {
             client: Arc::new(session),
             prepared_statements,
             concurrency_limit: config.concurrency_limit,
-        })
+            throttle: Throttle::new(config.concurrency_limit),
+            max_batch_rows: config.max_batch_rows,
+            retry: RetryPolicy::from_config(config)?,
+            dead_letter: DeadLetterSink::parse(config.dead_letter.as_deref()),
+        };
+        if let DeadLetterSink::Discard = service.dead_letter {
+            warn!("DEAD_LETTER is not set: rows that exhaust their retries are discarded");
+        }
+        Ok(service)
     }
 
     async fn read_queries_from_schema<P>(schema_path: P) -> Result<Vec<String>>
@@ -62,7 +218,12 @@ impl ScyllaService {
 
     async fn run_queries(session: &Session, queries: Vec<String>) -> Result<()> {
         for query in queries {
-            session.query(query, &[]).await?;
+            match session.query(query.as_str(), &[]).await {
+                Err(err) if is_existing_column(&query, &err) => {}
+                other => {
+                    other?;
+                }
+            }
         }
         Ok(())
     }
@@ -104,50 +265,291 @@ impl ScyllaService {
             get_node_direction_and_relation_prepared_statement,
         );
 
+        for (name, query) in [
+            ("INSERT_INGESTION_NODE_QUERY", INSERT_INGESTION_NODE_QUERY),
+            ("GET_INGESTION_NODES", GET_INGESTION_NODES),
+            ("DELETE_NODE_QUERY", DELETE_NODE_QUERY),
+            ("NODE_EXISTS_QUERY", NODE_EXISTS_QUERY),
+            ("DELETE_ROW_QUERY", DELETE_ROW_QUERY),
+            ("DELETE_INGESTION_NODE_QUERY", DELETE_INGESTION_NODE_QUERY),
+            ("DELETE_INGESTION_NODES_QUERY", DELETE_INGESTION_NODES_QUERY),
+            ("UPSERT_INGESTION_QUERY", UPSERT_INGESTION_QUERY),
+            ("GET_INGESTION", GET_INGESTION),
+            ("LIST_INGESTIONS", LIST_INGESTIONS),
+            ("INSERT_FAILED_ROW_QUERY", INSERT_FAILED_ROW_QUERY),
+            ("GET_FAILED_ROWS", GET_FAILED_ROWS),
+            ("DELETE_FAILED_ROW_QUERY", DELETE_FAILED_ROW_QUERY),
+            (
+                "SAVE_CHECKPOINT_REQUEST_QUERY",
+                SAVE_CHECKPOINT_REQUEST_QUERY,
+            ),
+            ("SAVE_CHECKPOINT_QUERY", SAVE_CHECKPOINT_QUERY),
+            ("GET_CHECKPOINTS", GET_CHECKPOINTS),
+            ("SAVE_WATCHED_OBJECT_QUERY", SAVE_WATCHED_OBJECT_QUERY),
+            ("GET_WATCHED_OBJECTS", GET_WATCHED_OBJECTS),
+            ("INSERT_INGESTION_JOB_QUERY", INSERT_INGESTION_JOB_QUERY),
+            ("GET_INGESTION_JOBS", GET_INGESTION_JOBS),
+            ("DELETE_INGESTION_JOBS_QUERY", DELETE_INGESTION_JOBS_QUERY),
+            ("DELETE_CHECKPOINTS_QUERY", DELETE_CHECKPOINTS_QUERY),
+            (
+                "GET_INGESTION_WATCHED_OBJECTS",
+                GET_INGESTION_WATCHED_OBJECTS,
+            ),
+            ("DELETE_WATCHED_OBJECT_QUERY", DELETE_WATCHED_OBJECT_QUERY),
+            ("DELETE_FAILED_ROWS_QUERY", DELETE_FAILED_ROWS_QUERY),
+            ("DELETE_INGESTION_QUERY", DELETE_INGESTION_QUERY),
+        ] {
+            prepared_statements.insert(name.to_lowercase(), session.prepare(query).await?);
+        }
+
         Ok(prepared_statements)
     }
 
-    pub async fn insert_nodes(&self, nodes: Vec<NodeModel>) -> Result<()> {
-        let mut handles = vec![];
-        let ps = self
-            .prepared_statements
-            .get("insert_node_query")
-            .ok_or_else(|| eyre!("insert node prepared statement not found"))?;
+    fn prepared(&self, name: &str) -> Result<&PreparedStatement> {
+        self.prepared_statements
+            .get(&name.to_lowercase())
+            .ok_or_else(|| eyre!("{} prepared statement not found", name))
+    }
 
-        for node in nodes {
-            let session = self.client.clone();
-            let ps = ps.clone();
-
-            let handle = tokio::spawn(async move {
-                session
-                    .execute(
-                        &ps,
-                        (
-                            node.uuid,
-                            node.direction.unwrap_or_default(),
-                            node.relation.unwrap_or_default(),
-                            node.relates_to.unwrap_or_default(),
-                            node.name,
-                            node.ingestion_id,
-                            node.path,
-                            node.node_type,
-                            node.tags.unwrap_or_default(),
-                        ),
-                    )
-                    .await
-            });
-            handles.push(handle);
+    /// Runs `op` until it succeeds or the retry policy gives up, sleeping
+    /// with backoff in between. On failure the attempts made are returned
+    /// with the last error.
+    async fn retrying<F, Fut>(&self, op: F) -> Result<QueryResult, (QueryError, usize)>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<QueryResult, QueryError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Ok(result) => return Ok(result),
+                Err(err) if self.retry.should_retry(attempt, &err) => {
+                    let delay = self.retry.backoff(attempt);
+                    warn!(
+                        "Statement failed on attempt {}, retrying in {:?}: {}",
+                        attempt, delay, err
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err((err, attempt)),
+            }
         }
+    }
+
+    /// Runs a write under the adaptive throttle, which bounds how many
+    /// statements are in flight across all callers.
+    async fn write(
+        &self,
+        ps: &PreparedStatement,
+        values: impl ValueList,
+    ) -> Result<QueryResult, QueryError> {
+        let values = values.serialized()?;
+        self.retrying(|| async {
+            let permit = self.throttle.acquire().await;
+            let result = self.client.execute(ps, &values).await;
+            self.throttle.release(permit, &result);
+            result
+        })
+        .await
+        .map_err(|(err, _)| err)
+    }
 
-        for handle in handles {
-            let res = handle.await?;
-            match res {
-                Ok(_) => info!("Node inserted successfully"),
-                Err(err) => error!("Error inserting node: {}", err),
+    /// Sends the values as one unlogged batch of `ps`, under the throttle.
+    async fn write_batch<T: ValueList>(
+        &self,
+        ps: &PreparedStatement,
+        values: &[T],
+    ) -> Result<QueryResult, (QueryError, usize)> {
+        let mut batch = Batch::new(BatchType::Unlogged);
+        for _ in 0..values.len() {
+            batch.append_statement(ps.clone());
+        }
+        self.retrying(|| async {
+            let permit = self.throttle.acquire().await;
+            let result = self.client.batch(&batch, values).await;
+            self.throttle.release(permit, &result);
+            result
+        })
+        .await
+    }
+
+    /// Writes the rows and records every node id in `graph.ingestion_nodes`
+    /// so the ingestion can later be deleted without a table scan. Failed
+    /// lookup writes count per node in `nodes_unindexed`. Rows go out as one batch per
+    /// partition, capped at `max_batch_rows`; batches that still fail after
+    /// the retries are handed to the dead-letter sink.
+    pub async fn insert_nodes(&self, nodes: Vec<NodeModel>) -> Result<InsertSummary> {
+        let ps = self.prepared("INSERT_NODE_QUERY")?;
+        let index_ps = self.prepared("INSERT_INGESTION_NODE_QUERY")?;
+        let concurrency = self.throttle.max();
+
+        // Only partitions with at least one written row are indexed, so the
+        // index never points at a node that was not stored.
+        let mut ids: HashSet<(String, Uuid)> = HashSet::new();
+        let mut summary = InsertSummary::default();
+        let mut failed = Vec::new();
+        let batches = partition_batches(nodes, self.max_batch_rows, |node| node.uuid);
+        let mut results = stream::iter(batches)
+            .map(|batch| async move {
+                let values: Vec<_> = batch.iter().map(node_values).collect();
+                let result = self.write_batch(ps, &values).await;
+                (batch, result)
+            })
+            .buffer_unordered(concurrency);
+        while let Some((batch, result)) = results.next().await {
+            match result {
+                Ok(_) => {
+                    summary.rows_written += batch.len();
+                    ids.extend(batch.iter().map(|row| (row.ingestion_id.clone(), row.uuid)));
+                }
+                Err((err, attempts)) => {
+                    error!(
+                        "Error inserting {} rows after {} attempts: {}",
+                        batch.len(),
+                        attempts,
+                        err
+                    );
+                    summary.record_failures(batch.len(), err.to_string());
+                    let failed_at = Utc::now();
+                    failed.extend(batch.into_iter().map(|row| FailedRow {
+                        row,
+                        error: err.to_string(),
+                        attempts,
+                        failed_at,
+                    }));
+                }
             }
         }
-        Ok(())
+        drop(results);
+
+        let ids: Vec<_> = ids.into_iter().collect();
+        let batches = partition_batches(ids, self.max_batch_rows, |id| id.0.clone());
+        let mut results = stream::iter(batches)
+            .map(|batch| async move {
+                let result = self.write_batch(index_ps, &batch).await;
+                (batch.len(), result)
+            })
+            .buffer_unordered(concurrency);
+        while let Some((nodes, result)) = results.next().await {
+            if let Err((err, _)) = result {
+                error!("Error indexing {} nodes: {}", nodes, err);
+                summary.nodes_unindexed += nodes;
+                summary.first_error.get_or_insert(err.to_string());
+            }
+        }
+        drop(results);
+
+        if !failed.is_empty() {
+            match self.dead_letter(&failed).await {
+                Ok(rows) => summary.rows_dead_lettered = rows,
+                Err(err) => error!("Error dead-lettering {} rows: {}", failed.len(), err),
+            }
+        }
+        info!(
+            "Inserted {} rows, {} rows failed, {} nodes unindexed",
+            summary.rows_written, summary.rows_failed, summary.nodes_unindexed
+        );
+        Ok(summary)
     }
+
+    /// Hands rows that exhausted their retries to the configured sink and
+    /// returns how many were kept for replay.
+    pub async fn dead_letter(&self, rows: &[FailedRow]) -> Result<usize> {
+        match &self.dead_letter {
+            DeadLetterSink::Discard => Ok(0),
+            DeadLetterSink::File { path, lock } => {
+                dead_letter::append_file(path, lock, rows).await?;
+                Ok(rows.len())
+            }
+            DeadLetterSink::Table => {
+                let ps = self.prepared("INSERT_FAILED_ROW_QUERY")?;
+                let mut values = Vec::with_capacity(rows.len());
+                for failed in rows {
+                    values.push((
+                        failed.row.ingestion_id.clone(),
+                        Uuid::new_v4(),
+                        failed.row.uuid,
+                        serde_json::to_string(&failed.row)?,
+                        failed.error.clone(),
+                        failed.attempts as i32,
+                        Timestamp(chrono::Duration::milliseconds(
+                            failed.failed_at.timestamp_millis(),
+                        )),
+                    ));
+                }
+                let batches = partition_batches(values, self.max_batch_rows, |v| v.0.clone());
+                for batch in batches {
+                    self.write_batch(ps, &batch).await.map_err(|(err, _)| err)?;
+                }
+                Ok(rows.len())
+            }
+        }
+    }
+
+    pub async fn get_failed_rows(&self, ingestion_id: &str) -> Result<Vec<FailedRow>> {
+        let rows = self.get_failed_rows_with_ids(ingestion_id).await?;
+        Ok(rows.into_iter().map(|(_, failed)| failed).collect())
+    }
+
+    async fn get_failed_rows_with_ids(&self, ingestion_id: &str) -> Result<Vec<(Uuid, FailedRow)>> {
+        let ps = self.prepared("GET_FAILED_ROWS")?;
+        let rows = self
+            .client
+            .execute_iter(ps.clone(), (ingestion_id,))
+            .await?
+            .into_typed::<(Uuid, String, String, i32, Timestamp)>()
+            .try_collect::<Vec<_>>()
+            .await?;
+        rows.into_iter()
+            .map(|(id, row, error, attempts, failed_at)| {
+                let failed = FailedRow {
+                    row: serde_json::from_str(&row)?,
+                    error,
+                    attempts: attempts as usize,
+                    failed_at: Utc
+                        .timestamp_millis_opt(failed_at.0.num_milliseconds())
+                        .single()
+                        .unwrap_or_default(),
+                };
+                Ok((id, failed))
+            })
+            .collect()
+    }
+
+    /// Writes the dead-lettered rows of an ingestion again and takes them
+    /// out of the sink. Rows that fail once more are dead-lettered anew.
+    pub async fn replay_failed_rows(&self, ingestion_id: &str) -> Result<InsertSummary> {
+        match &self.dead_letter {
+            DeadLetterSink::Discard => Err(eyre!("No dead-letter sink is configured")),
+            DeadLetterSink::File { path, lock } => {
+                let failed = dead_letter::take_file(path, lock, ingestion_id).await?;
+                let rows = failed.iter().map(|failed| failed.row.clone()).collect();
+                match self.insert_nodes(rows).await {
+                    Ok(summary) => Ok(summary),
+                    Err(err) => {
+                        // Put the rows back so a later replay finds them.
+                        dead_letter::append_file(path, lock, &failed).await?;
+                        Err(err)
+                    }
+                }
+            }
+            DeadLetterSink::Table => {
+                let failed = self.get_failed_rows_with_ids(ingestion_id).await?;
+                let (ids, rows): (Vec<Uuid>, Vec<NodeModel>) = failed
+                    .into_iter()
+                    .map(|(id, failed)| (id, failed.row))
+                    .unzip();
+                let summary = self.insert_nodes(rows).await?;
+                let ps = self.prepared("DELETE_FAILED_ROW_QUERY")?;
+                for id in ids {
+                    self.write(ps, (ingestion_id, id)).await?;
+                }
+                Ok(summary)
+            }
+        }
+    }
+
     pub async fn insert_node(&self, node: NodeModel) -> Result<()> {
         let ps = self
             .prepared_statements
@@ -156,7 +558,7 @@ impl ScyllaService {
         let _res = self
             .client
             .execute(
-                &ps,
+                ps,
                 (
                     node.uuid,
                     node.direction,
@@ -167,6 +569,7 @@ impl ScyllaService {
                     node.path,
                     node.node_type,
                     node.tags,
+                    node.total_children,
                 ),
             )
             .await?;
@@ -182,6 +585,254 @@ impl ScyllaService {
         node
     }
 
+    pub async fn get_ingestion_node_ids(&self, ingestion_id: &str) -> Result<Vec<Uuid>> {
+        let ps = self.prepared("GET_INGESTION_NODES")?;
+        let rows = self
+            .client
+            .execute_iter(ps.clone(), (ingestion_id,))
+            .await?
+            .into_typed::<(Uuid,)>();
+        let ids = rows.map_ok(|(id,)| id).try_collect::<Vec<_>>().await?;
+        Ok(ids)
+    }
+
+    /// Whether any row of the node partition is stored.
+    pub async fn node_exists(&self, uuid: Uuid) -> Result<bool> {
+        let ps = self.prepared("NODE_EXISTS_QUERY")?;
+        let res = self.client.execute(ps, (uuid,)).await?;
+        Ok(res.rows_num()? > 0)
+    }
+
+    pub async fn delete_node(&self, uuid: Uuid) -> Result<()> {
+        let ps = self.prepared("DELETE_NODE_QUERY")?;
+        self.write(ps, (uuid,)).await?;
+        Ok(())
+    }
+
+    /// Removes a node partition and its entry in the ingestion lookup.
+    pub async fn delete_ingestion_node(&self, ingestion_id: &str, uuid: Uuid) -> Result<()> {
+        self.delete_node(uuid).await?;
+        let ps = self.prepared("DELETE_INGESTION_NODE_QUERY")?;
+        self.write(ps, (ingestion_id, uuid)).await?;
+        Ok(())
+    }
+
+    pub async fn delete_row(&self, key: RowKey) -> Result<()> {
+        let ps = self.prepared("DELETE_ROW_QUERY")?;
+        self.write(ps, key).await?;
+        Ok(())
+    }
+
+    /// Streams the stored rows of an ingestion one node partition at a
+    /// time, paging through the node id lookup, so only the partitions in
+    /// flight are held.
+    pub async fn ingestion_partitions(
+        &self,
+        ingestion_id: &str,
+    ) -> Result<impl Stream<Item = Result<Vec<NodeModel>>> + '_> {
+        let ps = self.prepared("GET_INGESTION_NODES")?;
+        let ids = self
+            .client
+            .execute_iter(ps.clone(), (ingestion_id,))
+            .await?
+            .into_typed::<(Uuid,)>();
+        Ok(ids
+            .map_err(eyre::Report::from)
+            .map_ok(|(id,)| self.get_node_rows(id))
+            .try_buffer_unordered(self.concurrency_limit.max(1)))
+    }
+
+    pub async fn delete_ingestion_index(&self, ingestion_id: &str) -> Result<()> {
+        let ps = self.prepared("DELETE_INGESTION_NODES_QUERY")?;
+        self.write(ps, (ingestion_id,)).await?;
+        Ok(())
+    }
+
+    /// Removes what an ingestion leaves besides its nodes: the registry
+    /// row, the checkpoints of its jobs, its watched objects and its
+    /// dead-lettered rows. The job lookup goes last, so a failed run can be
+    /// retried.
+    pub async fn delete_ingestion_records(&self, ingestion_id: &str) -> Result<()> {
+        let ps = self.prepared("GET_INGESTION_JOBS")?;
+        let mut job_ids: HashSet<Uuid> = self
+            .client
+            .execute_iter(ps.clone(), (ingestion_id,))
+            .await?
+            .into_typed::<(Uuid,)>()
+            .map_ok(|(job_id,)| job_id)
+            .try_collect()
+            .await?;
+        // Jobs started before the lookup existed are only known by the
+        // registry row.
+        if let Some(ingestion) = self.get_ingestion(ingestion_id).await? {
+            job_ids.insert(ingestion.job_id);
+        }
+        let ps = self.prepared("DELETE_CHECKPOINTS_QUERY")?;
+        for job_id in job_ids {
+            self.write(ps, (job_id,)).await?;
+        }
+
+        let ps = self.prepared("GET_INGESTION_WATCHED_OBJECTS")?;
+        let objects: Vec<(String, String)> = self
+            .client
+            .execute_iter(ps.clone(), (ingestion_id,))
+            .await?
+            .into_typed::<(String, String)>()
+            .try_collect()
+            .await?;
+        let ps = self.prepared("DELETE_WATCHED_OBJECT_QUERY")?;
+        for object in objects {
+            self.write(ps, object).await?;
+        }
+
+        for name in [
+            "DELETE_FAILED_ROWS_QUERY",
+            "DELETE_INGESTION_QUERY",
+            "DELETE_INGESTION_JOBS_QUERY",
+        ] {
+            self.write(self.prepared(name)?, (ingestion_id,)).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn upsert_ingestion(&self, ingestion: IngestionModel) -> Result<()> {
+        let ps = self.prepared("UPSERT_INGESTION_QUERY")?;
+        self.write(
+            ps,
+            (
+                ingestion.id,
+                ingestion.job_id,
+                ingestion.files,
+                ingestion.started_at,
+                ingestion.finished_at,
+                ingestion.status,
+                ingestion.nodes,
+                ingestion.relations,
+                ingestion.rows_written,
+                ingestion.rows_failed,
+                ingestion.service_version,
+            ),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_ingestion(&self, id: &str) -> Result<Option<IngestionModel>> {
+        let ps = self.prepared("GET_INGESTION")?;
+        let res = self.client.execute(ps, (id,)).await?;
+        Ok(res.maybe_first_row_typed::<IngestionModel>()?)
+    }
+
+    pub async fn list_ingestions(&self) -> Result<Vec<IngestionModel>> {
+        let ps = self.prepared("LIST_INGESTIONS")?;
+        let rows = self
+            .client
+            .execute_iter(ps.clone(), ())
+            .await?
+            .into_typed::<IngestionModel>()
+            .try_collect::<Vec<_>>()
+            .await?;
+        Ok(rows)
+    }
+
+    /// Stores the request a job runs and lists the job under its ingestion,
+    /// so deleting the ingestion can find the checkpoints.
+    pub async fn save_checkpoint_request(
+        &self,
+        job_id: Uuid,
+        ingestion_id: &str,
+        request: String,
+    ) -> Result<()> {
+        let ps = self.prepared("INSERT_INGESTION_JOB_QUERY")?;
+        self.write(ps, (ingestion_id, job_id)).await?;
+        let ps = self.prepared("SAVE_CHECKPOINT_REQUEST_QUERY")?;
+        self.write(ps, (job_id, request)).await?;
+        Ok(())
+    }
+
+    pub async fn save_checkpoint(
+        &self,
+        job_id: Uuid,
+        file_index: usize,
+        file: &str,
+        rows_committed: usize,
+        completed: bool,
+    ) -> Result<()> {
+        let ps = self.prepared("SAVE_CHECKPOINT_QUERY")?;
+        let now = Timestamp(chrono::Duration::milliseconds(
+            Utc::now().timestamp_millis(),
+        ));
+        let values = (
+            job_id,
+            file_index as i32,
+            file,
+            rows_committed as i64,
+            completed,
+            now,
+        );
+        self.write(ps, values).await?;
+        Ok(())
+    }
+
+    pub async fn get_checkpoints(&self, job_id: Uuid) -> Result<Vec<CheckpointModel>> {
+        let ps = self.prepared("GET_CHECKPOINTS")?;
+        let rows = self
+            .client
+            .execute_iter(ps.clone(), (job_id,))
+            .await?
+            .into_typed::<CheckpointModel>()
+            .try_collect::<Vec<_>>()
+            .await?;
+        Ok(rows)
+    }
+
+    pub async fn save_watched_object(
+        &self,
+        source: &str,
+        key: &str,
+        etag: Option<&str>,
+        ingestion_id: &str,
+        job_id: Uuid,
+    ) -> Result<()> {
+        let ps = self.prepared("SAVE_WATCHED_OBJECT_QUERY")?;
+        let now = Timestamp(chrono::Duration::milliseconds(
+            Utc::now().timestamp_millis(),
+        ));
+        let values = (source, key, etag, ingestion_id, job_id, now);
+        self.write(ps, values).await?;
+        Ok(())
+    }
+
+    pub async fn get_watched_objects(&self, source: &str) -> Result<Vec<WatchedObjectModel>> {
+        let ps = self.prepared("GET_WATCHED_OBJECTS")?;
+        let rows = self
+            .client
+            .execute_iter(ps.clone(), (source,))
+            .await?
+            .into_typed::<WatchedObjectModel>()
+            .try_collect::<Vec<_>>()
+            .await?;
+        Ok(rows)
+    }
+
+    /// Reads a whole node partition under the throttle, retrying like a
+    /// write, since ingestions load every partition they touch this way.
+    pub async fn get_node_rows(&self, uuid: Uuid) -> Result<Vec<NodeModel>> {
+        let res = self
+            .retrying(|| async {
+                let permit = self.throttle.acquire().await;
+                let result = self.client.query(GET_NODE_BY_ID_WITH_ALL, (uuid,)).await;
+                self.throttle.release(permit, &result);
+                result
+            })
+            .await
+            .map_err(|(err, _)| err)?;
+        let rows = res
+            .rows_typed::<NodeModel>()?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
     pub async fn get_node(
         &self,
         uuid: Uuid,
@@ -206,12 +857,13 @@ impl ScyllaService {
         Ok(node)
     }
 
+    /// The node's own row and its edge rows in `direction`.
     pub async fn get_node_traversal(
         &self,
         uuid: Uuid,
         direction: String,
         relation_type: Option<String>,
-    ) -> Result<Option<RelationModel>> {
+    ) -> Result<Vec<RelationModel>> {
         let result = match relation_type {
             Some(rel) => {
                 let ps = self
@@ -222,25 +874,21 @@ impl ScyllaService {
                             .as_str(),
                     )
                     .ok_or_else(|| eyre!("insert node prepared statement not found"))?;
-                self.client.execute(&ps, (uuid, direction, rel)).await?
+                self.client.execute(ps, (uuid, direction, rel)).await?
             }
             None => {
                 let ps = self
                     .prepared_statements
                     .get("GET_NODE_BY_ID_AND_DIRECTION".to_lowercase().as_str())
                     .ok_or_else(|| eyre!("insert node prepared statement not found"))?;
-                self.client.execute(&ps, (uuid, direction)).await?
+                self.client.execute(ps, (uuid, direction)).await?
             }
         };
 
-        let relation = match result.single_row_typed::<RelationModel>() {
-            Ok(relation) => Some(relation),
-            Err(err) => match err {
-                SingleRowTypedError::BadNumberOfRows(_) => None,
-                _ => return Err(eyre!("ScyllaDB query failed: {}", err)),
-            },
-        };
-        Ok(relation)
+        let rows = result
+            .rows_typed_or_empty::<RelationModel>()
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
     }
 }
 
@@ -275,4 +923,20 @@ mod tests {
 
         assert_eq!(queries.len(), 5);
     }
+
+    #[test]
+    fn test_partition_batches() {
+        let rows = vec![(1, 'a'), (2, 'b'), (1, 'c'), (1, 'd'), (3, 'e'), (1, 'f')];
+        let batches = partition_batches(rows, 2, |row| row.0);
+        assert_eq!(
+            batches,
+            vec![
+                vec![(1, 'a'), (1, 'c')],
+                vec![(2, 'b')],
+                vec![(1, 'd'), (1, 'f')],
+                vec![(3, 'e')],
+            ]
+        );
+        assert_eq!(partition_batches(vec![1, 1, 1], 0, |row| *row).len(), 3);
+    }
 }
@@ -0,0 +1,172 @@
+use scylla::transport::errors::{DbError, QueryError};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Overload signals arriving within this window after a decrease belong to
+/// the same burst and do not shrink the limit again.
+const DECREASE_COOLDOWN: Duration = Duration::from_millis(500);
+
+/// True for errors that mean the cluster is saturated rather than that the
+/// statement itself is wrong.
+pub fn is_overload(err: &QueryError) -> bool {
+    matches!(
+        err,
+        QueryError::DbError(
+            DbError::Overloaded | DbError::WriteTimeout { .. } | DbError::Unavailable { .. },
+            _
+        ) | QueryError::TimeoutError
+            | QueryError::RequestTimeout(_)
+    )
+}
+
+#[derive(Debug)]
+struct Limit {
+    current: usize,
+    /// Permits to swallow on release after a decrease, for the part of the
+    /// decrease that was in flight and could not be taken back right away.
+    debt: usize,
+    successes: usize,
+    decreased_at: Option<Instant>,
+}
+
+/// Bounds in-flight writes with an AIMD limit: it starts at the configured
+/// `concurrency_limit`, halves when Scylla reports overload or a timeout and
+/// grows back by one after a full window of successful writes.
+#[derive(Debug)]
+pub struct Throttle {
+    semaphore: Arc<Semaphore>,
+    max: usize,
+    limit: Mutex<Limit>,
+}
+
+pub struct Permit {
+    permit: OwnedSemaphorePermit,
+}
+
+impl Throttle {
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            semaphore: Arc::new(Semaphore::new(max)),
+            max,
+            limit: Mutex::new(Limit {
+                current: max,
+                debt: 0,
+                successes: 0,
+                decreased_at: None,
+            }),
+        }
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn current(&self) -> usize {
+        self.lock().current
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Limit> {
+        self.limit.lock().expect("throttle lock poisoned")
+    }
+
+    pub async fn acquire(&self) -> Permit {
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("throttle semaphore is never closed");
+        Permit { permit }
+    }
+
+    /// Releases the permit and adapts the limit to the outcome of the write.
+    pub fn release<T>(&self, permit: Permit, result: &Result<T, QueryError>) {
+        let mut limit = self.lock();
+        match result {
+            Err(err) if is_overload(err) => {
+                limit.successes = 0;
+                let cooling = limit
+                    .decreased_at
+                    .is_some_and(|at| at.elapsed() < DECREASE_COOLDOWN);
+                if !cooling && limit.current > 1 {
+                    let current = (limit.current / 2).max(1);
+                    let mut shed = limit.current - current;
+                    while shed > 0 {
+                        match self.semaphore.try_acquire() {
+                            Ok(idle) => idle.forget(),
+                            Err(_) => break,
+                        }
+                        shed -= 1;
+                    }
+                    limit.debt += shed;
+                    limit.current = current;
+                    limit.decreased_at = Some(Instant::now());
+                }
+            }
+            Err(_) => {}
+            Ok(_) => {
+                limit.successes += 1;
+                if limit.successes >= limit.current && limit.current < self.max {
+                    limit.successes = 0;
+                    limit.current += 1;
+                    if limit.debt > 0 {
+                        limit.debt -= 1;
+                    } else {
+                        self.semaphore.add_permits(1);
+                    }
+                }
+            }
+        }
+        if limit.debt > 0 {
+            limit.debt -= 1;
+            permit.permit.forget();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overloaded() -> Result<(), QueryError> {
+        Err(QueryError::DbError(DbError::Overloaded, String::new()))
+    }
+
+    #[tokio::test]
+    async fn test_throttle_adapts_to_overload() {
+        let throttle = Throttle::new(8);
+        let permits = vec![
+            throttle.acquire().await,
+            throttle.acquire().await,
+            throttle.acquire().await,
+        ];
+        let mut permits = permits.into_iter();
+
+        throttle.release(permits.next().unwrap(), &overloaded());
+        assert_eq!(throttle.current(), 4);
+        throttle.release(permits.next().unwrap(), &overloaded());
+        assert_eq!(throttle.current(), 4, "decrease is rate limited");
+        throttle.release(permits.next().unwrap(), &Ok(()));
+        assert_eq!(throttle.semaphore.available_permits(), 4);
+
+        for _ in 0..4 {
+            let permit = throttle.acquire().await;
+            throttle.release(permit, &Ok(()));
+        }
+        assert_eq!(throttle.current(), 5);
+        assert_eq!(throttle.semaphore.available_permits(), 5);
+    }
+
+    #[test]
+    fn test_is_overload() {
+        assert!(is_overload(&QueryError::TimeoutError));
+        assert!(!is_overload(&QueryError::DbError(
+            DbError::SyntaxError,
+            String::new()
+        )));
+    }
+}
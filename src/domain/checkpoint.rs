@@ -0,0 +1,164 @@
+use super::pipeline::IngestionRequest;
+use crate::db::model::CheckpointModel;
+use eyre::{eyre, Result};
+use std::collections::HashMap;
+
+/// How far a file got: rows are committed in order, so a resumed run can
+/// skip the first `rows_committed` rows of the file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub rows_committed: usize,
+    pub completed: bool,
+}
+
+/// Tracks the checkpoint of a file while its chunks are written. The
+/// committed offset stops in front of the first chunk with failed rows, so a
+/// resumed run writes that chunk and everything after it again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    consumed: usize,
+    committed: usize,
+    failed: bool,
+}
+
+impl Progress {
+    /// Starts after the rows an earlier run committed.
+    pub fn new(offset: usize) -> Self {
+        Self {
+            consumed: 0,
+            committed: offset,
+            failed: false,
+        }
+    }
+
+    /// Takes the next chunk of `len` rows, returning how many rows at its
+    /// start were already committed and must be skipped.
+    pub fn advance(&mut self, len: usize) -> usize {
+        let skip = self.committed.saturating_sub(self.consumed).min(len);
+        self.consumed += len;
+        skip
+    }
+
+    /// Records the outcome of writing the chunk taken last.
+    pub fn written(&mut self, rows_failed: usize) {
+        self.failed |= rows_failed > 0;
+        if !self.failed {
+            self.committed = self.committed.max(self.consumed);
+        }
+    }
+
+    /// A file only completes once every row of it was written.
+    pub fn checkpoint(&self, finished: bool) -> Checkpoint {
+        Checkpoint {
+            rows_committed: self.committed,
+            completed: finished && !self.failed,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Resume {
+    pub request: IngestionRequest,
+    /// Keyed by the index of the file in the request.
+    pub checkpoints: HashMap<usize, Checkpoint>,
+}
+
+impl Resume {
+    /// Rebuilds the resume point of a job from its checkpoint rows, or
+    /// `None` when nothing was recorded for it.
+    pub fn from_rows(rows: Vec<CheckpointModel>) -> Result<Option<Resume>> {
+        let Some(request) = rows.iter().find_map(|row| row.request.clone()) else {
+            return Ok(None);
+        };
+        let request: IngestionRequest = serde_json::from_str(&request)
+            .map_err(|err| eyre!("Stored ingestion request is invalid: {}", err))?;
+        let checkpoints = rows
+            .into_iter()
+            .filter_map(|row| {
+                let index = usize::try_from(row.file_index?).ok()?;
+                let checkpoint = Checkpoint {
+                    rows_committed: row.rows_committed.unwrap_or_default().max(0) as usize,
+                    completed: row.completed.unwrap_or_default(),
+                };
+                Some((index, checkpoint))
+            })
+            .collect();
+        Ok(Some(Resume {
+            request,
+            checkpoints,
+        }))
+    }
+
+    pub fn checkpoint(&self, index: usize) -> Checkpoint {
+        self.checkpoints.get(&index).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(request: Option<String>, index: i32, committed: i64, done: bool) -> CheckpointModel {
+        CheckpointModel {
+            request,
+            file_index: Some(index),
+            file: Some(format!("file{}.json", index)),
+            rows_committed: Some(committed),
+            completed: Some(done),
+        }
+    }
+
+    #[test]
+    fn test_resume_from_rows() {
+        assert!(Resume::from_rows(vec![]).unwrap().is_none());
+
+        let request = IngestionRequest::new(
+            vec!["file0.json".to_string(), "file1.json".to_string()],
+            "ingestion".to_string(),
+        );
+        let json = Some(serde_json::to_string(&request).unwrap());
+        let rows = vec![row(json.clone(), 0, 40, true), row(json, 1, 1000, false)];
+        let resume = Resume::from_rows(rows).unwrap().unwrap();
+        assert_eq!(resume.request.files, request.files);
+        assert!(resume.checkpoint(0).completed);
+        assert_eq!(resume.checkpoint(1).rows_committed, 1000);
+        assert_eq!(resume.checkpoint(2), Checkpoint::default());
+
+        let broken = vec![row(Some("{".to_string()), 0, 0, false)];
+        assert!(Resume::from_rows(broken).is_err());
+    }
+
+    #[test]
+    fn test_resume_after_failed_chunk() {
+        let mut progress = Progress::new(0);
+        assert_eq!(progress.advance(10), 0);
+        progress.written(0);
+        assert_eq!(progress.advance(10), 0);
+        progress.written(3);
+        assert_eq!(progress.advance(10), 0);
+        progress.written(0);
+        let checkpoint = progress.checkpoint(true);
+        assert_eq!(
+            checkpoint,
+            Checkpoint {
+                rows_committed: 10,
+                completed: false
+            }
+        );
+
+        // The failed chunk and the one after it are written again.
+        let mut progress = Progress::new(checkpoint.rows_committed);
+        assert_eq!(progress.advance(10), 10);
+        assert_eq!(progress.advance(10), 0);
+        progress.written(0);
+        assert_eq!(progress.advance(10), 0);
+        progress.written(0);
+        assert_eq!(
+            progress.checkpoint(true),
+            Checkpoint {
+                rows_committed: 30,
+                completed: true
+            }
+        );
+    }
+}
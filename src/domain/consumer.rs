@@ -0,0 +1,247 @@
+use crate::application::AppState;
+use crate::domain::checkpoint::Resume;
+use crate::domain::job::{FailureKind, FileState, Job, JobState};
+use crate::domain::pipeline::{resume_job, spawn_job, IngestionRequest};
+use crate::queue::{Delivery, MessageQueue};
+use actix_web::web::Data;
+use eyre::Result;
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::Semaphore, task::JoinHandle};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// What to tell the queue once a message has been handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ack,
+    Nack,
+    /// The job of the message is still running here; the queue redelivers
+    /// it later if this instance dies.
+    Leave,
+}
+
+/// The job a message runs as. Stable across redeliveries, so a message that
+/// failed halfway resumes from the checkpoints of its earlier run.
+pub fn message_job_id(queue: &str, message_id: &str) -> Uuid {
+    let name = format!("queue/{}/{}", queue, message_id);
+    Uuid::new_v5(&Uuid::NAMESPACE_URL, name.as_bytes())
+}
+
+/// Settles a message by the job it ran as: acked once every row of it was
+/// written, left for redelivery while any row is missing. Files that could
+/// not be parsed do not hold up the ack, since redelivery cannot fix them.
+pub fn job_outcome(job: &Job) -> Outcome {
+    let unwritten = job.files.iter().any(|file| {
+        file.failure == Some(FailureKind::Storage)
+            || file.counts.rows_failed > 0
+            || matches!(file.state, FileState::Queued | FileState::Running)
+    });
+    if unwritten {
+        Outcome::Nack
+    } else {
+        Outcome::Ack
+    }
+}
+
+/// Runs the ingestion requests (`IngestionRequest` as JSON) that arrive on
+/// a queue. A message is acked once every row of its files was written, so
+/// every request is ingested at least once; a malformed request or a file
+/// that cannot be parsed is acked too, since redelivery cannot fix it.
+#[derive(Clone)]
+pub struct Consumer {
+    queue: Arc<dyn MessageQueue>,
+    /// One permit per message that may be handled at the same time.
+    permits: Arc<Semaphore>,
+    concurrency: usize,
+    block: Duration,
+}
+
+impl Consumer {
+    pub fn new(queue: Arc<dyn MessageQueue>, concurrency: usize, block: Duration) -> Self {
+        let concurrency = concurrency.max(1);
+        Self {
+            queue,
+            permits: Arc::new(Semaphore::new(concurrency)),
+            concurrency,
+            block,
+        }
+    }
+
+    pub fn spawn(self, state: Data<AppState>) -> JoinHandle<()> {
+        info!("Consuming ingestion requests from {}", self.queue.name());
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = self.run_once(&state).await {
+                    error!("Error consuming {}: {}", self.queue.name(), err);
+                    tokio::time::sleep(self.block).await;
+                }
+            }
+        })
+    }
+
+    /// Waits for a free slot, receives as many messages as there are free
+    /// slots and handles each in a task of its own. Returns how many
+    /// messages there were, without waiting for them to be handled.
+    pub async fn run_once(&self, state: &Data<AppState>) -> Result<usize> {
+        let mut permits = vec![self.permits.clone().acquire_owned().await?];
+        while let Ok(permit) = self.permits.clone().try_acquire_owned() {
+            permits.push(permit);
+        }
+        let deliveries = self.queue.receive(permits.len(), self.block).await?;
+        let count = deliveries.len();
+        for (delivery, permit) in deliveries.into_iter().zip(permits) {
+            let consumer = self.clone();
+            let state = state.clone();
+            drop(tokio::spawn(async move {
+                consumer.process(delivery, &state).await;
+                drop(permit);
+            }));
+        }
+        Ok(count)
+    }
+
+    /// Waits until no message is being handled.
+    pub async fn idle(&self) {
+        let _ = self.permits.acquire_many(self.concurrency as u32).await;
+    }
+
+    /// Handles the message, touching it while its job runs, and settles it.
+    async fn process(&self, delivery: Delivery, state: &Data<AppState>) {
+        let handling = self.handle(&delivery, state);
+        let outcome = match self.queue.lease() {
+            Some(lease) => {
+                tokio::pin!(handling);
+                let mut touches = tokio::time::interval((lease / 3).max(Duration::from_secs(1)));
+                touches.tick().await;
+                loop {
+                    tokio::select! {
+                        outcome = &mut handling => break outcome,
+                        _ = touches.tick() => {
+                            if let Err(err) = self.queue.touch(&delivery.id).await {
+                                warn!("Error touching message {}: {}", delivery.id, err);
+                            }
+                        }
+                    }
+                }
+            }
+            None => handling.await,
+        };
+        let settled = match outcome {
+            Outcome::Ack => self.queue.ack(&delivery.id).await,
+            Outcome::Nack => self.queue.nack(&delivery.id).await,
+            Outcome::Leave => Ok(()),
+        };
+        if let Err(err) = settled {
+            error!("Error settling message {}: {}", delivery.id, err);
+        }
+    }
+
+    async fn handle(&self, delivery: &Delivery, state: &Data<AppState>) -> Outcome {
+        let request = serde_json::from_slice::<IngestionRequest>(&delivery.payload)
+            .map_err(eyre::Report::from)
+            .and_then(|request| request.validate().map(|_| request))
+            .and_then(|request| {
+                state
+                    .notifier
+                    .check_callback(request.callback_url.as_deref())
+                    .map(|_| request)
+            });
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => {
+                error!("Dropping malformed message {}: {}", delivery.id, err);
+                return Outcome::Ack;
+            }
+        };
+
+        let job_id = message_job_id(self.queue.name(), &delivery.id);
+        if let Some(job) = state.jobs.get(&job_id) {
+            if job.finished_at.is_none() {
+                return Outcome::Leave;
+            }
+        }
+        let resume = match state.db.get_checkpoints(job_id).await {
+            Ok(rows) => Resume::from_rows(rows),
+            Err(err) => Err(err),
+        };
+        let handle = match resume {
+            Ok(Some(resume)) => {
+                info!("Resuming message {} as job {}", delivery.id, job_id);
+                state.jobs.create_with_id(
+                    job_id,
+                    resume.request.ingestion_id.clone(),
+                    resume.request.files.clone(),
+                );
+                resume_job(job_id, resume, state.clone())
+            }
+            Ok(None) => {
+                state.jobs.create_with_id(
+                    job_id,
+                    request.ingestion_id.clone(),
+                    request.files.clone(),
+                );
+                spawn_job(job_id, request, state.clone())
+            }
+            Err(err) => {
+                error!("Error loading checkpoints of job {}: {}", job_id, err);
+                return Outcome::Nack;
+            }
+        };
+        if let Err(err) = handle.await {
+            error!("Error joining task: {}", err);
+            return Outcome::Nack;
+        }
+
+        let Some(job) = state.jobs.get(&job_id) else {
+            return Outcome::Nack;
+        };
+        let outcome = job_outcome(&job);
+        if outcome == Outcome::Nack {
+            warn!(
+                "Job {} of message {} has rows left to write, leaving it for redelivery",
+                job_id, delivery.id
+            );
+        } else if job.state != JobState::Succeeded {
+            error!(
+                "Job {} of message {} has files that cannot be ingested",
+                job_id, delivery.id
+            );
+        }
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::job::FileCounts;
+
+    #[test]
+    fn test_message_job_id() {
+        let id = message_job_id("ingestion-requests", "1700000000000-0");
+        assert_eq!(id, message_job_id("ingestion-requests", "1700000000000-0"));
+        assert_ne!(id, message_job_id("ingestion-requests", "1700000000000-1"));
+        assert_ne!(id, message_job_id("other", "1700000000000-0"));
+    }
+
+    #[test]
+    fn test_job_outcome() {
+        let mut job = Job::new("test".to_string(), vec!["a.json".to_string()]);
+        assert_eq!(job_outcome(&job), Outcome::Nack);
+
+        job.files[0].state = FileState::Succeeded;
+        assert_eq!(job_outcome(&job), Outcome::Ack);
+
+        job.files[0].counts = FileCounts {
+            rows_written: 7,
+            rows_failed: 3,
+            ..FileCounts::default()
+        };
+        assert_eq!(job_outcome(&job), Outcome::Nack);
+
+        job.files[0].state = FileState::Failed;
+        job.files[0].failure = Some(FailureKind::Input);
+        job.files[0].counts = FileCounts::default();
+        assert_eq!(job_outcome(&job), Outcome::Ack);
+    }
+}
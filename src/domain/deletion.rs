@@ -0,0 +1,212 @@
+use super::job::{expired_jobs, JobState, FINISHED_JOB_TTL_HOURS, MAX_FINISHED_JOBS};
+use crate::application::AppState;
+use actix_web::web::Data;
+use chrono::{DateTime, Duration, Utc};
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::RwLock};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletionJob {
+    pub job_id: Uuid,
+    pub ingestion_id: String,
+    pub state: JobState,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub nodes_found: usize,
+    pub nodes_deleted: usize,
+    pub nodes_failed: usize,
+    pub error: Option<String>,
+}
+
+impl DeletionJob {
+    pub fn new(ingestion_id: String) -> Self {
+        Self {
+            job_id: Uuid::new_v4(),
+            ingestion_id,
+            state: JobState::Queued,
+            created_at: Utc::now(),
+            finished_at: None,
+            nodes_found: 0,
+            nodes_deleted: 0,
+            nodes_failed: 0,
+            error: None,
+        }
+    }
+
+    fn finish(&mut self, error: Option<String>) {
+        self.state = if error.is_none() && self.nodes_failed == 0 {
+            JobState::Succeeded
+        } else if self.nodes_deleted > 0 {
+            JobState::Partial
+        } else {
+            JobState::Failed
+        };
+        self.error = error;
+        self.finished_at = Some(Utc::now());
+    }
+}
+
+#[derive(Debug)]
+pub struct DeletionRegistry {
+    jobs: RwLock<HashMap<Uuid, DeletionJob>>,
+    finished_ttl: Duration,
+    max_finished: usize,
+}
+
+impl Default for DeletionRegistry {
+    fn default() -> Self {
+        Self::with_limits(Duration::hours(FINISHED_JOB_TTL_HOURS), MAX_FINISHED_JOBS)
+    }
+}
+
+impl DeletionRegistry {
+    /// Finished jobs are evicted like those of the ingestion job registry.
+    pub fn with_limits(finished_ttl: Duration, max_finished: usize) -> Self {
+        Self {
+            jobs: RwLock::default(),
+            finished_ttl,
+            max_finished,
+        }
+    }
+
+    pub fn create(&self, ingestion_id: String) -> DeletionJob {
+        let job = DeletionJob::new(ingestion_id);
+        let mut jobs = self.jobs.write().expect("deletion registry lock poisoned");
+        let finished = jobs
+            .values()
+            .filter_map(|job| job.finished_at.map(|at| (at, job.job_id)))
+            .collect();
+        for job_id in expired_jobs(finished, self.finished_ttl, self.max_finished) {
+            jobs.remove(&job_id);
+        }
+        jobs.insert(job.job_id, job.clone());
+        job
+    }
+
+    pub fn get(&self, job_id: &Uuid) -> Option<DeletionJob> {
+        let jobs = self.jobs.read().expect("deletion registry lock poisoned");
+        jobs.get(job_id).cloned()
+    }
+
+    pub fn update<F>(&self, job_id: &Uuid, update: F)
+    where
+        F: FnOnce(&mut DeletionJob),
+    {
+        let mut jobs = self.jobs.write().expect("deletion registry lock poisoned");
+        if let Some(job) = jobs.get_mut(job_id) {
+            update(job);
+        }
+    }
+}
+
+/// Deletes every partition listed for the ingestion, then drops the lookup
+/// rows and the rest of its records, so it can neither be listed nor resumed
+/// afterwards. The lookup is only cleared when all partitions are gone, so a
+/// failed run can simply be retried.
+pub fn spawn_deletion(job_id: Uuid, ingestion_id: String, state: Data<AppState>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let registry = &state.deletions;
+        let ids = match state.db.get_ingestion_node_ids(&ingestion_id).await {
+            Ok(ids) => ids,
+            Err(err) => {
+                error!("Error listing nodes of ingestion {}: {}", ingestion_id, err);
+                registry.update(&job_id, |job| job.finish(Some(err.to_string())));
+                return;
+            }
+        };
+        registry.update(&job_id, |job| {
+            job.state = JobState::Running;
+            job.nodes_found = ids.len();
+        });
+
+        let concurrency = state.db.concurrency_limit.max(1);
+        let mut results = stream::iter(ids)
+            .map(|id| {
+                let state = state.clone();
+                async move { state.db.delete_node(id).await }
+            })
+            .buffer_unordered(concurrency);
+        let mut first_error = None;
+        while let Some(result) = results.next().await {
+            match result {
+                Ok(()) => registry.update(&job_id, |job| job.nodes_deleted += 1),
+                Err(err) => {
+                    error!("Error deleting node: {}", err);
+                    first_error.get_or_insert(err.to_string());
+                    registry.update(&job_id, |job| job.nodes_failed += 1);
+                }
+            }
+        }
+
+        if first_error.is_none() {
+            if let Err(err) = state.db.delete_ingestion_index(&ingestion_id).await {
+                first_error = Some(err.to_string());
+            }
+        }
+        if first_error.is_none() {
+            if let Err(err) = state.db.delete_ingestion_records(&ingestion_id).await {
+                error!("Error deleting records of {}: {}", ingestion_id, err);
+                first_error = Some(err.to_string());
+            }
+        }
+        info!("Deletion of ingestion {} finished", ingestion_id);
+        registry.update(&job_id, |job| job.finish(first_error));
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deletion_job_finish() {
+        let registry = DeletionRegistry::default();
+        let job = registry.create("ingestion".to_string());
+        assert_eq!(job.state, JobState::Queued);
+
+        registry.update(&job.job_id, |job| {
+            job.nodes_found = 3;
+            job.nodes_deleted = 3;
+            job.finish(None);
+        });
+        let job = registry.get(&job.job_id).unwrap();
+        assert_eq!(job.state, JobState::Succeeded);
+        assert!(job.finished_at.is_some());
+
+        let mut job = DeletionJob::new("ingestion".to_string());
+        job.nodes_deleted = 1;
+        job.nodes_failed = 1;
+        job.finish(Some("timeout".to_string()));
+        assert_eq!(job.state, JobState::Partial);
+
+        let mut job = DeletionJob::new("ingestion".to_string());
+        job.finish(Some("unavailable".to_string()));
+        assert_eq!(job.state, JobState::Failed);
+    }
+
+    #[test]
+    fn test_finished_deletions_are_evicted() {
+        let registry = DeletionRegistry::with_limits(Duration::hours(1), 1);
+        let finish = |registry: &DeletionRegistry| {
+            let job = registry.create("ingestion".to_string());
+            registry.update(&job.job_id, |job| job.finish(None));
+            job.job_id
+        };
+        let first = finish(&registry);
+        let second = finish(&registry);
+        let running = registry.create("ingestion".to_string());
+        assert!(registry.get(&first).is_none());
+        assert!(registry.get(&second).is_some());
+
+        registry.update(&second, |job| {
+            job.finished_at = Some(Utc::now() - Duration::hours(2))
+        });
+        registry.create("ingestion".to_string());
+        assert!(registry.get(&second).is_none());
+        assert!(registry.get(&running.job_id).is_some());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::db::model::NodeModel;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
+use uuid::Uuid;
+
+/// Primary key of a `graph.nodes` row: id, direction, relation, relates_to.
+pub type RowKey = (Uuid, String, String, String);
+
+pub fn row_key(row: &NodeModel) -> RowKey {
+    (
+        row.uuid,
+        row.direction.clone().unwrap_or_default(),
+        row.relation.clone().unwrap_or_default(),
+        row.relates_to.clone().unwrap_or_default(),
+    )
+}
+
+fn is_node_row(key: &RowKey) -> bool {
+    key.1.is_empty() && key.2.is_empty()
+}
+
+fn hash_of(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hash of the columns outside the key. Empty strings and empty tag lists
+/// are read back from Scylla as nulls, so tags are normalised first.
+fn row_hash(row: &NodeModel) -> u64 {
+    hash_of((
+        &row.name,
+        &row.ingestion_id,
+        &row.path,
+        &row.node_type,
+        row.tags.as_deref().unwrap_or_default(),
+        row.total_children,
+    ))
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffSummary {
+    pub nodes_added: usize,
+    pub nodes_updated: usize,
+    pub nodes_removed: usize,
+    pub rows_inserted: usize,
+    pub rows_updated: usize,
+    pub rows_unchanged: usize,
+    pub rows_deleted: usize,
+    pub rows_failed: usize,
+    /// Set when a file of the job failed: the new snapshot is incomplete, so
+    /// nothing is deleted.
+    pub deletions_skipped: bool,
+}
+
+/// What disappeared from the snapshot: whole node partitions, and the
+/// partitions that are still present but lost some of their rows.
+#[derive(Debug, Default, PartialEq)]
+pub struct Removals {
+    pub nodes: Vec<Uuid>,
+    pub partitions: Vec<Uuid>,
+}
+
+/// Diffs the rows of a re-ingestion against what is stored for the same
+/// ingestion id. The stored rows are loaded one partition at a time and
+/// only kept as hashes of their key and columns. Rows are fed in as the
+/// files are flattened, and only the new or changed ones are handed back
+/// for writing.
+#[derive(Debug, Default)]
+pub struct RowDiff {
+    /// Node id, then key hash to column hash of each stored row.
+    stored: HashMap<Uuid, HashMap<u64, u64>>,
+    seen: HashMap<Uuid, HashSet<u64>>,
+    pub summary: DiffSummary,
+}
+
+impl RowDiff {
+    pub fn add_stored(&mut self, rows: &[NodeModel]) {
+        for row in rows {
+            self.stored
+                .entry(row.uuid)
+                .or_default()
+                .insert(hash_of(row_key(row)), row_hash(row));
+        }
+    }
+
+    pub fn changed(&mut self, rows: Vec<NodeModel>) -> Vec<NodeModel> {
+        rows.into_iter()
+            .filter(|row| {
+                let key = row_key(row);
+                let key_hash = hash_of(&key);
+                let first = self.seen.entry(row.uuid).or_default().insert(key_hash);
+                let node = is_node_row(&key);
+                let summary = &mut self.summary;
+                let stored = self
+                    .stored
+                    .get(&row.uuid)
+                    .and_then(|rows| rows.get(&key_hash));
+                match stored {
+                    None => {
+                        if first {
+                            summary.rows_inserted += 1;
+                            summary.nodes_added += node as usize;
+                        }
+                        true
+                    }
+                    Some(hash) if *hash != row_hash(row) => {
+                        if first {
+                            summary.rows_updated += 1;
+                            summary.nodes_updated += node as usize;
+                        }
+                        true
+                    }
+                    Some(_) => {
+                        summary.rows_unchanged += first as usize;
+                        false
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Partitions with stored rows that were not seen again. A partition is
+    /// removed as a whole when none of its rows were seen.
+    pub fn removals(&self) -> Removals {
+        let mut removals = Removals::default();
+        for (uuid, rows) in &self.stored {
+            match self.seen.get(uuid) {
+                None => removals.nodes.push(*uuid),
+                Some(seen) if rows.keys().any(|key| !seen.contains(key)) => {
+                    removals.partitions.push(*uuid)
+                }
+                Some(_) => {}
+            }
+        }
+        removals.nodes.sort();
+        removals.partitions.sort();
+        removals
+    }
+
+    /// Keys of the rows of a partition, read back once the files are in,
+    /// that were stored before and not seen again.
+    pub fn removed_rows(&self, rows: &[NodeModel]) -> Vec<RowKey> {
+        rows.iter()
+            .map(row_key)
+            .filter(|key| {
+                let key_hash = hash_of(key);
+                let stored = self
+                    .stored
+                    .get(&key.0)
+                    .is_some_and(|rows| rows.contains_key(&key_hash));
+                let seen = self
+                    .seen
+                    .get(&key.0)
+                    .is_some_and(|seen| seen.contains(&key_hash));
+                stored && !seen
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: u128, direction: &str, relates_to: &str, name: &str) -> NodeModel {
+        NodeModel {
+            uuid: Uuid::from_u128(id),
+            direction: Some(direction.to_string()),
+            relation: Some(if direction.is_empty() { "" } else { "uses" }.to_string()),
+            relates_to: Some(relates_to.to_string()),
+            name: name.to_string(),
+            ingestion_id: "test".to_string(),
+            ..NodeModel::default()
+        }
+    }
+
+    #[test]
+    fn test_row_diff() {
+        let stored = vec![
+            row(1, "", "", "a"),
+            row(1, "Out", "2", "b"),
+            row(1, "Out", "3", "c"),
+            row(2, "", "", "b"),
+            row(3, "", "", "c"),
+            row(3, "In", "1", "a"),
+        ];
+        let mut diff = RowDiff::default();
+        diff.add_stored(&stored);
+
+        let mut renamed = row(2, "", "", "b2");
+        renamed.tags = Some(vec![]);
+        let changed = diff.changed(vec![
+            row(1, "", "", "a"),
+            row(1, "Out", "2", "b"),
+            renamed,
+            row(4, "", "", "d"),
+        ]);
+        assert_eq!(changed.len(), 2);
+        assert_eq!(changed[0].name, "b2");
+
+        let removals = diff.removals();
+        assert_eq!(removals.nodes, vec![Uuid::from_u128(3)]);
+        assert_eq!(removals.partitions, vec![Uuid::from_u128(1)]);
+        let partition: Vec<NodeModel> = stored
+            .iter()
+            .filter(|row| row.uuid == Uuid::from_u128(1))
+            .cloned()
+            .collect();
+        assert_eq!(
+            diff.removed_rows(&partition),
+            vec![(
+                Uuid::from_u128(1),
+                "Out".to_string(),
+                "uses".to_string(),
+                "3".to_string()
+            )]
+        );
+        assert_eq!(diff.summary.nodes_added, 1);
+        assert_eq!(diff.summary.nodes_updated, 1);
+        assert_eq!(diff.summary.rows_inserted, 1);
+        assert_eq!(diff.summary.rows_updated, 1);
+        assert_eq!(diff.summary.rows_unchanged, 2);
+    }
+
+    #[test]
+    fn test_unchanged_tags_are_normalised() {
+        let mut stored = row(1, "", "", "a");
+        stored.tags = None;
+        let mut diff = RowDiff::default();
+        diff.add_stored(&[stored]);
+        let mut row = row(1, "", "", "a");
+        row.tags = Some(vec![]);
+        assert!(diff.changed(vec![row]).is_empty());
+        assert_eq!(diff.removals(), Removals::default());
+    }
+}
@@ -0,0 +1,137 @@
+use super::job::{Job, JobState};
+use crate::db::model::IngestionModel;
+use chrono::{DateTime, TimeZone, Utc};
+use scylla::frame::value::Timestamp;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const SERVICE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Registry entry for the latest run of an ingestion id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ingestion {
+    pub ingestion_id: String,
+    pub job_id: Uuid,
+    pub files: Vec<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: JobState,
+    pub nodes: usize,
+    pub relations: usize,
+    pub rows_written: usize,
+    pub rows_failed: usize,
+    pub service_version: String,
+}
+
+impl Ingestion {
+    pub fn from_job(job: &Job) -> Self {
+        let files = &job.files;
+        Self {
+            ingestion_id: job.ingestion_id.clone(),
+            job_id: job.job_id,
+            files: files.iter().map(|f| f.file.clone()).collect(),
+            started_at: job.created_at,
+            finished_at: job.finished_at,
+            status: job.state,
+            nodes: files.iter().map(|f| f.counts.nodes_parsed).sum(),
+            relations: files.iter().map(|f| f.counts.relations_parsed).sum(),
+            rows_written: files.iter().map(|f| f.counts.rows_written).sum(),
+            rows_failed: files.iter().map(|f| f.counts.rows_failed).sum(),
+            service_version: SERVICE_VERSION.to_owned(),
+        }
+    }
+}
+
+fn to_timestamp(time: DateTime<Utc>) -> Timestamp {
+    Timestamp(chrono::Duration::milliseconds(time.timestamp_millis()))
+}
+
+fn from_timestamp(timestamp: Timestamp) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(timestamp.0.num_milliseconds())
+        .single()
+        .unwrap_or_default()
+}
+
+fn status_to_string(status: JobState) -> String {
+    serde_json::to_value(status)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_owned))
+        .unwrap_or_default()
+}
+
+impl From<Ingestion> for IngestionModel {
+    fn from(ingestion: Ingestion) -> Self {
+        IngestionModel {
+            id: ingestion.ingestion_id,
+            job_id: ingestion.job_id,
+            files: ingestion.files,
+            started_at: to_timestamp(ingestion.started_at),
+            finished_at: ingestion.finished_at.map(to_timestamp),
+            status: status_to_string(ingestion.status),
+            nodes: ingestion.nodes as i64,
+            relations: ingestion.relations as i64,
+            rows_written: ingestion.rows_written as i64,
+            rows_failed: ingestion.rows_failed as i64,
+            service_version: ingestion.service_version,
+        }
+    }
+}
+
+impl TryFrom<IngestionModel> for Ingestion {
+    type Error = eyre::Report;
+
+    fn try_from(model: IngestionModel) -> eyre::Result<Self> {
+        Ok(Ingestion {
+            ingestion_id: model.id,
+            job_id: model.job_id,
+            files: model.files,
+            started_at: from_timestamp(model.started_at),
+            finished_at: model.finished_at.map(from_timestamp),
+            status: serde_json::from_value(serde_json::Value::String(model.status))?,
+            nodes: model.nodes as usize,
+            relations: model.relations as usize,
+            rows_written: model.rows_written as usize,
+            rows_failed: model.rows_failed as usize,
+            service_version: model.service_version,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::job::{FileCounts, JobRegistry};
+
+    #[test]
+    fn test_ingestion_from_job_round_trip() {
+        let registry = JobRegistry::default();
+        let files = vec!["a.json".to_string(), "b.json".to_string()];
+        let job = registry.create("ingestion".to_string(), files);
+        for index in 0..2 {
+            let counts = FileCounts {
+                nodes_parsed: 3,
+                relations_parsed: 1,
+                rows_written: 8,
+                ..FileCounts::default()
+            };
+            registry.file_finished(&job.job_id, index, counts, None);
+        }
+        let job = registry.get(&job.job_id).unwrap();
+
+        let ingestion = Ingestion::from_job(&job);
+        assert_eq!(ingestion.status, JobState::Succeeded);
+        assert_eq!(ingestion.nodes, 6);
+        assert_eq!(ingestion.rows_written, 16);
+        assert_eq!(ingestion.service_version, SERVICE_VERSION);
+
+        let model = IngestionModel::from(ingestion.clone());
+        assert_eq!(model.status, "succeeded");
+        let restored = Ingestion::try_from(model).unwrap();
+        assert_eq!(restored.files, ingestion.files);
+        assert_eq!(
+            restored.started_at.timestamp_millis(),
+            ingestion.started_at.timestamp_millis()
+        );
+        assert_eq!(restored.status, ingestion.status);
+    }
+}
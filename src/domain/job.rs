@@ -0,0 +1,634 @@
+use super::{
+    diff::DiffSummary,
+    validation::{ValidationIssue, ValidationReport},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::RwLock};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Events buffered per subscriber before a slow one starts missing some.
+const EVENT_CAPACITY: usize = 256;
+
+/// How long a finished job stays available for status requests.
+pub(crate) const FINISHED_JOB_TTL_HOURS: i64 = 24;
+
+/// Upper bound on the finished jobs kept; the oldest go first.
+pub(crate) const MAX_FINISHED_JOBS: usize = 1000;
+
+/// Picks the finished jobs to evict: those older than `ttl`, plus the
+/// oldest ones beyond `max`.
+pub(crate) fn expired_jobs(
+    mut finished: Vec<(DateTime<Utc>, Uuid)>,
+    ttl: Duration,
+    max: usize,
+) -> Vec<Uuid> {
+    let now = Utc::now();
+    finished.sort();
+    let excess = finished.len().saturating_sub(max);
+    finished
+        .into_iter()
+        .enumerate()
+        .filter(|(index, (finished_at, _))| *index < excess || now - *finished_at > ttl)
+        .map(|(_, (_, job_id))| job_id)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Partial,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureKind {
+    Input,
+    Storage,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileCounts {
+    pub nodes_parsed: usize,
+    pub relations_parsed: usize,
+    pub rows_written: usize,
+    pub rows_failed: usize,
+    /// Written nodes missing from the ingestion's node index.
+    #[serde(default)]
+    pub nodes_unindexed: usize,
+    #[serde(default)]
+    pub issues_found: usize,
+    #[serde(default)]
+    pub records_skipped: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<ValidationIssue>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileStatus {
+    pub file: String,
+    pub state: FileState,
+    pub failure: Option<FailureKind>,
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub counts: FileCounts,
+}
+
+impl FileStatus {
+    fn new(file: String) -> Self {
+        Self {
+            file,
+            state: FileState::Queued,
+            failure: None,
+            error: None,
+            counts: FileCounts::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub job_id: Uuid,
+    pub ingestion_id: String,
+    pub state: JobState,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub files: Vec<FileStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<DiffSummary>,
+}
+
+impl Job {
+    pub fn new(ingestion_id: String, files: Vec<String>) -> Self {
+        Self {
+            job_id: Uuid::new_v4(),
+            ingestion_id,
+            state: JobState::Queued,
+            created_at: Utc::now(),
+            finished_at: None,
+            files: files.into_iter().map(FileStatus::new).collect(),
+            diff: None,
+        }
+    }
+
+    fn refresh_state(&mut self) {
+        let pending = self
+            .files
+            .iter()
+            .any(|f| matches!(f.state, FileState::Queued | FileState::Running));
+        let started = self.files.iter().any(|f| f.state != FileState::Queued);
+
+        self.state = if !started && !self.files.is_empty() {
+            JobState::Queued
+        } else if pending {
+            JobState::Running
+        } else if self.files.iter().all(|f| f.state == FileState::Succeeded) {
+            JobState::Succeeded
+        } else if self
+            .files
+            .iter()
+            .any(|f| f.state == FileState::Succeeded || f.counts.rows_written > 0)
+        {
+            JobState::Partial
+        } else {
+            JobState::Failed
+        };
+
+        if !pending && self.finished_at.is_none() {
+            self.finished_at = Some(Utc::now());
+        }
+    }
+}
+
+/// Progress of a running job, as pushed to `/ingest/{job_id}/events`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JobEvent {
+    FileStarted {
+        index: usize,
+        file: String,
+    },
+    RowsWritten {
+        index: usize,
+        file: String,
+        rows_written: usize,
+        rows_failed: usize,
+    },
+    ValidationWarnings {
+        index: usize,
+        file: String,
+        issues_found: usize,
+        issues: Vec<ValidationIssue>,
+    },
+    FileFinished {
+        index: usize,
+        status: FileStatus,
+    },
+    JobFinished {
+        job: Box<Job>,
+    },
+}
+
+impl JobEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobEvent::FileStarted { .. } => "file_started",
+            JobEvent::RowsWritten { .. } => "rows_written",
+            JobEvent::ValidationWarnings { .. } => "validation_warnings",
+            JobEvent::FileFinished { .. } => "file_finished",
+            JobEvent::JobFinished { .. } => "job_finished",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct JobRegistry {
+    jobs: RwLock<HashMap<Uuid, Job>>,
+    /// Present while the job runs; dropped once `JobFinished` went out.
+    events: RwLock<HashMap<Uuid, broadcast::Sender<JobEvent>>>,
+    finished_ttl: Duration,
+    max_finished: usize,
+}
+
+impl Default for JobRegistry {
+    fn default() -> Self {
+        Self::with_limits(Duration::hours(FINISHED_JOB_TTL_HOURS), MAX_FINISHED_JOBS)
+    }
+}
+
+impl JobRegistry {
+    /// Finished jobs are evicted once they are older than `finished_ttl` or
+    /// more than `max_finished` of them are kept.
+    pub fn with_limits(finished_ttl: Duration, max_finished: usize) -> Self {
+        Self {
+            jobs: RwLock::default(),
+            events: RwLock::default(),
+            finished_ttl,
+            max_finished,
+        }
+    }
+
+    pub fn create(&self, ingestion_id: String, files: Vec<String>) -> Job {
+        self.create_with_id(Uuid::new_v4(), ingestion_id, files)
+    }
+
+    /// Registers a job under a known id, replacing any previous entry.
+    pub fn create_with_id(&self, job_id: Uuid, ingestion_id: String, files: Vec<String>) -> Job {
+        self.insert(job_id, ingestion_id, files, false)
+            .expect("replacing jobs cannot conflict")
+    }
+
+    /// Registers a job under a known id unless a job with that id is still
+    /// running, which is returned instead; used when a job is resumed.
+    pub fn try_create_with_id(
+        &self,
+        job_id: Uuid,
+        ingestion_id: String,
+        files: Vec<String>,
+    ) -> Result<Job, Box<Job>> {
+        self.insert(job_id, ingestion_id, files, true)
+    }
+
+    fn insert(
+        &self,
+        job_id: Uuid,
+        ingestion_id: String,
+        files: Vec<String>,
+        exclusive: bool,
+    ) -> Result<Job, Box<Job>> {
+        let mut job = Job::new(ingestion_id, files);
+        job.job_id = job_id;
+        if job.files.is_empty() {
+            job.refresh_state();
+        }
+        // Both locks are held from the check to the insert, so two callers
+        // cannot both find the id free.
+        let mut events = self.events.write().expect("job registry lock poisoned");
+        let mut jobs = self.jobs.write().expect("job registry lock poisoned");
+        if exclusive {
+            // A job runs until its `JobFinished` event went out.
+            if let Some(running) = jobs
+                .get(&job_id)
+                .filter(|existing| existing.finished_at.is_none() || events.contains_key(&job_id))
+            {
+                return Err(Box::new(running.clone()));
+            }
+        }
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        events.insert(job.job_id, sender);
+        self.evict(&mut jobs, &events);
+        jobs.insert(job.job_id, job.clone());
+        Ok(job)
+    }
+
+    /// Drops the finished jobs past their TTL or over the cap. Jobs whose
+    /// `JobFinished` event has not gone out yet are kept.
+    fn evict(
+        &self,
+        jobs: &mut HashMap<Uuid, Job>,
+        events: &HashMap<Uuid, broadcast::Sender<JobEvent>>,
+    ) {
+        let finished = jobs
+            .values()
+            .filter(|job| !events.contains_key(&job.job_id))
+            .filter_map(|job| job.finished_at.map(|at| (at, job.job_id)))
+            .collect();
+        for job_id in expired_jobs(finished, self.finished_ttl, self.max_finished) {
+            jobs.remove(&job_id);
+        }
+    }
+
+    /// Subscribes to the events of a running job. Returns `None` once the
+    /// job has finished, in which case its status is final.
+    pub fn subscribe(&self, job_id: &Uuid) -> Option<broadcast::Receiver<JobEvent>> {
+        let events = self.events.read().expect("job registry lock poisoned");
+        events.get(job_id).map(broadcast::Sender::subscribe)
+    }
+
+    fn emit(&self, job_id: &Uuid, event: JobEvent) {
+        let events = self.events.read().expect("job registry lock poisoned");
+        if let Some(sender) = events.get(job_id) {
+            // Nobody listening is fine.
+            let _ = sender.send(event);
+        }
+    }
+
+    /// Announces the final status and closes the event stream. Called by the
+    /// pipeline after the post-file work (diff, registry) is done.
+    pub fn finish_job(&self, job_id: &Uuid) {
+        if let Some(job) = self.get(job_id) {
+            let job = Box::new(job);
+            self.emit(job_id, JobEvent::JobFinished { job });
+        }
+        let mut events = self.events.write().expect("job registry lock poisoned");
+        events.remove(job_id);
+    }
+
+    pub fn rows_written(
+        &self,
+        job_id: &Uuid,
+        index: usize,
+        rows_written: usize,
+        rows_failed: usize,
+    ) {
+        let mut file = None;
+        self.update_file(job_id, index, |status| {
+            status.counts.rows_written = rows_written;
+            status.counts.rows_failed = rows_failed;
+            file = Some(status.file.clone());
+        });
+        if let Some(file) = file {
+            let event = JobEvent::RowsWritten {
+                index,
+                file,
+                rows_written,
+                rows_failed,
+            };
+            self.emit(job_id, event);
+        }
+    }
+
+    pub fn validation_warnings(&self, job_id: &Uuid, index: usize, report: &ValidationReport) {
+        if report.is_clean() {
+            return;
+        }
+        if let Some(file) = self.file_status(job_id, index) {
+            let event = JobEvent::ValidationWarnings {
+                index,
+                file: file.file,
+                issues_found: report.issues_found,
+                issues: report.issues.clone(),
+            };
+            self.emit(job_id, event);
+        }
+    }
+
+    fn file_status(&self, job_id: &Uuid, index: usize) -> Option<FileStatus> {
+        let jobs = self.jobs.read().expect("job registry lock poisoned");
+        jobs.get(job_id)?.files.get(index).cloned()
+    }
+
+    fn emit_file_finished(&self, job_id: &Uuid, index: usize) {
+        if let Some(status) = self.file_status(job_id, index) {
+            self.emit(job_id, JobEvent::FileFinished { index, status });
+        }
+    }
+
+    pub fn get(&self, job_id: &Uuid) -> Option<Job> {
+        let jobs = self.jobs.read().expect("job registry lock poisoned");
+        jobs.get(job_id).cloned()
+    }
+
+    pub fn update_file<F>(&self, job_id: &Uuid, index: usize, update: F)
+    where
+        F: FnOnce(&mut FileStatus),
+    {
+        let mut jobs = self.jobs.write().expect("job registry lock poisoned");
+        if let Some(job) = jobs.get_mut(job_id) {
+            if let Some(file) = job.files.get_mut(index) {
+                update(file);
+            }
+            job.refresh_state();
+        }
+    }
+
+    pub fn set_diff(&self, job_id: &Uuid, diff: DiffSummary) {
+        let mut jobs = self.jobs.write().expect("job registry lock poisoned");
+        if let Some(job) = jobs.get_mut(job_id) {
+            job.diff = Some(diff);
+        }
+    }
+
+    pub fn file_started(&self, job_id: &Uuid, index: usize) {
+        self.update_file(job_id, index, |file| file.state = FileState::Running);
+        if let Some(status) = self.file_status(job_id, index) {
+            let file = status.file;
+            self.emit(job_id, JobEvent::FileStarted { index, file });
+        }
+    }
+
+    pub fn file_finished(
+        &self,
+        job_id: &Uuid,
+        index: usize,
+        counts: FileCounts,
+        error: Option<String>,
+    ) {
+        self.update_file(job_id, index, |file| {
+            let error = error.unwrap_or_default();
+            if counts.rows_failed > 0 {
+                file.state = FileState::Failed;
+                file.failure = Some(FailureKind::Storage);
+                file.error = Some(format!(
+                    "{} of {} rows failed to write: {}",
+                    counts.rows_failed,
+                    counts.rows_written + counts.rows_failed,
+                    error
+                ));
+            } else if counts.nodes_unindexed > 0 {
+                file.state = FileState::Failed;
+                file.failure = Some(FailureKind::Storage);
+                file.error = Some(format!(
+                    "{} written nodes failed to index: {}",
+                    counts.nodes_unindexed, error
+                ));
+            } else {
+                file.state = FileState::Succeeded;
+            }
+            file.counts = counts;
+        });
+        self.emit_file_finished(job_id, index);
+    }
+
+    pub fn file_failed(
+        &self,
+        job_id: &Uuid,
+        index: usize,
+        kind: FailureKind,
+        error: String,
+        counts: FileCounts,
+    ) {
+        self.update_file(job_id, index, |file| {
+            file.state = FileState::Failed;
+            file.failure = Some(kind);
+            file.error = Some(error);
+            file.counts = counts;
+        });
+        self.emit_file_finished(job_id, index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_state_transitions() {
+        let registry = JobRegistry::default();
+        let job = registry.create(
+            "ingestion".to_string(),
+            vec!["a.json".to_string(), "b.json".to_string()],
+        );
+        assert_eq!(job.state, JobState::Queued);
+
+        registry.file_started(&job.job_id, 0);
+        assert_eq!(registry.get(&job.job_id).unwrap().state, JobState::Running);
+
+        let counts = FileCounts {
+            nodes_parsed: 4,
+            relations_parsed: 1,
+            rows_written: 12,
+            rows_failed: 0,
+            ..FileCounts::default()
+        };
+        registry.file_finished(&job.job_id, 0, counts, None);
+        registry.file_started(&job.job_id, 1);
+        registry.file_failed(
+            &job.job_id,
+            1,
+            FailureKind::Input,
+            "boom".to_string(),
+            FileCounts::default(),
+        );
+
+        let job = registry.get(&job.job_id).unwrap();
+        assert_eq!(job.state, JobState::Partial);
+        assert!(job.finished_at.is_some());
+        assert_eq!(job.files[0].counts.rows_written, 12);
+        assert_eq!(job.files[1].failure, Some(FailureKind::Input));
+        assert_eq!(job.files[1].error.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    fn test_job_failed_rows() {
+        let registry = JobRegistry::default();
+        let job = registry.create("ingestion".to_string(), vec!["a.json".to_string()]);
+        registry.file_started(&job.job_id, 0);
+        let counts = FileCounts {
+            nodes_parsed: 1,
+            relations_parsed: 0,
+            rows_written: 0,
+            rows_failed: 2,
+            ..FileCounts::default()
+        };
+        registry.file_finished(&job.job_id, 0, counts, Some("timeout".to_string()));
+
+        let job = registry.get(&job.job_id).unwrap();
+        assert_eq!(job.state, JobState::Failed);
+        assert_eq!(job.files[0].state, FileState::Failed);
+        assert_eq!(job.files[0].failure, Some(FailureKind::Storage));
+        assert_eq!(
+            job.files[0].error.as_deref(),
+            Some("2 of 2 rows failed to write: timeout")
+        );
+    }
+
+    #[test]
+    fn test_job_unindexed_nodes() {
+        let registry = JobRegistry::default();
+        let job = registry.create("ingestion".to_string(), vec!["a.json".to_string()]);
+        registry.file_started(&job.job_id, 0);
+        let counts = FileCounts {
+            rows_written: 6,
+            nodes_unindexed: 2,
+            ..FileCounts::default()
+        };
+        registry.file_finished(&job.job_id, 0, counts, Some("timeout".to_string()));
+
+        let job = registry.get(&job.job_id).unwrap();
+        assert_eq!(job.files[0].failure, Some(FailureKind::Storage));
+        assert_eq!(job.files[0].counts.rows_failed, 0);
+        assert_eq!(
+            job.files[0].error.as_deref(),
+            Some("2 written nodes failed to index: timeout")
+        );
+    }
+
+    #[test]
+    fn test_job_succeeded() {
+        let registry = JobRegistry::default();
+        let job = registry.create("ingestion".to_string(), vec!["a.json".to_string()]);
+        registry.file_started(&job.job_id, 0);
+        registry.file_finished(&job.job_id, 0, FileCounts::default(), None);
+        assert_eq!(
+            registry.get(&job.job_id).unwrap().state,
+            JobState::Succeeded
+        );
+        assert!(registry.get(&Uuid::new_v4()).is_none());
+    }
+
+    #[test]
+    fn test_resume_conflicts_with_running_job() {
+        let registry = JobRegistry::default();
+        let job = registry.create("ingestion".to_string(), vec!["a.json".to_string()]);
+        let files = vec!["a.json".to_string()];
+        let conflict = registry
+            .try_create_with_id(job.job_id, "ingestion".to_string(), files.clone())
+            .unwrap_err();
+        assert_eq!(conflict.job_id, job.job_id);
+
+        registry.file_started(&job.job_id, 0);
+        registry.file_finished(&job.job_id, 0, FileCounts::default(), None);
+        assert!(registry
+            .try_create_with_id(job.job_id, "ingestion".to_string(), files.clone())
+            .is_err());
+        registry.finish_job(&job.job_id);
+        let resumed = registry
+            .try_create_with_id(job.job_id, "ingestion".to_string(), files)
+            .unwrap();
+        assert_eq!(resumed.state, JobState::Queued);
+    }
+
+    #[test]
+    fn test_finished_jobs_are_evicted() {
+        let registry = JobRegistry::with_limits(Duration::hours(1), 2);
+        let finish = |registry: &JobRegistry| {
+            let job = registry.create("ingestion".to_string(), vec!["a.json".to_string()]);
+            registry.file_started(&job.job_id, 0);
+            registry.file_finished(&job.job_id, 0, FileCounts::default(), None);
+            registry.finish_job(&job.job_id);
+            job.job_id
+        };
+        let first = finish(&registry);
+        let second = finish(&registry);
+        let third = finish(&registry);
+        let running = registry.create("ingestion".to_string(), vec!["a.json".to_string()]);
+        assert!(registry.get(&first).is_none());
+        assert!(registry.get(&second).is_some());
+        assert!(registry.get(&third).is_some());
+
+        let expired = Utc::now() - Duration::hours(2);
+        registry
+            .jobs
+            .write()
+            .unwrap()
+            .get_mut(&second)
+            .unwrap()
+            .finished_at = Some(expired);
+        registry.create("ingestion".to_string(), vec![]);
+        assert!(registry.get(&second).is_none());
+        assert!(registry.get(&third).is_some());
+        assert!(registry.get(&running.job_id).is_some());
+    }
+
+    #[test]
+    fn test_job_events() {
+        let registry = JobRegistry::default();
+        let job = registry.create("ingestion".to_string(), vec!["a.json".to_string()]);
+        let mut events = registry.subscribe(&job.job_id).unwrap();
+
+        registry.file_started(&job.job_id, 0);
+        registry.rows_written(&job.job_id, 0, 10, 1);
+        registry.file_finished(&job.job_id, 0, FileCounts::default(), None);
+        registry.finish_job(&job.job_id);
+
+        let mut names = vec![];
+        while let Ok(event) = events.try_recv() {
+            names.push(event.name());
+        }
+        assert_eq!(
+            names,
+            vec![
+                "file_started",
+                "rows_written",
+                "file_finished",
+                "job_finished"
+            ]
+        );
+        assert!(registry.subscribe(&job.job_id).is_none());
+    }
+}
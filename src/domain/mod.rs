@@ -1,3 +1,16 @@
+pub mod checkpoint;
+pub mod consumer;
+pub mod deletion;
+pub mod diff;
+pub mod ingestion;
+pub mod job;
 pub mod node;
+pub mod path;
+pub mod pipeline;
+pub mod preview;
 pub mod relation;
 pub mod s3;
+pub mod upload;
+pub mod validation;
+pub mod watcher;
+pub mod webhook;
@@ -1,5 +1,5 @@
-use super::relation::Relation;
-use crate::db::model::RelationModel;
+use super::relation::{Relation, TagFilter};
+use crate::db::model::{NodeModel, RelationModel};
 use serde::Deserialize;
 use serde::Serialize;
 use uuid::Uuid;
@@ -14,6 +14,10 @@ pub struct Node {
     pub node_type: String,
     pub tags: Vec<(String, String)>,
     pub relations: Vec<Relation>,
+    /// Children the node has at the source. More than its `Child` relations
+    /// means the node is only partially loaded.
+    #[serde(default)]
+    pub total_children: Option<i64>,
 }
 
 impl Node {
@@ -32,8 +36,37 @@ impl Node {
             node_type,
             tags: Vec::new(),
             relations: Vec::new(),
+            total_children: None,
         }
     }
+
+    /// Builds a node from every row of its partition: the node row itself
+    /// plus one relation per edge row. Partitions that only hold edge rows,
+    /// such as relation endpoints ingested before their node, fall back to
+    /// the first row for the node fields.
+    pub fn from_rows(rows: Vec<NodeModel>) -> Option<Node> {
+        let is_edge = |row: &NodeModel| !row.direction.as_deref().unwrap_or_default().is_empty();
+        let node_row = rows
+            .iter()
+            .find(|row| !is_edge(row))
+            .or(rows.first())?
+            .clone();
+        let mut node = Node::from(node_row);
+        node.relations = rows
+            .into_iter()
+            .filter(is_edge)
+            .map(|row| {
+                Relation::from(
+                    row.name,
+                    row.relation.unwrap_or_default(),
+                    row.relates_to.unwrap_or_default(),
+                    row.direction.as_deref() == Some("Out"),
+                )
+                .with_tags(row.tags.unwrap_or_default())
+            })
+            .collect();
+        Some(node)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,6 +78,14 @@ pub struct TraversalNode {
     pub node_type: String,
     pub relations: Vec<TraversalNode>,
     pub relation_ids: Vec<String>,
+    /// Tags of the node itself.
+    #[serde(default)]
+    pub tags: Vec<(String, String)>,
+    /// Tags of the edge to each of `relation_ids`, in the same order.
+    #[serde(default)]
+    pub relation_tags: Vec<Vec<(String, String)>>,
+    #[serde(default)]
+    pub total_children: Option<i64>,
 }
 
 impl TraversalNode {
@@ -56,17 +97,126 @@ impl TraversalNode {
             node_type,
             relations: vec![],
             relation_ids: vec![],
+            tags: vec![],
+            relation_tags: vec![],
+            total_children: None,
         }
     }
 
-    pub fn from(relation: RelationModel, depth: usize) -> TraversalNode {
+    /// Builds a node from its own row plus the edge rows to follow. Only
+    /// edge rows are checked against `filter`; the node row has no direction
+    /// and always contributes the node fields, falling back to the first row
+    /// for partitions that only hold edge rows.
+    pub fn from_rows(
+        rows: Vec<RelationModel>,
+        depth: usize,
+        filter: Option<&TagFilter>,
+    ) -> Option<TraversalNode> {
+        let is_edge =
+            |row: &RelationModel| !row.direction.as_deref().unwrap_or_default().is_empty();
+        let node_row = rows.iter().find(|row| !is_edge(row)).or(rows.first())?;
         let mut node = TraversalNode::new(
-            relation.uuid.clone(),
+            node_row.uuid,
             depth,
-            relation.name.clone(),
-            relation.node_type.clone(),
+            node_row.name.clone(),
+            node_row.node_type.clone(),
         );
-        node.relation_ids.push(relation.relates_to.clone().unwrap());
-        node
+        if !is_edge(node_row) {
+            node.tags = node_row.tags.clone().unwrap_or_default();
+            node.total_children = node_row.total_children;
+        }
+        for row in rows.into_iter().filter(is_edge) {
+            let tags = row.tags.unwrap_or_default();
+            let keep = match filter {
+                Some(filter) => filter.matches(&tags),
+                None => true,
+            };
+            if keep {
+                node.relation_ids.push(row.relates_to.unwrap_or_default());
+                node.relation_tags.push(tags);
+            }
+        }
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Node, TraversalNode};
+    use crate::{
+        db::model::{NodeModel, RelationModel},
+        domain::relation::TagFilter,
+    };
+    use uuid::Uuid;
+
+    #[test]
+    fn test_node_from_rows() {
+        let uuid = Uuid::new_v4();
+        let node_row = NodeModel {
+            uuid,
+            direction: Some(String::new()),
+            relation: Some(String::new()),
+            relates_to: Some(String::new()),
+            name: "node".to_string(),
+            path: "/node".to_string(),
+            ..NodeModel::default()
+        };
+        let edge_row = NodeModel {
+            uuid,
+            direction: Some("Out".to_string()),
+            relation: Some("uses".to_string()),
+            relates_to: Some(Uuid::new_v4().to_string()),
+            name: "other".to_string(),
+            tags: Some(vec![("confidence".to_string(), "0.8".to_string())]),
+            ..NodeModel::default()
+        };
+
+        let node = Node::from_rows(vec![edge_row, node_row]).unwrap();
+        assert_eq!(node.name, "node");
+        assert_eq!(node.relations.len(), 1);
+        assert!(node.relations[0].outbound);
+        assert_eq!(node.relations[0].rel_type, "uses");
+        assert_eq!(node.relations[0].tags[0].1, "0.8");
+        assert!(Node::from_rows(vec![]).is_none());
+    }
+
+    #[test]
+    fn test_traversal_node_filters_edge_rows_only() {
+        let uuid = Uuid::new_v4();
+        let tag = |key: &str, value: &str| vec![(key.to_string(), value.to_string())];
+        let node_row = RelationModel {
+            uuid,
+            direction: Some(String::new()),
+            relates_to: Some(String::new()),
+            name: "node".to_string(),
+            tags: Some(tag("owner", "team")),
+            ..RelationModel::default()
+        };
+        let edge = |relates_to: &str, tags| RelationModel {
+            uuid,
+            direction: Some("In".to_string()),
+            relates_to: Some(relates_to.to_string()),
+            tags,
+            ..RelationModel::default()
+        };
+        let rows = vec![
+            node_row,
+            edge("a", Some(tag("confidence", "high"))),
+            edge("b", Some(tag("confidence", "low"))),
+            edge("c", None),
+        ];
+
+        let filter = TagFilter::parse("confidence:high").unwrap();
+        let node = TraversalNode::from_rows(rows.clone(), 1, Some(&filter)).unwrap();
+        assert_eq!(node.name, "node");
+        assert_eq!(node.depth, 1);
+        assert_eq!(node.tags, tag("owner", "team"));
+        assert_eq!(node.relation_ids, vec!["a"]);
+        assert_eq!(node.relation_tags, vec![tag("confidence", "high")]);
+
+        let node = TraversalNode::from_rows(rows, 1, None).unwrap();
+        assert_eq!(node.relation_ids, vec!["a", "b", "c"]);
+        assert!(node.relation_tags[2].is_empty());
+        assert!(TraversalNode::from_rows(vec![], 0, None).is_none());
     }
 }
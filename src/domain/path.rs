@@ -0,0 +1,218 @@
+use super::s3::data::{GraphData, RawNode};
+use crate::db::model::path_to_uuid;
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// The path of the (implicit) root every top-level node hangs off. Every
+/// other path is the root followed by `/<escaped name>` per level, so node
+/// paths and relation endpoints alike read `/root/child`.
+pub const ROOT: &str = "";
+const SEPARATOR: char = '/';
+const ESCAPE: char = '\\';
+
+/// Escapes the separator and the escape character itself, so a name
+/// containing `/` cannot be mistaken for two levels.
+pub fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c == SEPARATOR || c == ESCAPE {
+            escaped.push(ESCAPE);
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub fn join(parent: &str, name: &str) -> String {
+    format!("{}{}{}", parent, SEPARATOR, escape(name))
+}
+
+pub fn from_segments<S: AsRef<str>>(segments: &[S]) -> String {
+    segments
+        .iter()
+        .fold(ROOT.to_owned(), |path, name| join(&path, name.as_ref()))
+}
+
+/// Splits a path into its unescaped names. The leading separator is
+/// optional, so hand-written paths like `root/child` parse too.
+pub fn parse(path: &str) -> Result<Vec<String>> {
+    let relative = path.strip_prefix(SEPARATOR).unwrap_or(path);
+    let mut segments = Vec::new();
+    let mut segment = String::new();
+    let mut chars = relative.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ESCAPE => match chars.next() {
+                Some(escaped) => segment.push(escaped),
+                None => return Err(eyre!("Invalid path '{}': dangling escape", path)),
+            },
+            SEPARATOR => segments.push(std::mem::take(&mut segment)),
+            c => segment.push(c),
+        }
+    }
+    segments.push(segment);
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(eyre!("Invalid path '{}'", path));
+    }
+    Ok(segments)
+}
+
+/// The unescaped last name of a path.
+pub fn name(path: &str) -> Option<String> {
+    parse(path).ok().and_then(|mut segments| segments.pop())
+}
+
+/// How paths were built before they were escaped: node paths joined the raw
+/// names behind a leading `/`, relation endpoints joined them without one.
+pub mod legacy {
+    pub fn node_path<S: AsRef<str>>(segments: &[S]) -> String {
+        segments.iter().fold(String::new(), |path, name| {
+            format!("{}/{}", path, name.as_ref())
+        })
+    }
+
+    pub fn relation_path<S: AsRef<str>>(segments: &[S]) -> String {
+        segments
+            .iter()
+            .map(|name| name.as_ref())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathKind {
+    Node,
+    RelationEndpoint,
+}
+
+/// A UUID an earlier version generated for a path that the canonical
+/// encoding maps elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UuidChange {
+    pub path: String,
+    pub kind: PathKind,
+    pub old_uuid: Uuid,
+    pub new_uuid: Uuid,
+}
+
+/// Lists the UUIDs of `data` that change with the canonical encoding: nodes
+/// with `/` or `\` in a name on their path, and every relation endpoint.
+pub fn uuid_changes(ingestion_id: &str, data: &GraphData) -> Vec<UuidChange> {
+    let mut changes = BTreeMap::new();
+    let mut record = |kind: PathKind, segments: &[String], legacy: String| {
+        let path = from_segments(segments);
+        let (old_uuid, new_uuid) = (
+            path_to_uuid(ingestion_id, &legacy),
+            path_to_uuid(ingestion_id, &path),
+        );
+        if old_uuid != new_uuid {
+            changes.insert(
+                (kind, path.clone()),
+                UuidChange {
+                    path,
+                    kind,
+                    old_uuid,
+                    new_uuid,
+                },
+            );
+        }
+    };
+
+    fn walk(
+        nodes: &[RawNode],
+        parent: &mut Vec<String>,
+        record: &mut dyn FnMut(PathKind, &[String], String),
+    ) {
+        for node in nodes {
+            parent.push(node.name.clone());
+            record(PathKind::Node, parent, legacy::node_path(parent));
+            walk(&node.children, parent, record);
+            parent.pop();
+        }
+    }
+    let mut parent = data.parent.clone().unwrap_or_default();
+    walk(&data.nodes, &mut parent, &mut record);
+
+    for relation in &data.relations {
+        for endpoint in [&relation.source, &relation.target] {
+            let legacy = legacy::relation_path(endpoint);
+            record(PathKind::RelationEndpoint, endpoint, legacy);
+        }
+    }
+    changes.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::s3::data::RawRelation;
+
+    fn segments(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_escaped_names_do_not_collide() {
+        let nested = from_segments(&segments(&["a", "b"]));
+        let slashed = from_segments(&segments(&["a/b"]));
+        assert_eq!(nested, "/a/b");
+        assert_eq!(slashed, "/a\\/b");
+        assert_ne!(
+            path_to_uuid("test", &nested),
+            path_to_uuid("test", &slashed)
+        );
+        assert_eq!(from_segments::<String>(&[]), ROOT);
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let names = segments(&["root", "a/b", "c\\d", "e"]);
+        let path = from_segments(&names);
+        assert_eq!(parse(&path).unwrap(), names);
+        assert_eq!(parse("root/child").unwrap(), segments(&["root", "child"]));
+        assert_eq!(name(&path).as_deref(), Some("e"));
+        assert!(parse("/a//b").is_err());
+        assert!(parse("/a\\").is_err());
+        assert!(parse(ROOT).is_err());
+    }
+
+    #[test]
+    fn test_uuid_changes() {
+        let node = |name: &str, children: Vec<RawNode>| RawNode {
+            name: name.to_string(),
+            type_field: "t".to_string(),
+            children,
+            ..RawNode::default()
+        };
+        let data = GraphData {
+            nodes: vec![node("root", vec![node("a/b", vec![]), node("c", vec![])])],
+            relations: vec![RawRelation {
+                type_field: "uses".to_string(),
+                source: segments(&["root", "c"]),
+                target: segments(&["root", "a/b"]),
+                tags: None,
+            }],
+            parent: None,
+        };
+        let changes = uuid_changes("test", &data);
+        let paths: Vec<(PathKind, &str)> = changes
+            .iter()
+            .map(|change| (change.kind, change.path.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                (PathKind::Node, "/root/a\\/b"),
+                (PathKind::RelationEndpoint, "/root/a\\/b"),
+                (PathKind::RelationEndpoint, "/root/c"),
+            ]
+        );
+        assert_eq!(changes[0].old_uuid, path_to_uuid("test", "/root/a/b"));
+        assert_eq!(changes[2].old_uuid, path_to_uuid("test", "root/c"));
+        assert_eq!(changes[2].new_uuid, path_to_uuid("test", "/root/c"));
+    }
+}
@@ -0,0 +1,620 @@
+use crate::application::AppState;
+use crate::db::model::{detached_relation_rows, process_nodes, NodeModel};
+use crate::domain::checkpoint::{Checkpoint, Progress, Resume};
+use crate::domain::diff::{DiffSummary, RowDiff};
+use crate::domain::ingestion::Ingestion;
+use crate::domain::job::{FailureKind, FileCounts, JobState};
+use crate::domain::relation::process_relations;
+use crate::domain::s3::{
+    data::GraphData, download::TransferWatch, format::InputFormat, stream::stream_graph,
+};
+use crate::domain::validation::{validate, Strictness};
+use crate::domain::webhook::validate_callback_url;
+use actix_web::web::Data;
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc, Mutex},
+};
+use tokio::{sync::mpsc, task::JoinHandle};
+use tracing::{error, info};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IngestionRequest {
+    pub files: Vec<String>,
+    pub ingestion_id: String,
+    /// Parses nested JSON input while writing it; other formats are rejected.
+    #[serde(default)]
+    pub streaming: bool,
+    #[serde(default)]
+    pub format: Option<InputFormat>,
+    /// Streaming mode never holds the whole tree, so it cannot validate and
+    /// only accepts the default `warn`, which then reports nothing.
+    #[serde(default)]
+    pub validation: Strictness,
+    /// Diffs the files against the rows already stored for `ingestion_id`:
+    /// only new or changed rows are written and vanished ones are deleted.
+    #[serde(default)]
+    pub incremental: bool,
+    /// Receives the final job status once every file is done.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+}
+
+impl IngestionRequest {
+    pub fn new(files: Vec<String>, ingestion_id: String) -> Self {
+        Self {
+            files,
+            ingestion_id,
+            streaming: false,
+            format: None,
+            validation: Strictness::default(),
+            incremental: false,
+            callback_url: None,
+        }
+    }
+
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    pub fn with_format(mut self, format: InputFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    pub fn with_validation(mut self, validation: Strictness) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    pub fn with_incremental(mut self, incremental: bool) -> Self {
+        self.incremental = incremental;
+        self
+    }
+
+    pub fn with_callback_url(mut self, callback_url: String) -> Self {
+        self.callback_url = Some(callback_url);
+        self
+    }
+
+    pub fn validate(&self) -> eyre::Result<()> {
+        if self.streaming && self.validation != Strictness::Warn {
+            return Err(eyre::eyre!(
+                "Only 'warn' validation is supported in streaming mode"
+            ));
+        }
+        if self.streaming && matches!(self.format, Some(format) if format != InputFormat::Json) {
+            return Err(eyre::eyre!(
+                "Only JSON input is supported in streaming mode"
+            ));
+        }
+        match &self.callback_url {
+            Some(url) => validate_callback_url(url),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FileError {
+    pub kind: FailureKind,
+    pub error: eyre::Report,
+    pub counts: FileCounts,
+}
+
+impl FileError {
+    fn input(error: eyre::Report) -> Self {
+        Self {
+            kind: FailureKind::Input,
+            error,
+            counts: FileCounts::default(),
+        }
+    }
+
+    fn storage(error: eyre::Report) -> Self {
+        Self {
+            kind: FailureKind::Storage,
+            error,
+            counts: FileCounts::default(),
+        }
+    }
+
+    fn with_counts(mut self, counts: FileCounts) -> Self {
+        self.counts = counts;
+        self
+    }
+}
+
+/// Runs every file of the job and keeps the `graph.ingestions` entry in
+/// sync: it is written when the job starts and again once all files are done.
+pub fn spawn_job(job_id: Uuid, request: IngestionRequest, state: Data<AppState>) -> JoinHandle<()> {
+    run_job(job_id, request, HashMap::new(), state)
+}
+
+/// Runs a job again from its checkpoints: completed files are skipped and
+/// the others continue after their last committed row. Incremental jobs
+/// start over instead, since the diff already skips what was written.
+pub fn resume_job(job_id: Uuid, resume: Resume, state: Data<AppState>) -> JoinHandle<()> {
+    let checkpoints = if resume.request.incremental {
+        HashMap::new()
+    } else {
+        resume.checkpoints
+    };
+    run_job(job_id, resume.request, checkpoints, state)
+}
+
+fn run_job(
+    job_id: Uuid,
+    request: IngestionRequest,
+    checkpoints: HashMap<usize, Checkpoint>,
+    state: Data<AppState>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        record_ingestion(&job_id, &state).await;
+        let saved = match serde_json::to_string(&request) {
+            Ok(json) => {
+                state
+                    .db
+                    .save_checkpoint_request(job_id, &request.ingestion_id, json)
+                    .await
+            }
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = saved {
+            error!("Error saving request of job {}: {}", job_id, err);
+        }
+        let diff = if request.incremental {
+            match load_diff(&request.ingestion_id, &state).await {
+                Ok(diff) => Some(Arc::new(Mutex::new(diff))),
+                Err(err) => {
+                    error!("Error loading stored rows: {}", err);
+                    for index in 0..request.files.len() {
+                        let kind = FailureKind::Storage;
+                        let message = format!("Error loading stored rows: {}", err);
+                        let counts = FileCounts::default();
+                        state
+                            .jobs
+                            .file_failed(&job_id, index, kind, message, counts);
+                    }
+                    finish_job(&job_id, &request, &state).await;
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+
+        let handles = spawn_files(
+            job_id,
+            request.clone(),
+            checkpoints,
+            diff.clone(),
+            state.clone(),
+        );
+        for handle in handles {
+            if let Err(err) = handle.await {
+                error!("Error joining task: {}", err);
+            }
+        }
+        if let Some(diff) = diff {
+            let summary = apply_removals(&job_id, &request.ingestion_id, &diff, &state).await;
+            state.jobs.set_diff(&job_id, summary);
+        }
+        finish_job(&job_id, &request, &state).await;
+    })
+}
+
+/// Records the final state of the job and hands it to the callback, if the
+/// request has one. Delivery runs on its own so retries never hold up the job.
+async fn finish_job(job_id: &Uuid, request: &IngestionRequest, state: &Data<AppState>) {
+    record_ingestion(job_id, state).await;
+    state.jobs.finish_job(job_id);
+    let (Some(url), Some(job)) = (request.callback_url.clone(), state.jobs.get(job_id)) else {
+        return;
+    };
+    let state = state.clone();
+    tokio::spawn(async move {
+        if let Err(err) = state.notifier.notify(&url, &job).await {
+            error!("Error delivering callback of job {}: {}", job.job_id, err);
+        }
+    });
+}
+
+/// Hashes the stored rows of the ingestion, a partition at a time.
+async fn load_diff(ingestion_id: &str, state: &AppState) -> eyre::Result<RowDiff> {
+    let mut diff = RowDiff::default();
+    let partitions = state.db.ingestion_partitions(ingestion_id).await?;
+    tokio::pin!(partitions);
+    while let Some(rows) = partitions.try_next().await? {
+        diff.add_stored(&rows);
+    }
+    Ok(diff)
+}
+
+/// Deletes what vanished from the snapshot. Skipped unless every file made
+/// it in, since a missing file would otherwise wipe its nodes.
+async fn apply_removals(
+    job_id: &Uuid,
+    ingestion_id: &str,
+    diff: &Mutex<RowDiff>,
+    state: &AppState,
+) -> DiffSummary {
+    let (removals, mut summary) = {
+        let diff = diff.lock().expect("row diff lock poisoned");
+        (diff.removals(), diff.summary.clone())
+    };
+    let succeeded = state
+        .jobs
+        .get(job_id)
+        .is_some_and(|job| job.state == JobState::Succeeded);
+    if !succeeded {
+        summary.deletions_skipped = true;
+        return summary;
+    }
+
+    let concurrency = state.db.concurrency_limit.max(1);
+    let mut nodes = stream::iter(removals.nodes)
+        .map(|id| state.db.delete_ingestion_node(ingestion_id, id))
+        .buffer_unordered(concurrency);
+    while let Some(result) = nodes.next().await {
+        match result {
+            Ok(()) => summary.nodes_removed += 1,
+            Err(err) => {
+                error!("Error removing node: {}", err);
+                summary.rows_failed += 1;
+            }
+        }
+    }
+    // Partitions are read back to learn the keys of their vanished rows.
+    let mut partitions = stream::iter(removals.partitions)
+        .map(|id| state.db.get_node_rows(id))
+        .buffer_unordered(concurrency);
+    while let Some(result) = partitions.next().await {
+        let rows = match result {
+            Ok(rows) => rows,
+            Err(err) => {
+                error!("Error reading node rows: {}", err);
+                summary.rows_failed += 1;
+                continue;
+            }
+        };
+        let keys = diff
+            .lock()
+            .expect("row diff lock poisoned")
+            .removed_rows(&rows);
+        for key in keys {
+            match state.db.delete_row(key).await {
+                Ok(()) => summary.rows_deleted += 1,
+                Err(err) => {
+                    error!("Error removing row: {}", err);
+                    summary.rows_failed += 1;
+                }
+            }
+        }
+    }
+    info!(
+        "Ingestion {} diff applied: {} nodes added, {} updated, {} removed",
+        ingestion_id, summary.nodes_added, summary.nodes_updated, summary.nodes_removed
+    );
+    summary
+}
+
+/// Keeps only the rows the diff says need writing; everything passes when
+/// the job is not incremental.
+fn select_rows(diff: &Option<Arc<Mutex<RowDiff>>>, rows: Vec<NodeModel>) -> Vec<NodeModel> {
+    match diff {
+        Some(diff) => diff.lock().expect("row diff lock poisoned").changed(rows),
+        None => rows,
+    }
+}
+
+async fn record_ingestion(job_id: &Uuid, state: &AppState) {
+    let Some(job) = state.jobs.get(job_id) else {
+        return;
+    };
+    let ingestion = Ingestion::from_job(&job);
+    if let Err(err) = state.db.upsert_ingestion(ingestion.into()).await {
+        error!("Error recording ingestion {}: {}", job.ingestion_id, err);
+    }
+}
+
+/// One file of a job, with the number of its rows already committed by an
+/// earlier run.
+struct FileTask {
+    job_id: Uuid,
+    index: usize,
+    file: String,
+    offset: usize,
+}
+
+fn spawn_files(
+    job_id: Uuid,
+    request: IngestionRequest,
+    checkpoints: HashMap<usize, Checkpoint>,
+    diff: Option<Arc<Mutex<RowDiff>>>,
+    state: Data<AppState>,
+) -> Vec<JoinHandle<()>> {
+    let mut handles = vec![];
+    for (index, file) in request.files.iter().cloned().enumerate() {
+        let checkpoint = checkpoints.get(&index).copied().unwrap_or_default();
+        if checkpoint.completed {
+            info!("File {} already ingested, skipping", file);
+            let counts = FileCounts {
+                rows_written: checkpoint.rows_committed,
+                ..FileCounts::default()
+            };
+            state.jobs.file_finished(&job_id, index, counts, None);
+            continue;
+        }
+        let task = FileTask {
+            job_id,
+            index,
+            file,
+            offset: checkpoint.rows_committed,
+        };
+        let request = request.clone();
+        let diff = diff.clone();
+        let state = state.clone();
+        let handle = tokio::spawn(async move {
+            let _permit = match state.semaphore.acquire().await {
+                Ok(permit) => permit,
+                Err(err) => {
+                    let kind = FailureKind::Storage;
+                    let counts = FileCounts::default();
+                    state
+                        .jobs
+                        .file_failed(&job_id, index, kind, err.to_string(), counts);
+                    return;
+                }
+            };
+            state.jobs.file_started(&job_id, index);
+            let result = if request.streaming {
+                process_file_streaming(&request, &task, &diff, &state).await
+            } else {
+                process_file(&request, &task, &diff, &state).await
+            };
+            let file = &task.file;
+            match result {
+                Ok((counts, first_error)) => {
+                    info!(
+                        "File {} ingested: {} rows written, {} rows failed",
+                        file, counts.rows_written, counts.rows_failed
+                    );
+                    state
+                        .jobs
+                        .file_finished(&job_id, index, counts, first_error);
+                }
+                Err(err) => {
+                    error!("Error occured when ingesting file {}: {}", file, err.error);
+                    let message = err.error.to_string();
+                    state
+                        .jobs
+                        .file_failed(&job_id, index, err.kind, message, err.counts);
+                }
+            }
+        });
+        handles.push(handle);
+    }
+    handles
+}
+
+/// Flattens a parsed file into the rows `insert_nodes` writes.
+pub async fn build_rows(ingestion_id: &str, data: GraphData) -> eyre::Result<Vec<NodeModel>> {
+    let relations = process_relations(ingestion_id, data.relations);
+    let parent = data.parent.unwrap_or_default();
+    let mut nodes = process_nodes(ingestion_id, &parent, data.nodes, relations.clone()).await?;
+    nodes.extend(detached_relation_rows(ingestion_id, &nodes, &relations));
+    Ok(nodes)
+}
+
+/// Writes the rows of one file in order and checkpoints after every chunk,
+/// skipping the rows an earlier run already committed.
+struct FileWriter<'a> {
+    task: &'a FileTask,
+    diff: &'a Option<Arc<Mutex<RowDiff>>>,
+    state: &'a AppState,
+    progress: Progress,
+    counts: FileCounts,
+    first_error: Option<String>,
+}
+
+impl<'a> FileWriter<'a> {
+    fn new(
+        task: &'a FileTask,
+        diff: &'a Option<Arc<Mutex<RowDiff>>>,
+        state: &'a AppState,
+        counts: FileCounts,
+    ) -> Self {
+        Self {
+            task,
+            diff,
+            state,
+            progress: Progress::new(task.offset),
+            counts,
+            first_error: None,
+        }
+    }
+
+    async fn write(&mut self, mut chunk: Vec<NodeModel>) -> Result<(), FileError> {
+        let skip = self.progress.advance(chunk.len());
+        chunk.drain(..skip);
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        let chunk = select_rows(self.diff, chunk);
+        let summary = self
+            .state
+            .db
+            .insert_nodes(chunk)
+            .await
+            .map_err(|err| FileError::storage(err).with_counts(self.counts.clone()))?;
+        // Unindexed nodes are written again on resume so they get indexed.
+        self.progress
+            .written(summary.rows_failed + summary.nodes_unindexed);
+        self.counts.rows_written += summary.rows_written;
+        self.counts.rows_failed += summary.rows_failed;
+        self.counts.nodes_unindexed += summary.nodes_unindexed;
+        if self.first_error.is_none() {
+            self.first_error = summary.first_error;
+        }
+        let (written, failed) = (self.counts.rows_written, self.counts.rows_failed);
+        let task = self.task;
+        self.state
+            .jobs
+            .rows_written(&task.job_id, task.index, written, failed);
+        self.checkpoint(false).await;
+        Ok(())
+    }
+
+    async fn checkpoint(&self, finished: bool) {
+        let task = self.task;
+        let checkpoint = self.progress.checkpoint(finished);
+        let saved = self
+            .state
+            .db
+            .save_checkpoint(
+                task.job_id,
+                task.index,
+                &task.file,
+                checkpoint.rows_committed,
+                checkpoint.completed,
+            )
+            .await;
+        if let Err(err) = saved {
+            error!("Error saving checkpoint of file {}: {}", task.file, err);
+        }
+    }
+
+    async fn finish(self) -> (FileCounts, Option<String>) {
+        self.checkpoint(true).await;
+        (self.counts, self.first_error)
+    }
+}
+
+async fn process_file(
+    request: &IngestionRequest,
+    task: &FileTask,
+    diff: &Option<Arc<Mutex<RowDiff>>>,
+    state: &AppState,
+) -> Result<(FileCounts, Option<String>), FileError> {
+    let ingestion_id = &request.ingestion_id;
+    let file = task.file.as_str();
+    info!(
+        "Processing file {} with ingestion id {}",
+        file, ingestion_id
+    );
+    let format = InputFormat::detect(file, request.format);
+    let mut contents = state
+        .sources
+        .read_graph_as(file, format)
+        .await
+        .map_err(FileError::input)?;
+    let nodes_parsed = contents.node_count();
+    let relations_parsed = contents.relations.len();
+    let report = validate(&mut contents, request.validation);
+    state
+        .jobs
+        .validation_warnings(&task.job_id, task.index, &report);
+    let counts = FileCounts {
+        nodes_parsed,
+        relations_parsed,
+        issues_found: report.issues_found,
+        records_skipped: report.records_skipped,
+        issues: report.issues,
+        ..FileCounts::default()
+    };
+    if request.validation == Strictness::Reject && counts.issues_found > 0 {
+        let error = eyre::eyre!(
+            "File rejected by validation: {} issues found",
+            counts.issues_found
+        );
+        return Err(FileError::input(error).with_counts(counts));
+    }
+    let nodes = build_rows(ingestion_id, contents)
+        .await
+        .map_err(FileError::input)?;
+    let chunk_size = state.chunk_size.max(1);
+    let mut writer = FileWriter::new(task, diff, state, counts);
+    let mut rows = nodes.into_iter();
+    loop {
+        let chunk: Vec<NodeModel> = rows.by_ref().take(chunk_size).collect();
+        if chunk.is_empty() {
+            break;
+        }
+        writer.write(chunk).await?;
+    }
+    Ok(writer.finish().await)
+}
+
+async fn process_file_streaming(
+    request: &IngestionRequest,
+    task: &FileTask,
+    diff: &Option<Arc<Mutex<RowDiff>>>,
+    state: &AppState,
+) -> Result<(FileCounts, Option<String>), FileError> {
+    let ingestion_id = request.ingestion_id.clone();
+    let file = task.file.as_str();
+    info!("Streaming file {} with ingestion id {}", file, ingestion_id);
+    // Only nested JSON has a streaming parser; a file whose extension names
+    // another format would otherwise be misread as JSON.
+    let format = InputFormat::detect(file, request.format);
+    if format != InputFormat::Json {
+        let error = eyre::eyre!("Streaming is not supported for {:?} input", format);
+        return Err(FileError::input(error));
+    }
+    let (bucket_ops, key) = state.sources.resolve(file).map_err(FileError::input)?;
+    let reader = bucket_ops
+        .get_reader(&key)
+        .await
+        .map_err(FileError::input)?;
+    let (reader, transfer_failed) = TransferWatch::new(reader);
+
+    // The channel is bounded so the parser blocks while the writer catches up,
+    // keeping at most a couple of chunks in memory at any time.
+    let chunk_size = state.chunk_size.max(1);
+    let (sender, mut receiver) = mpsc::channel::<Vec<NodeModel>>(2);
+    let parser = tokio::task::spawn_blocking(move || {
+        let mut chunk = Vec::with_capacity(chunk_size);
+        let summary = stream_graph(reader, &ingestion_id, &mut |row| {
+            chunk.push(row);
+            if chunk.len() >= chunk_size {
+                let full = std::mem::replace(&mut chunk, Vec::with_capacity(chunk_size));
+                sender
+                    .blocking_send(full)
+                    .map_err(|_| eyre::eyre!("row writer stopped"))?;
+            }
+            Ok(())
+        })?;
+        if !chunk.is_empty() {
+            sender
+                .blocking_send(chunk)
+                .map_err(|_| eyre::eyre!("row writer stopped"))?;
+        }
+        Ok::<_, eyre::Report>(summary)
+    });
+
+    let mut writer = FileWriter::new(task, diff, state, FileCounts::default());
+    while let Some(chunk) = receiver.recv().await {
+        writer.write(chunk).await?;
+    }
+
+    let summary = parser
+        .await
+        .map_err(|err| FileError::storage(err.into()).with_counts(writer.counts.clone()))?
+        .map_err(|err| {
+            // A download that broke off is worth retrying; bad data is not.
+            let error = if transfer_failed.load(Ordering::Relaxed) {
+                FileError::storage(err)
+            } else {
+                FileError::input(err)
+            };
+            error.with_counts(writer.counts.clone())
+        })?;
+    writer.counts.nodes_parsed = summary.nodes;
+    writer.counts.relations_parsed = summary.relations;
+    Ok(writer.finish().await)
+}
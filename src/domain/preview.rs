@@ -0,0 +1,171 @@
+use super::{
+    pipeline::build_rows,
+    s3::{
+        data::{GraphData, RawNode},
+        format::InputFormat,
+    },
+    validation::{validate, Strictness, ValidationReport},
+};
+use crate::db::model::NodeModel;
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PreviewRequest {
+    pub ingestion_id: String,
+    #[serde(default)]
+    pub file: Option<String>,
+    #[serde(default)]
+    pub data: Option<GraphData>,
+    #[serde(default)]
+    pub format: Option<InputFormat>,
+    #[serde(default)]
+    pub validation: Strictness,
+}
+
+impl PreviewRequest {
+    pub fn from_file(file: String, ingestion_id: String) -> Self {
+        Self {
+            ingestion_id,
+            file: Some(file),
+            data: None,
+            format: None,
+            validation: Strictness::default(),
+        }
+    }
+
+    pub fn from_data(data: GraphData, ingestion_id: String) -> Self {
+        Self {
+            ingestion_id,
+            file: None,
+            data: Some(data),
+            format: None,
+            validation: Strictness::default(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match (&self.file, &self.data) {
+            (Some(_), None) | (None, Some(_)) => Ok(()),
+            _ => Err(eyre!("Exactly one of 'file' or 'data' must be provided")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct PreviewSummary {
+    pub nodes_by_type: BTreeMap<String, usize>,
+    pub relations_by_type: BTreeMap<String, usize>,
+    pub max_depth: usize,
+    pub rows: usize,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Preview {
+    pub ingestion_id: String,
+    pub summary: PreviewSummary,
+    pub validation: ValidationReport,
+    /// Node path to the UUID `path_to_uuid` generates for it.
+    pub uuids: BTreeMap<String, Uuid>,
+    pub rows: Vec<NodeModel>,
+}
+
+/// Runs the same validation and flattening as an ingestion without touching
+/// Scylla. Rejections show up in the report instead of failing the preview.
+pub async fn preview(
+    ingestion_id: &str,
+    mut data: GraphData,
+    strictness: Strictness,
+) -> Result<Preview> {
+    let validation = validate(&mut data, strictness);
+    let mut summary = PreviewSummary::default();
+    count_nodes(&data.nodes, 1, &mut summary);
+    for relation in &data.relations {
+        *summary
+            .relations_by_type
+            .entry(relation.type_field.clone())
+            .or_default() += 1;
+    }
+
+    let rows = build_rows(ingestion_id, data).await?;
+    summary.rows = rows.len();
+    let uuids = rows
+        .iter()
+        .filter(|row| row.direction.is_none())
+        .map(|row| (row.path.clone(), row.uuid))
+        .collect();
+    Ok(Preview {
+        ingestion_id: ingestion_id.to_owned(),
+        summary,
+        validation,
+        uuids,
+        rows,
+    })
+}
+
+fn count_nodes(nodes: &[RawNode], depth: usize, summary: &mut PreviewSummary) {
+    for node in nodes {
+        *summary
+            .nodes_by_type
+            .entry(node.type_field.clone())
+            .or_default() += 1;
+        summary.max_depth = summary.max_depth.max(depth);
+        count_nodes(&node.children, depth + 1, summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::model::path_to_uuid;
+
+    #[tokio::test]
+    async fn test_preview_example() {
+        let file = std::fs::File::open("tests/data/example.json").unwrap();
+        let data: GraphData = serde_json::from_reader(file).unwrap();
+        let node_count = data.node_count();
+        let relation_count = data.relations.len();
+
+        let preview = preview("test", data, Strictness::Warn).await.unwrap();
+        let summary = &preview.summary;
+        assert_eq!(summary.nodes_by_type.values().sum::<usize>(), node_count);
+        assert_eq!(summary.nodes_by_type["root_type"], 1);
+        assert_eq!(
+            summary.relations_by_type.values().sum::<usize>(),
+            relation_count
+        );
+        assert_eq!(summary.rows, preview.rows.len());
+        assert!(summary.max_depth > 1);
+        assert_eq!(preview.uuids.len(), node_count);
+        assert_eq!(preview.uuids["/root"], path_to_uuid("test", "/root"));
+        assert!(preview
+            .validation
+            .issues
+            .iter()
+            .all(|issue| issue.kind != crate::domain::validation::IssueKind::DuplicateSibling));
+    }
+
+    #[tokio::test]
+    async fn test_preview_empty() {
+        let preview = preview("test", GraphData::default(), Strictness::Reject)
+            .await
+            .unwrap();
+        assert_eq!(preview.summary, PreviewSummary::default());
+        assert!(preview.rows.is_empty());
+    }
+
+    #[test]
+    fn test_preview_request_validate() {
+        let request = PreviewRequest::from_data(GraphData::default(), "test".to_string());
+        assert!(request.validate().is_ok());
+        let mut request = PreviewRequest::from_file("graph.json".to_string(), "test".to_string());
+        assert!(request.validate().is_ok());
+        request.data = Some(GraphData::default());
+        assert!(request.validate().is_err());
+        request.file = None;
+        request.data = None;
+        assert!(request.validate().is_err());
+    }
+}
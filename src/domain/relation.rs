@@ -1,17 +1,9 @@
-use crate::domain::s3::data::RawRelation;
+use crate::db::model::{extract_tag_pairs, path_to_uuid};
+use crate::domain::{path, s3::data::RawRelation};
+use eyre::{eyre, Result};
 use serde::Deserialize;
 use serde::Serialize;
 use std::collections::HashMap;
-use uuid::Uuid;
-
-const NAMESPACE_UUID: Uuid = Uuid::from_bytes([
-    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-]);
-
-pub fn url_to_uuid(ingestion_id: String, url: String) -> Uuid {
-    let unique_id = format!("{}/{}", ingestion_id, url);
-    Uuid::new_v5(&NAMESPACE_UUID, unique_id.as_bytes())
-}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Relation {
@@ -20,20 +12,22 @@ pub struct Relation {
     pub outbound: bool,
     pub target_name: String,
     pub relates_to: String,
+    #[serde(default)]
+    pub tags: Vec<(String, String)>,
 }
 
 impl Relation {
+    /// A relation to the node at `url`, a canonical path (see
+    /// `domain::path`).
     pub fn new(ingestion_id: String, rel_type: String, url: String, outbound: bool) -> Self {
-        let name = url
-            .split("/")
-            .last()
-            .map_or("default".to_string(), |s| s.to_string());
-        let relates_to = url_to_uuid(ingestion_id, url);
+        let name = path::name(&url).unwrap_or_else(|| "default".to_string());
+        let relates_to = path_to_uuid(&ingestion_id, &url);
         Self {
-            rel_type: rel_type,
-            outbound: outbound,
+            rel_type,
+            outbound,
             target_name: name,
             relates_to: relates_to.to_string(),
+            tags: vec![],
         }
     }
     pub fn from(name: String, rel_type: String, relates_to: String, outbound: bool) -> Self {
@@ -42,8 +36,47 @@ impl Relation {
             target_name: name,
             relates_to,
             outbound,
+            tags: vec![],
         }
     }
+
+    pub fn with_tags(mut self, tags: Vec<(String, String)>) -> Self {
+        self.tags = tags;
+        self
+    }
+}
+
+/// A `key` or `key:value` filter matched against relation tags.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagFilter {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl TagFilter {
+    pub fn parse(filter: &str) -> Result<TagFilter> {
+        let (key, value) = match filter.split_once(':') {
+            Some((key, value)) => (key, Some(value.to_owned())),
+            None => (filter, None),
+        };
+        if key.is_empty() {
+            return Err(eyre!("Tag filter '{}' has an empty key", filter));
+        }
+        Ok(TagFilter {
+            key: key.to_owned(),
+            value,
+        })
+    }
+
+    pub fn matches(&self, tags: &[(String, String)]) -> bool {
+        tags.iter().any(|(key, value)| {
+            key == &self.key
+                && match &self.value {
+                    Some(expected) => expected == value,
+                    None => true,
+                }
+        })
+    }
 }
 
 pub fn process_relations(
@@ -53,15 +86,17 @@ pub fn process_relations(
     let mut rels: HashMap<String, Vec<Relation>> = HashMap::new();
 
     for r in relations {
-        let source = join_paths(&r.source);
-        let target = join_paths(&r.target);
+        let source = path::from_segments(&r.source);
+        let target = path::from_segments(&r.target);
+        let tags = extract_tag_pairs(r.tags.unwrap_or_default());
 
         let relation = Relation::new(
             ingestion_id.to_owned(),
             r.type_field.clone(),
             target.clone(),
             true,
-        );
+        )
+        .with_tags(tags.clone());
         rels.entry(source.clone()).or_insert(vec![]).push(relation);
 
         let relation = Relation::new(
@@ -69,25 +104,19 @@ pub fn process_relations(
             r.type_field.clone(),
             source.clone(),
             false,
-        );
+        )
+        .with_tags(tags);
         rels.entry(target.clone()).or_insert(vec![]).push(relation);
     }
 
     rels
 }
 
-fn join_paths(paths: &[String]) -> String {
-    paths
-        .iter()
-        .map(|p| p.as_str())
-        .collect::<Vec<_>>()
-        .join("/")
-}
-
 #[cfg(test)]
 mod tests {
+    use crate::db::model::{path_from_segments, path_to_uuid};
     use crate::domain::{
-        relation::{join_paths, process_relations},
+        relation::{process_relations, TagFilter},
         s3::data::{RawRelation, RawTag},
     };
 
@@ -124,17 +153,42 @@ mod tests {
         ];
         let result = process_relations("test", relations);
         println!("{:?}", result);
-        assert_eq!(result.len(), 2);
+        assert_eq!(result.len(), 4);
+        let expected = vec![("tag_type_x".to_string(), "value".to_string())];
+        assert_eq!(result["/sourceA1"][0].tags, expected);
+        assert_eq!(result["/targetA1"][0].tags, expected);
+        assert!(result["/sourceB1/sourceB2"][0].tags.is_empty());
+        assert_eq!(result["/sourceB1/sourceB2"][0].target_name, "targetB2");
     }
 
     #[test]
-    fn test_join_paths() {
-        let path = vec!["a".to_string(), "b".to_string(), "c".to_string()];
-        let result = join_paths(&path);
-        assert_eq!(result, "a/b/c");
-
-        let single_path = vec!["a".to_string()];
-        let result = join_paths(&single_path);
-        assert_eq!(result, "a");
+    fn test_tag_filter() {
+        let tags = vec![("confidence".to_string(), "high".to_string())];
+        assert!(TagFilter::parse("confidence").unwrap().matches(&tags));
+        assert!(TagFilter::parse("confidence:high").unwrap().matches(&tags));
+        assert!(!TagFilter::parse("confidence:low").unwrap().matches(&tags));
+        assert!(!TagFilter::parse("source").unwrap().matches(&tags));
+        assert!(TagFilter::parse(":high").is_err());
+    }
+
+    #[test]
+    fn test_relation_endpoints_match_node_paths() {
+        let relations = vec![RawRelation {
+            type_field: "uses".to_string(),
+            source: vec!["a".to_string(), "b".to_string()],
+            target: vec!["a/b".to_string()],
+            tags: None,
+        }];
+        let result = process_relations("test", relations);
+        let outbound = &result["/a/b"][0];
+        assert_eq!(outbound.target_name, "a/b");
+        assert_eq!(
+            outbound.relates_to,
+            path_to_uuid("test", &path_from_segments(&["a/b".to_string()])).to_string()
+        );
+        assert_eq!(
+            result["/a\\/b"][0].relates_to,
+            path_to_uuid("test", "/a/b").to_string()
+        );
     }
 }
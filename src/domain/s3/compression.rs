@@ -0,0 +1,162 @@
+use super::download::ObjectReader;
+use flate2::read::MultiGzDecoder;
+use std::io::{BufRead, BufReader, Read, Result};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_extension(path: &str) -> Compression {
+        match extension(path).as_deref() {
+            Some("gz") | Some("gzip") => Compression::Gzip,
+            Some("zst") | Some("zstd") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// Magic bytes win over the extension, so a `.json.gz` object that was
+    /// already decoded in transit is still read as plain JSON.
+    pub fn detect(path: &str, head: &[u8]) -> Compression {
+        if head.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else if head.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if head.len() >= ZSTD_MAGIC.len() {
+            Compression::None
+        } else {
+            Compression::from_extension(path)
+        }
+    }
+}
+
+fn extension(path: &str) -> Option<String> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let name = name.split(['?', '#']).next().unwrap_or_default();
+    name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase())
+}
+
+/// Strips a trailing compression extension, e.g. `graph.json.gz` becomes
+/// `graph.json`, so the input format can be guessed from what remains.
+pub fn strip_extension(path: &str) -> &str {
+    let end = path.find(['?', '#']).unwrap_or(path.len());
+    match Compression::from_extension(path) {
+        Compression::None => path,
+        _ => path[..end].rsplit_once('.').map_or(path, |(stem, _)| stem),
+    }
+}
+
+/// Wraps an object reader and picks the decoder on the first read. Sniffing
+/// is deferred because the underlying reader may block and must only be
+/// touched from a blocking thread.
+pub struct Decompressed {
+    path: String,
+    pending: Option<BufReader<ObjectReader>>,
+    reader: Option<ObjectReader>,
+}
+
+impl Decompressed {
+    pub fn new(reader: ObjectReader, path: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+            pending: Some(BufReader::new(reader)),
+            reader: None,
+        }
+    }
+
+    fn open(mut reader: BufReader<ObjectReader>, path: &str) -> Result<ObjectReader> {
+        Ok(match Compression::detect(path, reader.fill_buf()?) {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+            Compression::Zstd => Box::new(BufReader::new(
+                zstd::stream::read::Decoder::with_buffer(reader)?,
+            )),
+        })
+    }
+}
+
+impl Read for Decompressed {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if let Some(pending) = self.pending.take() {
+            self.reader = Some(Self::open(pending, &self.path)?);
+        }
+        match self.reader.as_mut() {
+            Some(reader) => reader.read(buf),
+            None => Err(std::io::Error::other("decompression reader failed to open")),
+        }
+    }
+}
+
+pub fn decompress(reader: ObjectReader, path: &str) -> ObjectReader {
+    Box::new(Decompressed::new(reader, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    const INPUT: &str = r#"{"nodes": [], "relations": []}"#;
+
+    fn read_all(bytes: Vec<u8>, path: &str) -> String {
+        let mut output = String::new();
+        decompress(Box::new(Cursor::new(bytes)), path)
+            .read_to_string(&mut output)
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn test_detect_compression() {
+        assert_eq!(
+            Compression::from_extension("s3://bucket/graph.json.gz"),
+            Compression::Gzip
+        );
+        assert_eq!(
+            Compression::from_extension("https://example.com/graph.json.ZST?token=1"),
+            Compression::Zstd
+        );
+        assert_eq!(Compression::from_extension("graph.json"), Compression::None);
+        assert_eq!(
+            Compression::detect("graph.json", &GZIP_MAGIC),
+            Compression::Gzip
+        );
+        assert_eq!(
+            Compression::detect("graph.json.gz", INPUT.as_bytes()),
+            Compression::None
+        );
+    }
+
+    #[test]
+    fn test_strip_extension() {
+        assert_eq!(strip_extension("s3://b/graph.json.gz"), "s3://b/graph.json");
+        assert_eq!(strip_extension("nodes.csv.zst?x=1"), "nodes.csv");
+        assert_eq!(strip_extension("graph.json"), "graph.json");
+    }
+
+    #[test]
+    fn test_decompress_gzip() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(INPUT.as_bytes()).unwrap();
+        let bytes = encoder.finish().unwrap();
+        assert_eq!(read_all(bytes.clone(), "graph.json.gz"), INPUT);
+        assert_eq!(read_all(bytes, "graph.json"), INPUT);
+    }
+
+    #[test]
+    fn test_decompress_zstd() {
+        let bytes = zstd::encode_all(INPUT.as_bytes(), 0).unwrap();
+        assert_eq!(read_all(bytes, "graph.json.zst"), INPUT);
+    }
+
+    #[test]
+    fn test_plain_input_passes_through() {
+        assert_eq!(read_all(INPUT.as_bytes().to_vec(), "graph.json"), INPUT);
+    }
+}
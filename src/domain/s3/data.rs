@@ -6,6 +6,23 @@ use serde::Serialize;
 pub struct GraphData {
     pub nodes: Vec<RawNode>,
     pub relations: Vec<RawRelation>,
+    /// Marks a continuation file: the path of a node ingested from another
+    /// file of the same ingestion, whose children `nodes` are appended to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Vec<String>>,
+}
+
+impl GraphData {
+    pub fn node_count(&self) -> usize {
+        count_nodes(&self.nodes)
+    }
+}
+
+fn count_nodes(nodes: &[RawNode]) -> usize {
+    nodes
+        .iter()
+        .map(|node| 1 + count_nodes(&node.children))
+        .sum()
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
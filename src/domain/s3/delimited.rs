@@ -0,0 +1,145 @@
+use super::{
+    data::{GraphData, RawRelation, RawTag},
+    ndjson::{build_tree, PathNode},
+};
+use crate::domain::path;
+use eyre::{eyre, Result};
+use std::io::Read;
+
+const PATH_COLUMN: &str = "path";
+const TYPE_COLUMN: &str = "type";
+const TOTAL_CHILDREN_COLUMN: &str = "total_children";
+const SOURCE_COLUMN: &str = "source";
+const TARGET_COLUMN: &str = "target";
+
+/// Reads either a nodes file (`path,type,...`) or an edges file
+/// (`source,target,type,...`), telling them apart by their header. Every
+/// other column becomes a tag named after its header.
+pub fn read_csv<R: Read>(reader: R) -> Result<GraphData> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let type_index =
+        column(TYPE_COLUMN).ok_or_else(|| eyre!("Missing '{}' column", TYPE_COLUMN))?;
+
+    if let (Some(source), Some(target)) = (column(SOURCE_COLUMN), column(TARGET_COLUMN)) {
+        let reserved = [source, target, type_index];
+        let mut relations = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+            relations.push(RawRelation {
+                type_field: record[type_index].to_owned(),
+                source: split_path(&record[source])?,
+                target: split_path(&record[target])?,
+                tags: tags(&headers, &record, &reserved),
+            });
+        }
+        return Ok(GraphData {
+            nodes: vec![],
+            relations,
+            parent: None,
+        });
+    }
+
+    let path = column(PATH_COLUMN).ok_or_else(|| {
+        eyre!(
+            "Expected a '{}' column for nodes or '{}' and '{}' columns for edges",
+            PATH_COLUMN,
+            SOURCE_COLUMN,
+            TARGET_COLUMN
+        )
+    })?;
+    let total_children = column(TOTAL_CHILDREN_COLUMN);
+    let reserved: Vec<usize> = [Some(path), Some(type_index), total_children]
+        .into_iter()
+        .flatten()
+        .collect();
+    let mut nodes = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let total_children = match total_children.map(|i| &record[i]) {
+            Some(value) if !value.is_empty() => Some(value.parse()?),
+            _ => None,
+        };
+        nodes.push(PathNode {
+            path: split_path(&record[path])?,
+            type_field: record[type_index].to_owned(),
+            tags: tags(&headers, &record, &reserved),
+            total_children,
+        });
+    }
+    Ok(GraphData {
+        nodes: build_tree(nodes)?,
+        relations: vec![],
+        parent: None,
+    })
+}
+
+pub fn split_path(path: &str) -> Result<Vec<String>> {
+    path::parse(path)
+}
+
+fn tags(
+    headers: &csv::StringRecord,
+    record: &csv::StringRecord,
+    reserved: &[usize],
+) -> Option<Vec<RawTag>> {
+    let tags: Vec<RawTag> = headers
+        .iter()
+        .zip(record.iter())
+        .enumerate()
+        .filter(|(index, (_, value))| !reserved.contains(index) && !value.is_empty())
+        .map(|(_, (header, value))| RawTag {
+            type_field: header.to_owned(),
+            value: value.to_owned(),
+        })
+        .collect();
+    if tags.is_empty() {
+        None
+    } else {
+        Some(tags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_csv;
+
+    #[test]
+    fn test_read_nodes_csv() {
+        let input = "path,type,owner,total_children\n\
+                     root,root_type,,1\n\
+                     root/child,leaf,alice,\n";
+        let data = read_csv(input.as_bytes()).unwrap();
+        assert_eq!(data.nodes.len(), 1);
+        assert_eq!(data.nodes[0].total_children, Some(1));
+        assert!(data.nodes[0].tags.is_none());
+        let child = &data.nodes[0].children[0];
+        assert_eq!(child.name, "child");
+        let tags = child.tags.as_ref().unwrap();
+        assert_eq!(tags[0].type_field, "owner");
+        assert_eq!(tags[0].value, "alice");
+    }
+
+    #[test]
+    fn test_read_edges_csv() {
+        let input = "source,target,type,confidence\n\
+                     root/a,root/b,depends_on,0.8\n";
+        let data = read_csv(input.as_bytes()).unwrap();
+        assert!(data.nodes.is_empty());
+        assert_eq!(data.relations.len(), 1);
+        assert_eq!(data.relations[0].source, vec!["root", "a"]);
+        assert_eq!(data.relations[0].target, vec!["root", "b"]);
+        assert_eq!(data.relations[0].type_field, "depends_on");
+        assert_eq!(data.relations[0].tags.as_ref().unwrap()[0].value, "0.8");
+    }
+
+    #[test]
+    fn test_read_csv_invalid() {
+        assert!(read_csv("name,type\na,b\n".as_bytes()).is_err());
+        assert!(read_csv("path\na\n".as_bytes()).is_err());
+        assert!(read_csv("path,type\na//b,t\n".as_bytes()).is_err());
+    }
+}
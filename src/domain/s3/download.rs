@@ -1,21 +1,119 @@
-use super::data::GraphData;
+use super::{compression::decompress, data::GraphData};
 use async_trait::async_trait;
-use eyre::Result;
+use eyre::{eyre, Result};
+use futures::TryStreamExt;
 use s3::{creds::Credentials, Bucket, Region};
-use std::{fs::File, io::Read, time::Duration};
+use std::{
+    fs::File,
+    io::{self, BufReader, ErrorKind, Read},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio_util::io::{StreamReader, SyncIoBridge};
+
+pub type ObjectReader = Box<dyn Read + Send>;
+
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+/// Presigned URLs only have to be valid when the download starts.
+const PRESIGN_EXPIRY_SECS: u32 = 300;
+/// The kind of the read errors remote readers return when the download
+/// breaks off, as opposed to errors about the data itself.
+const TRANSFER_ERROR: ErrorKind = ErrorKind::ConnectionAborted;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a download may stall between two reads. There is no limit on
+/// the whole transfer, since the writer may drain a large object slowly.
+const READ_TIMEOUT: Duration = Duration::from_secs(290);
+
+fn client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT)
+}
+
+fn transfer_error(err: reqwest::Error) -> io::Error {
+    io::Error::new(TRANSFER_ERROR, err)
+}
+
+/// Streams a response body that already passed its status check; a failed
+/// download surfaces as a read error instead of a truncated object.
+fn response_reader(response: reqwest::Response, path: &str) -> ObjectReader {
+    let stream = response.bytes_stream().map_err(transfer_error);
+    let reader = SyncIoBridge::new(StreamReader::new(stream));
+    decompress(Box::new(reader), path)
+}
+
+/// Parses nested JSON straight from an object reader, decompressing on the
+/// fly instead of buffering the whole object first.
+async fn read_json(reader: ObjectReader) -> Result<GraphData> {
+    let data = tokio::task::spawn_blocking(move || serde_json::from_reader(reader)).await??;
+    Ok(data)
+}
+
+/// Remembers whether a read failed because the download broke off, so a
+/// parser error can be told apart from an object that cannot be parsed.
+pub struct TransferWatch {
+    reader: ObjectReader,
+    failed: Arc<AtomicBool>,
+}
+
+impl TransferWatch {
+    pub fn new(reader: ObjectReader) -> (Self, Arc<AtomicBool>) {
+        let failed = Arc::new(AtomicBool::new(false));
+        let watch = Self {
+            reader,
+            failed: failed.clone(),
+        };
+        (watch, failed)
+    }
+}
+
+impl Read for TransferWatch {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let result = self.reader.read(buf);
+        if matches!(&result, Err(err) if err.kind() == TRANSFER_ERROR) {
+            self.failed.store(true, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectInfo {
+    pub key: String,
+    /// Changes whenever the object is rewritten.
+    pub etag: Option<String>,
+    pub size: u64,
+}
 
 #[async_trait]
 pub trait BucketOps: Send + Sync {
     async fn get_object(&self, path: &str) -> Result<GraphData>;
+
+    /// Opens the object as a blocking reader so it can be parsed
+    /// incrementally on a blocking thread instead of being buffered.
+    async fn get_reader(&self, path: &str) -> Result<ObjectReader> {
+        Err(eyre!("Streaming reads are not supported for {}", path))
+    }
+
+    /// Lists every object whose key starts with `prefix`.
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        Err(eyre!("Listing is not supported for {}", prefix))
+    }
 }
 
-struct S3Bucket {
+pub struct S3Bucket {
     bucket: Bucket,
+    client: reqwest::Client,
 }
 
 impl S3Bucket {
-    fn new(bucket: Bucket) -> Self {
-        Self { bucket }
+    fn new(bucket: Bucket) -> Result<Self> {
+        let client = client_builder().build()?;
+        Ok(Self { bucket, client })
     }
 }
 
@@ -25,18 +123,159 @@ unsafe impl Sync for S3Bucket {}
 #[async_trait]
 impl BucketOps for S3Bucket {
     async fn get_object(&self, key: &str) -> Result<GraphData> {
-        let response = self.bucket.get_object(key).await?;
-        let graph_structure: GraphData = serde_json::from_slice(&response.bytes())?;
-        Ok(graph_structure)
+        read_json(self.get_reader(key).await?).await
+    }
+
+    /// Fetches a presigned URL directly, since the bucket client only reports
+    /// the status after it has written the whole body.
+    async fn get_reader(&self, key: &str) -> Result<ObjectReader> {
+        let url = self.bucket.presign_get(key, PRESIGN_EXPIRY_SECS, None)?;
+        let response = self.client.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(eyre!(
+                "Failed to get object {} from bucket {}: status {}",
+                key,
+                self.bucket.name,
+                response.status().as_u16()
+            ));
+        }
+        Ok(response_reader(response, key))
+    }
+
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        let pages = self.bucket.list(prefix.to_owned(), None).await?;
+        Ok(pages
+            .into_iter()
+            .flat_map(|page| page.contents)
+            // Zero-byte keys ending in a slash are folder markers.
+            .filter(|object| !object.key.ends_with('/'))
+            .map(|object| ObjectInfo {
+                key: object.key,
+                etag: object.e_tag,
+                size: object.size,
+            })
+            .collect())
+    }
+}
+
+pub struct LocalFileBucket;
+
+#[async_trait]
+impl BucketOps for LocalFileBucket {
+    async fn get_object(&self, path: &str) -> Result<GraphData> {
+        read_json(self.get_reader(path).await?).await
+    }
+
+    async fn get_reader(&self, path: &str) -> Result<ObjectReader> {
+        let file = File::open(path).map_err(|err| eyre!("Failed to open {}: {}", path, err))?;
+        let reader = BufReader::with_capacity(STREAM_BUFFER_SIZE, file);
+        Ok(decompress(Box::new(reader), path))
+    }
+
+    /// Walks the directory holding `prefix`. Keys are full paths and the
+    /// etag combines modification time and size.
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        let prefix = prefix.to_owned();
+        tokio::task::spawn_blocking(move || {
+            let root = if prefix.ends_with('/') {
+                PathBuf::from(&prefix)
+            } else {
+                PathBuf::from(&prefix)
+                    .parent()
+                    .map(PathBuf::from)
+                    .unwrap_or_default()
+            };
+            let mut objects = Vec::new();
+            let mut dirs = vec![root];
+            while let Some(dir) = dirs.pop() {
+                let entries = match std::fs::read_dir(&dir) {
+                    Ok(entries) => entries,
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                    Err(err) => return Err(eyre!("Failed to list {}: {}", dir.display(), err)),
+                };
+                for entry in entries {
+                    let entry = entry?;
+                    let metadata = entry.metadata()?;
+                    let path = entry.path();
+                    if metadata.is_dir() {
+                        dirs.push(path);
+                        continue;
+                    }
+                    let key = path.to_string_lossy().into_owned();
+                    if !key.starts_with(&prefix) {
+                        continue;
+                    }
+                    let modified = metadata
+                        .modified()?
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default();
+                    objects.push(ObjectInfo {
+                        key,
+                        etag: Some(format!("{}-{}", modified.as_nanos(), metadata.len())),
+                        size: metadata.len(),
+                    });
+                }
+            }
+            objects.sort_by(|a, b| a.key.cmp(&b.key));
+            Ok(objects)
+        })
+        .await?
+    }
+}
+
+pub struct HttpBucket {
+    client: reqwest::Client,
+}
+
+impl HttpBucket {
+    /// Redirects are only followed to `hosts`, the hosts sources may be
+    /// fetched from in the first place.
+    pub fn new(hosts: Vec<String>) -> Result<Self> {
+        let policy = reqwest::redirect::Policy::custom(move |attempt| {
+            let allowed = attempt
+                .url()
+                .host_str()
+                .is_some_and(|host| hosts.iter().any(|h| h.eq_ignore_ascii_case(host)));
+            if allowed {
+                attempt.follow()
+            } else {
+                let error = format!("Redirect to {} is not allowed", attempt.url());
+                attempt.error(error)
+            }
+        });
+        let client = client_builder().redirect(policy).build()?;
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl BucketOps for HttpBucket {
+    async fn get_object(&self, url: &str) -> Result<GraphData> {
+        read_json(self.get_reader(url).await?).await
+    }
+
+    async fn get_reader(&self, url: &str) -> Result<ObjectReader> {
+        let response = self.client.get(url).send().await?.error_for_status()?;
+        Ok(response_reader(response, url))
     }
 }
 
 pub fn create_bucket_ops(region: &str, bucket_name: &str) -> Result<Box<dyn BucketOps>> {
-    let creds = Credentials::from_env()?;
     let region: Region = region.parse()?;
+    let bucket = create_s3_bucket(region, bucket_name, false)?;
+    Ok(Box::new(bucket))
+}
+
+pub fn create_s3_bucket(region: Region, bucket_name: &str, path_style: bool) -> Result<S3Bucket> {
+    let creds = Credentials::from_env()?;
     let mut bucket = Bucket::new(bucket_name, region, creds)?;
-    bucket.set_request_timeout(Some(Duration::new(290, 0)));
-    Ok(Box::new(S3Bucket::new(bucket)))
+    if path_style {
+        bucket = bucket.with_path_style();
+    }
+    // The bucket's own client only lists objects, one small page per
+    // request; object bodies are streamed by `S3Bucket::client`.
+    bucket.set_request_timeout(Some(READ_TIMEOUT));
+    S3Bucket::new(bucket)
 }
 
 pub async fn read_graph_from_s3(
@@ -47,16 +286,6 @@ pub async fn read_graph_from_s3(
     Ok(data)
 }
 
-pub async fn read_from_local_file(file_path: &str) -> Result<GraphData> {
-    let current_dir = std::env::current_dir()?;
-    let example_file_path = current_dir.join(format!("tests/data/{}", file_path));
-    let mut file = File::open(example_file_path)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
-    let data: GraphData = serde_json::from_slice(&buffer)?;
-    Ok(data)
-}
-
 #[cfg(test)]
 mod tests {
     use std::{fs::File, io::Read};
@@ -73,6 +302,7 @@ mod tests {
                 let data = GraphData {
                     nodes: vec![],
                     relations: vec![],
+                    parent: None,
                 };
                 Ok(data)
             }
@@ -107,4 +337,96 @@ mod tests {
         let data = result.unwrap();
         assert_eq!(data.nodes.len(), 2);
     }
+
+    #[tokio::test]
+    async fn test_read_graph_from_local_file_bucket() {
+        let current_dir = std::env::current_dir().unwrap();
+        let path = current_dir.join("tests/data/example.json");
+        let result = LocalFileBucket.get_object(path.to_str().unwrap()).await;
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().nodes.len(), 2);
+
+        let result = LocalFileBucket.get_object("/does/not/exist.json").await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_file_bucket_reader() {
+        let current_dir = std::env::current_dir().unwrap();
+        let path = current_dir.join("tests/data/example.json");
+        let reader = LocalFileBucket
+            .get_reader(path.to_str().unwrap())
+            .await
+            .unwrap();
+        let data: GraphData = serde_json::from_reader(reader).unwrap();
+        assert_eq!(data.nodes.len(), 2);
+
+        let result = LocalFileBucket.get_reader("/does/not/exist.json").await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_file_bucket_lists_objects() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("nested")).unwrap();
+        std::fs::write(dir.path().join("a.json"), "{}").unwrap();
+        std::fs::write(dir.path().join("nested/b.json"), "{}").unwrap();
+        std::fs::write(dir.path().join("other.json"), "{}").unwrap();
+
+        let root = format!("{}/", dir.path().display());
+        let objects = LocalFileBucket.list_objects(&root).await.unwrap();
+        let keys: Vec<&str> = objects.iter().map(|o| o.key.as_str()).collect();
+        let a = format!("{}a.json", root);
+        let b = format!("{}nested/b.json", root);
+        let other = format!("{}other.json", root);
+        assert_eq!(keys, vec![a.as_str(), b.as_str(), other.as_str()]);
+        assert!(objects.iter().all(|o| o.size == 2 && o.etag.is_some()));
+
+        let objects = LocalFileBucket.list_objects(&a).await.unwrap();
+        assert_eq!(objects.len(), 1);
+        let missing = format!("{}missing/", root);
+        assert!(LocalFileBucket
+            .list_objects(&missing)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_transfer_errors_reach_the_reader() {
+        let chunks: Vec<io::Result<&[u8]>> = vec![
+            Ok(b"{\"nodes\": ["),
+            Err(io::Error::new(TRANSFER_ERROR, "connection reset")),
+        ];
+        let stream = futures::stream::iter(chunks);
+        let reader = SyncIoBridge::new(StreamReader::new(stream));
+        let (reader, failed) = TransferWatch::new(decompress(Box::new(reader), "graph.json"));
+        let result =
+            tokio::task::spawn_blocking(move || serde_json::from_reader::<_, GraphData>(reader))
+                .await
+                .unwrap();
+        assert!(result.unwrap_err().is_io());
+        assert!(failed.load(Ordering::Relaxed));
+
+        let (mut reader, failed) = TransferWatch::new(Box::new(io::Cursor::new(b"{]".to_vec())));
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).unwrap();
+        assert!(!failed.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn test_local_file_bucket_decompresses() {
+        let current_dir = std::env::current_dir().unwrap();
+        let path = current_dir.join("tests/data/example.json.gz");
+        let path = path.to_str().unwrap();
+        let data = LocalFileBucket.get_object(path).await.unwrap();
+        assert_eq!(data.nodes.len(), 2);
+
+        let reader = LocalFileBucket.get_reader(path).await.unwrap();
+        let data: GraphData = tokio::task::spawn_blocking(move || serde_json::from_reader(reader))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.nodes.len(), 2);
+    }
 }
@@ -0,0 +1,89 @@
+use super::{
+    compression::strip_extension, data::GraphData, delimited::read_csv, graphml::read_graphml,
+    ndjson::read_ndjson,
+};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    Json,
+    Ndjson,
+    Csv,
+    Graphml,
+}
+
+impl InputFormat {
+    /// Picks the explicit format when given, otherwise guesses from the file
+    /// extension and falls back to nested `GraphData` JSON.
+    pub fn detect(file: &str, explicit: Option<InputFormat>) -> InputFormat {
+        if let Some(format) = explicit {
+            return format;
+        }
+        let file = strip_extension(file);
+        let name = file.rsplit('/').next().unwrap_or(file).to_lowercase();
+        let name = name.split(['?', '#']).next().unwrap_or_default();
+        match name.rsplit('.').next() {
+            Some("ndjson") | Some("jsonl") => InputFormat::Ndjson,
+            Some("csv") => InputFormat::Csv,
+            Some("graphml") => InputFormat::Graphml,
+            _ => InputFormat::Json,
+        }
+    }
+
+    pub fn read<R: Read>(&self, reader: R) -> Result<GraphData> {
+        match self {
+            InputFormat::Json => Ok(serde_json::from_reader(reader)?),
+            InputFormat::Ndjson => read_ndjson(reader),
+            InputFormat::Csv => read_csv(reader),
+            InputFormat::Graphml => read_graphml(reader),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InputFormat;
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            InputFormat::detect("s3://bucket/graph.json", None),
+            InputFormat::Json
+        );
+        assert_eq!(
+            InputFormat::detect("s3://bucket/graph.ndjson", None),
+            InputFormat::Ndjson
+        );
+        assert_eq!(
+            InputFormat::detect("https://example.com/graph.JSONL?token=1", None),
+            InputFormat::Ndjson
+        );
+        assert_eq!(
+            InputFormat::detect("file:///tmp/nodes.csv", None),
+            InputFormat::Csv
+        );
+        assert_eq!(
+            InputFormat::detect("s3://bucket/export.graphml", None),
+            InputFormat::Graphml
+        );
+        assert_eq!(
+            InputFormat::detect("s3://bucket/nodes.csv.gz", None),
+            InputFormat::Csv
+        );
+        assert_eq!(
+            InputFormat::detect("s3://bucket/graph.ndjson.zst", None),
+            InputFormat::Ndjson
+        );
+        assert_eq!(
+            InputFormat::detect("file:///tmp/graph", None),
+            InputFormat::Json
+        );
+        assert_eq!(
+            InputFormat::detect("file:///tmp/graph.json", Some(InputFormat::Ndjson)),
+            InputFormat::Ndjson
+        );
+    }
+}
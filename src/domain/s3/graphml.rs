@@ -0,0 +1,572 @@
+use super::{
+    data::{GraphData, RawRelation, RawTag},
+    delimited::split_path,
+    ndjson::{build_tree, PathNode},
+};
+use crate::{db::model::NodeModel, domain::path};
+use eyre::{eyre, Result};
+use quick_xml::{
+    events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event},
+    Reader, Writer,
+};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io::{BufReader, Read},
+};
+use uuid::Uuid;
+
+const GRAPHML_NAMESPACE: &str = "http://graphml.graphdrawing.org/xmlns";
+const PATH_KEY: &str = "path";
+const NAME_KEY: &str = "name";
+/// Where Gephi and yEd keep node names.
+const LABEL_KEY: &str = "label";
+const TYPE_KEY: &str = "type";
+const UUID_KEY: &str = "uuid";
+const TOTAL_CHILDREN_KEY: &str = "total_children";
+/// Graph data holding the ancestor chain of an exported subtree; node paths
+/// are relative to it, as in a continuation file.
+const PARENT_KEY: &str = "parent";
+const DEFAULT_NODE_TYPE: &str = "node";
+const DEFAULT_EDGE_TYPE: &str = "related";
+/// Parent/child edges are implied by node paths, so they are exported for
+/// viewers but not read back as relations.
+const STRUCTURAL_EDGES: [&str; 2] = ["Child", "Parent"];
+
+#[derive(Debug, PartialEq)]
+enum ElementKind {
+    Node,
+    Edge,
+}
+
+#[derive(Debug)]
+struct Element {
+    kind: ElementKind,
+    id: String,
+    source: String,
+    target: String,
+    data: Vec<(String, String)>,
+}
+
+impl Element {
+    fn new(kind: ElementKind, start: &BytesStart) -> Result<Self> {
+        Ok(Self {
+            kind,
+            id: attribute(start, "id")?.unwrap_or_default(),
+            source: attribute(start, "source")?.unwrap_or_default(),
+            target: attribute(start, "target")?.unwrap_or_default(),
+            data: vec![],
+        })
+    }
+
+    fn take(&mut self, key: &str) -> Option<String> {
+        let position = self.data.iter().position(|(k, _)| k == key)?;
+        Some(self.data.remove(position).1)
+    }
+
+    fn into_tags(self, ignored: &[&str]) -> Option<Vec<RawTag>> {
+        let tags: Vec<RawTag> = self
+            .data
+            .into_iter()
+            .filter(|(key, _)| !ignored.contains(&key.as_str()))
+            .map(|(type_field, value)| RawTag { type_field, value })
+            .collect();
+        if tags.is_empty() {
+            None
+        } else {
+            Some(tags)
+        }
+    }
+}
+
+fn attribute(start: &BytesStart, name: &str) -> Result<Option<String>> {
+    match start.try_get_attribute(name)? {
+        Some(attr) => Ok(Some(attr.unescape_value()?.into_owned())),
+        None => Ok(None),
+    }
+}
+
+/// Reads a GraphML document. Node paths come from a `path` data key. Without
+/// one, a node is a top-level node named after its `name` or `label` data,
+/// or else placed at the path its id spells out. Every `<data>` value other
+/// than the reserved keys becomes a tag named after its `attr.name`. A `parent` data key on the
+/// graph makes the document a continuation below that path.
+pub fn read_graphml<R: Read>(reader: R) -> Result<GraphData> {
+    let mut reader = Reader::from_reader(BufReader::new(reader));
+    reader.trim_text(true);
+
+    let mut keys: HashMap<String, String> = HashMap::new();
+    let mut stack: Vec<Element> = vec![];
+    let mut finished: Vec<Element> = vec![];
+    let mut graph_data: Vec<(String, String)> = vec![];
+    let mut data_key: Option<String> = None;
+    let mut text = String::new();
+    let mut buf = Vec::new();
+
+    loop {
+        let event = reader.read_event_into(&mut buf).map_err(|err| {
+            eyre!(
+                "Invalid GraphML at position {}: {}",
+                reader.buffer_position(),
+                err
+            )
+        })?;
+        match event {
+            Event::Start(ref start) | Event::Empty(ref start) => {
+                let empty = matches!(event, Event::Empty(_));
+                match start.local_name().as_ref() {
+                    b"key" => {
+                        let id = attribute(start, "id")?
+                            .ok_or_else(|| eyre!("GraphML <key> without an id"))?;
+                        let name = attribute(start, "attr.name")?.unwrap_or_else(|| id.clone());
+                        keys.insert(id, name);
+                    }
+                    b"node" | b"edge" => {
+                        let kind = if start.local_name().as_ref() == b"node" {
+                            ElementKind::Node
+                        } else {
+                            ElementKind::Edge
+                        };
+                        let element = Element::new(kind, start)?;
+                        if empty {
+                            finished.push(element);
+                        } else {
+                            stack.push(element);
+                        }
+                    }
+                    b"data" => {
+                        let key = attribute(start, "key")?
+                            .ok_or_else(|| eyre!("GraphML <data> without a key"))?;
+                        let name = keys.get(&key).cloned().unwrap_or(key);
+                        if empty {
+                            match stack.last_mut() {
+                                Some(element) => element.data.push((name, String::new())),
+                                None => graph_data.push((name, String::new())),
+                            }
+                        } else {
+                            data_key = Some(name);
+                            text.clear();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(value) if data_key.is_some() => text.push_str(&value.unescape()?),
+            Event::CData(value) if data_key.is_some() => {
+                text.push_str(std::str::from_utf8(&value)?)
+            }
+            Event::End(end) => match end.local_name().as_ref() {
+                b"data" => {
+                    if let Some(key) = data_key.take() {
+                        let value = std::mem::take(&mut text);
+                        match stack.last_mut() {
+                            Some(element) => element.data.push((key, value)),
+                            None => graph_data.push((key, value)),
+                        }
+                    }
+                }
+                b"node" | b"edge" => {
+                    if let Some(element) = stack.pop() {
+                        finished.push(element);
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    let parent = match graph_data.into_iter().find(|(key, _)| key == PARENT_KEY) {
+        Some((_, value)) if !value.is_empty() => Some(path::parse(&value)?),
+        _ => None,
+    };
+    let mut paths: HashMap<String, Vec<String>> = HashMap::new();
+    let mut names: HashSet<String> = HashSet::new();
+    let mut nodes = vec![];
+    let mut edges = vec![];
+    for mut element in finished {
+        if element.kind == ElementKind::Edge {
+            edges.push(element);
+            continue;
+        }
+        let name = element.take(NAME_KEY).or_else(|| element.take(LABEL_KEY));
+        let path = match (element.take(PATH_KEY), name) {
+            (Some(path), _) => split_path(&path)?,
+            // Names need not be unique; repeated ones fall back to the id.
+            (None, Some(name)) if !name.is_empty() && names.insert(name.clone()) => vec![name],
+            (None, Some(_)) => vec![element.id.clone()],
+            (None, None) => split_path(&element.id)?,
+        };
+        let type_field = element
+            .take(TYPE_KEY)
+            .unwrap_or_else(|| DEFAULT_NODE_TYPE.to_owned());
+        let total_children = match element.take(TOTAL_CHILDREN_KEY) {
+            Some(value) if !value.is_empty() => Some(value.parse()?),
+            _ => None,
+        };
+        paths.insert(element.id.clone(), path.clone());
+        nodes.push(PathNode {
+            path,
+            type_field,
+            total_children,
+            tags: element.into_tags(&[UUID_KEY]),
+        });
+    }
+
+    let mut relations = vec![];
+    for mut edge in edges {
+        let type_field = edge
+            .take(TYPE_KEY)
+            .unwrap_or_else(|| DEFAULT_EDGE_TYPE.to_owned());
+        if STRUCTURAL_EDGES.contains(&type_field.as_str()) {
+            continue;
+        }
+        // Relation endpoints are absolute, unlike the node paths below a
+        // parent.
+        let endpoint = |id: &str| {
+            let relative = paths
+                .get(id)
+                .ok_or_else(|| eyre!("Edge references unknown node '{}'", id))?;
+            let mut absolute = parent.clone().unwrap_or_default();
+            absolute.extend(relative.iter().cloned());
+            Ok::<_, eyre::Report>(absolute)
+        };
+        relations.push(RawRelation {
+            type_field,
+            source: endpoint(&edge.source)?,
+            target: endpoint(&edge.target)?,
+            tags: edge.into_tags(&[]),
+        });
+    }
+
+    Ok(GraphData {
+        nodes: build_tree(nodes)?,
+        relations,
+        parent,
+    })
+}
+
+fn is_node_row(row: &NodeModel) -> bool {
+    row.direction.as_deref().unwrap_or_default().is_empty()
+}
+
+/// The ancestors every node of `paths` shares above its own level, which is
+/// what a subtree export is nested under.
+fn common_parent(paths: &[Vec<String>]) -> Vec<String> {
+    let mut parent: Option<&[String]> = None;
+    for path in paths {
+        let own = &path[..path.len().saturating_sub(1)];
+        let shared = match parent {
+            Some(parent) => parent.iter().zip(own).take_while(|(a, b)| a == b).count(),
+            None => own.len(),
+        };
+        parent = Some(&own[..shared]);
+    }
+    parent.map(<[String]>::to_vec).unwrap_or_default()
+}
+
+/// Serializes stored rows back to GraphML. Node ids are the stored UUIDs and
+/// the `path` key carries the path they were derived from, relative to the
+/// ancestors of the exported subtree in the graph's `parent` key, so
+/// importing the document under the same ingestion id yields the same UUIDs.
+pub fn write_graphml(rows: &[NodeModel]) -> Result<String> {
+    let nodes: Vec<&NodeModel> = rows.iter().filter(|row| is_node_row(row)).collect();
+    let paths = nodes
+        .iter()
+        .map(|node| path::parse(&node.path))
+        .collect::<Result<Vec<_>>>()?;
+    let parent = common_parent(&paths);
+    let ids: HashSet<Uuid> = nodes.iter().map(|node| node.uuid).collect();
+    let edges: Vec<&NodeModel> = rows
+        .iter()
+        .filter(|row| row.direction.as_deref() == Some("Out"))
+        .filter(|row| {
+            row.relates_to
+                .as_deref()
+                .and_then(|id| Uuid::parse_str(id).ok())
+                .is_some_and(|id| ids.contains(&id))
+        })
+        .collect();
+    let tag_names: BTreeSet<&str> = nodes
+        .iter()
+        .flat_map(|node| node.tags.iter().flatten())
+        .map(|(name, _)| name.as_str())
+        .filter(|name| ![PATH_KEY, NAME_KEY, TYPE_KEY, UUID_KEY, TOTAL_CHILDREN_KEY].contains(name))
+        .collect();
+    let tag_keys: HashMap<&str, String> = tag_names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| (*name, format!("n_tag{}", index)))
+        .collect();
+    let edge_tag_names: BTreeSet<&str> = edges
+        .iter()
+        .flat_map(|edge| edge.tags.iter().flatten())
+        .map(|(name, _)| name.as_str())
+        .filter(|name| *name != TYPE_KEY)
+        .collect();
+    let edge_tag_keys: HashMap<&str, String> = edge_tag_names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| (*name, format!("e_tag{}", index)))
+        .collect();
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    writer.write_event(Event::Start(
+        BytesStart::new("graphml").with_attributes([("xmlns", GRAPHML_NAMESPACE)]),
+    ))?;
+    write_key(&mut writer, "g_parent", "graph", PARENT_KEY)?;
+    for name in [PATH_KEY, NAME_KEY, TYPE_KEY, UUID_KEY, TOTAL_CHILDREN_KEY] {
+        write_key(&mut writer, &format!("n_{}", name), "node", name)?;
+    }
+    for name in &tag_names {
+        write_key(&mut writer, &tag_keys[name], "node", name)?;
+    }
+    write_key(&mut writer, "e_type", "edge", TYPE_KEY)?;
+    for name in &edge_tag_names {
+        write_key(&mut writer, &edge_tag_keys[name], "edge", name)?;
+    }
+    writer.write_event(Event::Start(
+        BytesStart::new("graph").with_attributes([("id", "G"), ("edgedefault", "directed")]),
+    ))?;
+    if !parent.is_empty() {
+        write_data(&mut writer, "g_parent", &path::from_segments(&parent))?;
+    }
+
+    for (node, node_path) in nodes.into_iter().zip(&paths) {
+        let id = node.uuid.to_string();
+        writer.write_event(Event::Start(
+            BytesStart::new("node").with_attributes([("id", id.as_str())]),
+        ))?;
+        let relative = path::from_segments(&node_path[parent.len()..]);
+        write_data(&mut writer, "n_path", &relative)?;
+        write_data(&mut writer, "n_name", &node.name)?;
+        write_data(&mut writer, "n_type", &node.node_type)?;
+        write_data(&mut writer, "n_uuid", &id)?;
+        if let Some(total_children) = node.total_children {
+            write_data(&mut writer, "n_total_children", &total_children.to_string())?;
+        }
+        for (name, value) in node.tags.iter().flatten() {
+            if let Some(key) = tag_keys.get(name.as_str()) {
+                write_data(&mut writer, key, value)?;
+            }
+        }
+        writer.write_event(Event::End(BytesEnd::new("node")))?;
+    }
+
+    for edge in edges {
+        let source = edge.uuid.to_string();
+        let target = edge.relates_to.as_deref().unwrap_or_default();
+        writer.write_event(Event::Start(
+            BytesStart::new("edge")
+                .with_attributes([("source", source.as_str()), ("target", target)]),
+        ))?;
+        write_data(
+            &mut writer,
+            "e_type",
+            edge.relation.as_deref().unwrap_or_default(),
+        )?;
+        for (name, value) in edge.tags.iter().flatten() {
+            if let Some(key) = edge_tag_keys.get(name.as_str()) {
+                write_data(&mut writer, key, value)?;
+            }
+        }
+        writer.write_event(Event::End(BytesEnd::new("edge")))?;
+    }
+
+    writer.write_event(Event::End(BytesEnd::new("graph")))?;
+    writer.write_event(Event::End(BytesEnd::new("graphml")))?;
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+fn write_key(writer: &mut Writer<Vec<u8>>, id: &str, target: &str, name: &str) -> Result<()> {
+    writer.write_event(Event::Empty(BytesStart::new("key").with_attributes([
+        ("id", id),
+        ("for", target),
+        ("attr.name", name),
+        ("attr.type", "string"),
+    ])))?;
+    Ok(())
+}
+
+fn write_data(writer: &mut Writer<Vec<u8>>, key: &str, value: &str) -> Result<()> {
+    writer.write_event(Event::Start(
+        BytesStart::new("data").with_attributes([("key", key)]),
+    ))?;
+    writer.write_event(Event::Text(BytesText::new(value)))?;
+    writer.write_event(Event::End(BytesEnd::new("data")))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::model::{path_to_uuid, process_nodes};
+    use crate::domain::relation::process_relations;
+    use crate::domain::s3::data::{RawNode, RawRelation, RawTag};
+
+    #[test]
+    fn test_read_graphml() {
+        let input = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="d0" for="node" attr.name="type" attr.type="string"/>
+  <key id="d1" for="node" attr.name="owner" attr.type="string"/>
+  <key id="d2" for="edge" attr.name="type" attr.type="string"/>
+  <graph id="G" edgedefault="directed">
+    <node id="root"><data key="d0">root_type</data></node>
+    <node id="root/a"><data key="d0">leaf</data><data key="d1">alice &amp; bob</data></node>
+    <node id="root/b"/>
+    <edge source="root/a" target="root/b"><data key="d2">depends_on</data></edge>
+    <edge source="root" target="root/a"><data key="d2">Child</data></edge>
+  </graph>
+</graphml>"#;
+        let data = read_graphml(input.as_bytes()).unwrap();
+        assert_eq!(data.nodes.len(), 1);
+        assert_eq!(data.node_count(), 3);
+        let a = &data.nodes[0].children[0];
+        assert_eq!(a.name, "a");
+        assert_eq!(a.type_field, "leaf");
+        let tags = a.tags.as_ref().unwrap();
+        assert_eq!(tags[0].type_field, "owner");
+        assert_eq!(tags[0].value, "alice & bob");
+        assert_eq!(data.nodes[0].children[1].type_field, DEFAULT_NODE_TYPE);
+        assert_eq!(data.relations.len(), 1);
+        assert_eq!(data.relations[0].type_field, "depends_on");
+        assert_eq!(data.relations[0].target, vec!["root", "b"]);
+    }
+
+    #[test]
+    fn test_read_graphml_names_from_data() {
+        let input = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="label" for="node" attr.name="label" attr.type="string"/>
+  <key id="weight" for="node" attr.name="weight" attr.type="double"/>
+  <key id="kind" for="edge" attr.name="kind" attr.type="string"/>
+  <graph id="G" edgedefault="undirected">
+    <node id="n0"><data key="label">Alice</data><data key="weight">1.0</data></node>
+    <node id="n1"><data key="label">Bob</data></node>
+    <node id="n2"><data key="label">Alice</data></node>
+    <node id="n3"/>
+    <edge id="e0" source="n0" target="n1"><data key="kind">knows</data></edge>
+  </graph>
+</graphml>"#;
+        let data = read_graphml(input.as_bytes()).unwrap();
+        let names: Vec<&str> = data.nodes.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, vec!["Alice", "Bob", "n2", "n3"]);
+        let tags = data.nodes[0].tags.as_ref().unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].type_field, "weight");
+        assert_eq!(data.relations[0].source, vec!["Alice"]);
+        assert_eq!(data.relations[0].target, vec!["Bob"]);
+        assert_eq!(data.relations[0].tags.as_ref().unwrap()[0].value, "knows");
+    }
+
+    #[test]
+    fn test_read_graphml_invalid() {
+        let unknown =
+            r#"<graphml><graph><node id="a"/><edge source="a" target="b"/></graph></graphml>"#;
+        assert_eq!(
+            read_graphml(unknown.as_bytes()).unwrap_err().to_string(),
+            "Edge references unknown node 'b'"
+        );
+        assert!(read_graphml("<graphml><graph><node id=\"a\"></graph>".as_bytes()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_graphml_round_trip_preserves_uuids() {
+        let file = std::fs::File::open("tests/data/example.json").unwrap();
+        let data: GraphData = serde_json::from_reader(file).unwrap();
+        let relations = process_relations("test", data.relations.clone());
+        let rows = process_nodes("test", &[], data.nodes, relations)
+            .await
+            .unwrap();
+
+        let document = write_graphml(&rows).unwrap();
+        let imported = read_graphml(document.as_bytes()).unwrap();
+        let relations = process_relations("test", imported.relations);
+        let round_trip = process_nodes("test", &[], imported.nodes, relations)
+            .await
+            .unwrap();
+
+        let uuids = |rows: &[NodeModel]| -> BTreeSet<Uuid> {
+            rows.iter()
+                .filter(|row| row.direction.is_none())
+                .map(|row| row.uuid)
+                .collect()
+        };
+        assert_eq!(uuids(&rows), uuids(&round_trip));
+        let root = round_trip.iter().find(|row| row.path == "/root").unwrap();
+        assert_eq!(root.uuid, path_to_uuid("test", "/root"));
+    }
+
+    #[tokio::test]
+    async fn test_graphml_subtree_round_trip() {
+        let node = |name: &str, children: Vec<RawNode>| RawNode {
+            name: name.to_string(),
+            type_field: "t".to_string(),
+            children,
+            ..RawNode::default()
+        };
+        let segments = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+        let relations = process_relations(
+            "test",
+            vec![RawRelation {
+                type_field: "uses".to_string(),
+                source: segments(&["root", "a", "b"]),
+                target: segments(&["root", "a", "c/d"]),
+                tags: Some(vec![RawTag {
+                    type_field: "weight".to_string(),
+                    value: "3".to_string(),
+                }]),
+            }],
+        );
+        let tree = vec![node(
+            "root",
+            vec![node("a", vec![node("b", vec![]), node("c/d", vec![])])],
+        )];
+        let rows = process_nodes("test", &[], tree, relations).await.unwrap();
+        let a = path_to_uuid("test", "/root/a");
+        let subtree: HashSet<Uuid> = rows
+            .iter()
+            .filter(|row| row.path == "/root/a" || row.path.starts_with("/root/a/"))
+            .map(|row| row.uuid)
+            .collect();
+        let rows: Vec<NodeModel> = rows
+            .into_iter()
+            .filter(|row| subtree.contains(&row.uuid))
+            .collect();
+
+        let document = write_graphml(&rows).unwrap();
+        let imported = read_graphml(document.as_bytes()).unwrap();
+        assert_eq!(imported.parent, Some(segments(&["root"])));
+        assert_eq!(imported.nodes[0].name, "a");
+        assert_eq!(
+            imported.relations[0].target,
+            segments(&["root", "a", "c/d"])
+        );
+        let tags = imported.relations[0].tags.as_ref().unwrap();
+        assert_eq!(
+            (tags[0].type_field.as_str(), tags[0].value.as_str()),
+            ("weight", "3")
+        );
+
+        let parent = imported.parent.unwrap();
+        let relations = process_relations("test", imported.relations);
+        let round_trip = process_nodes("test", &parent, imported.nodes, relations)
+            .await
+            .unwrap();
+        let nodes: BTreeSet<Uuid> = round_trip
+            .iter()
+            .filter(|row| row.direction.is_none())
+            .map(|row| row.uuid)
+            .collect();
+        assert_eq!(nodes, subtree.into_iter().collect());
+        assert!(nodes.contains(&a));
+        let edge = round_trip
+            .iter()
+            .find(|row| row.relation.as_deref() == Some("uses"))
+            .unwrap();
+        assert_eq!(edge.uuid, path_to_uuid("test", "/root/a/b"));
+    }
+}
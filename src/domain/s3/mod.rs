@@ -1,2 +1,9 @@
+pub mod compression;
 pub mod data;
+pub mod delimited;
 pub mod download;
+pub mod format;
+pub mod graphml;
+pub mod ndjson;
+pub mod source;
+pub mod stream;
@@ -0,0 +1,164 @@
+use super::data::{GraphData, RawNode, RawRelation, RawTag};
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read},
+};
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathNode {
+    pub path: Vec<String>,
+    #[serde(rename = "type")]
+    pub type_field: String,
+    pub tags: Option<Vec<RawTag>>,
+    pub total_children: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NdjsonRecord {
+    Node(PathNode),
+    Relation(RawRelation),
+}
+
+pub fn read_ndjson<R: Read>(reader: R) -> Result<GraphData> {
+    let mut nodes = Vec::new();
+    let mut relations = Vec::new();
+    for (index, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: NdjsonRecord = serde_json::from_str(&line)
+            .map_err(|err| eyre!("Invalid record on line {}: {}", index + 1, err))?;
+        match record {
+            NdjsonRecord::Node(node) => nodes.push(node),
+            NdjsonRecord::Relation(relation) => relations.push(relation),
+        }
+    }
+    Ok(GraphData {
+        nodes: build_tree(nodes)?,
+        relations,
+        parent: None,
+    })
+}
+
+/// Rebuilds the nested `RawNode` tree from nodes that carry their full path,
+/// so flat inputs can go through the same `process_nodes` pipeline. Parents
+/// are looked up by path, and a path given twice is an error rather than a
+/// second sibling of the same name.
+pub fn build_tree(mut nodes: Vec<PathNode>) -> Result<Vec<RawNode>> {
+    nodes.sort_by_key(|node| node.path.len());
+    let mut index: HashMap<Vec<String>, usize> = HashMap::with_capacity(nodes.len());
+    let mut arena: Vec<Option<RawNode>> = Vec::with_capacity(nodes.len());
+    let mut children: Vec<Vec<usize>> = Vec::with_capacity(nodes.len());
+    let mut roots = Vec::new();
+    for node in nodes {
+        let (name, parents) = node
+            .path
+            .split_last()
+            .ok_or_else(|| eyre!("Node record has an empty path"))?;
+        if index.contains_key(&node.path) {
+            return Err(eyre!("Duplicate node {}", node.path.join("/")));
+        }
+        let position = arena.len();
+        if parents.is_empty() {
+            roots.push(position);
+        } else {
+            match index.get(parents) {
+                Some(&parent) => children[parent].push(position),
+                None => {
+                    let depth = (1..=parents.len())
+                        .find(|depth| !index.contains_key(&parents[..*depth]))
+                        .unwrap_or(parents.len());
+                    return Err(eyre!(
+                        "Node {} references missing parent {}",
+                        node.path.join("/"),
+                        parents[..depth].join("/")
+                    ));
+                }
+            }
+        }
+        arena.push(Some(RawNode {
+            name: name.clone(),
+            type_field: node.type_field,
+            children: vec![],
+            tags: node.tags,
+            total_children: node.total_children,
+        }));
+        children.push(vec![]);
+        index.insert(node.path, position);
+    }
+
+    fn assemble(
+        position: usize,
+        arena: &mut [Option<RawNode>],
+        children: &[Vec<usize>],
+    ) -> RawNode {
+        let mut node = arena[position].take().unwrap_or_default();
+        node.children = children[position]
+            .iter()
+            .map(|&child| assemble(child, arena, children))
+            .collect();
+        node
+    }
+    Ok(roots
+        .into_iter()
+        .map(|root| assemble(root, &mut arena, &children))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_ndjson() {
+        let input = r#"
+{"path": ["root", "child"], "type": "leaf", "tags": [{"type": "t", "value": "v"}]}
+{"path": ["root"], "type": "root_type"}
+{"type": "uses", "source": ["root", "child"], "target": ["root"]}
+"#;
+        let data = read_ndjson(input.as_bytes()).unwrap();
+        assert_eq!(data.nodes.len(), 1);
+        assert_eq!(data.nodes[0].name, "root");
+        assert_eq!(data.nodes[0].children.len(), 1);
+        assert_eq!(data.nodes[0].children[0].name, "child");
+        assert_eq!(data.nodes[0].children[0].type_field, "leaf");
+        assert_eq!(data.relations.len(), 1);
+        assert_eq!(data.node_count(), 2);
+    }
+
+    #[test]
+    fn test_read_ndjson_missing_parent() {
+        let input = r#"{"path": ["root", "child"], "type": "leaf"}"#;
+        let result = read_ndjson(input.as_bytes());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Node root/child references missing parent root"
+        );
+    }
+
+    #[test]
+    fn test_read_ndjson_duplicate_node() {
+        let input = r#"
+{"path": ["root"], "type": "root_type"}
+{"path": ["root", "child"], "type": "leaf"}
+{"path": ["root", "child"], "type": "other"}
+"#;
+        let result = read_ndjson(input.as_bytes());
+        assert_eq!(result.unwrap_err().to_string(), "Duplicate node root/child");
+    }
+
+    #[test]
+    fn test_read_ndjson_invalid_line() {
+        let input = "{\"path\": [\"root\"], \"type\": \"t\"}\nnot json\n";
+        let result = read_ndjson(input.as_bytes());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("Invalid record on line 2"));
+    }
+}
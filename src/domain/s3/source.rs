@@ -0,0 +1,404 @@
+use super::{
+    data::GraphData,
+    download::{create_s3_bucket, BucketOps, HttpBucket, LocalFileBucket},
+    format::InputFormat,
+};
+use crate::{
+    config::config::{AppConfig, SourceConfig},
+    domain::upload::uploads_dir,
+};
+use eyre::{eyre, Result};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use s3::Region;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+use url::Url;
+
+/// Escaped in object keys put into `s3://` uris; `/` keeps separating levels.
+const KEY_ESCAPES: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceUri {
+    S3 { bucket: String, key: String },
+    File { path: String },
+    Http { url: String },
+}
+
+impl SourceUri {
+    pub fn parse(uri: &str) -> Result<SourceUri> {
+        let url = match Url::parse(uri) {
+            Ok(url) => url,
+            Err(url::ParseError::RelativeUrlWithoutBase) => {
+                return Ok(SourceUri::File {
+                    path: uri.to_owned(),
+                })
+            }
+            Err(err) => return Err(eyre!("Invalid source uri {}: {}", uri, err)),
+        };
+
+        match url.scheme() {
+            "s3" => {
+                let bucket = url
+                    .host_str()
+                    .filter(|host| !host.is_empty())
+                    .ok_or_else(|| eyre!("Missing bucket name in source uri {}", uri))?;
+                let key = percent_decode_str(url.path().trim_start_matches('/'))
+                    .decode_utf8()
+                    .map_err(|_| eyre!("Invalid object key in source uri {}", uri))?;
+                if key.is_empty() {
+                    return Err(eyre!("Missing object key in source uri {}", uri));
+                }
+                Ok(SourceUri::S3 {
+                    bucket: bucket.to_owned(),
+                    key: key.into_owned(),
+                })
+            }
+            "file" => {
+                let path = url
+                    .to_file_path()
+                    .map_err(|_| eyre!("Invalid file path in source uri {}", uri))?;
+                Ok(SourceUri::File {
+                    path: path.to_string_lossy().into_owned(),
+                })
+            }
+            "http" | "https" => Ok(SourceUri::Http {
+                url: uri.to_owned(),
+            }),
+            scheme => Err(eyre!("Unsupported source scheme '{}' in {}", scheme, uri)),
+        }
+    }
+
+    /// The uri of an object listed from this source, `key` being what
+    /// `BucketOps::list_objects` returned.
+    pub fn object_uri(&self, key: &str) -> Result<String> {
+        match self {
+            SourceUri::S3 { bucket, .. } => Ok(format!(
+                "s3://{}/{}",
+                bucket,
+                utf8_percent_encode(key, KEY_ESCAPES)
+            )),
+            SourceUri::File { .. } => Url::from_file_path(key)
+                .map(String::from)
+                .map_err(|_| eyre!("Invalid local path {}", key)),
+            SourceUri::Http { url } => Err(eyre!("Objects of {} cannot be listed", url)),
+        }
+    }
+}
+
+pub struct SourceRegistry {
+    region: Region,
+    path_style: bool,
+    file_roots: Vec<PathBuf>,
+    http_hosts: Vec<String>,
+    buckets: RwLock<HashMap<String, Arc<dyn BucketOps>>>,
+    local: Arc<dyn BucketOps>,
+    http: Arc<dyn BucketOps>,
+}
+
+impl std::fmt::Debug for SourceRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SourceRegistry")
+            .field("region", &self.region)
+            .field("path_style", &self.path_style)
+            .field("file_roots", &self.file_roots)
+            .field("http_hosts", &self.http_hosts)
+            .finish()
+    }
+}
+
+impl SourceRegistry {
+    pub fn new(app: &AppConfig, source: &SourceConfig) -> Result<SourceRegistry> {
+        let region = match &source.s3_endpoint {
+            Some(endpoint) => Region::Custom {
+                region: app.region.clone(),
+                endpoint: endpoint.clone(),
+            },
+            None => app.region.parse()?,
+        };
+        let path_style = source.s3_path_style.unwrap_or(source.s3_endpoint.is_some());
+        let file_roots = split_list(&source.source_file_roots)
+            .map(|root| resolve_local(Path::new(root)))
+            .chain([resolve_local(&uploads_dir())])
+            .collect::<Result<Vec<_>>>()?;
+        let http_hosts: Vec<String> = split_list(&source.source_http_hosts)
+            .map(str::to_lowercase)
+            .collect();
+        Ok(SourceRegistry {
+            region,
+            path_style,
+            file_roots,
+            http: Arc::new(HttpBucket::new(http_hosts.clone())?),
+            http_hosts,
+            buckets: RwLock::new(HashMap::new()),
+            local: Arc::new(LocalFileBucket),
+        })
+    }
+
+    pub fn register_bucket(&self, name: &str, bucket_ops: Arc<dyn BucketOps>) {
+        let mut buckets = self.buckets.write().expect("bucket registry lock poisoned");
+        buckets.insert(name.to_owned(), bucket_ops);
+    }
+
+    fn bucket(&self, name: &str) -> Result<Arc<dyn BucketOps>> {
+        if let Some(bucket) = self
+            .buckets
+            .read()
+            .expect("bucket registry lock poisoned")
+            .get(name)
+        {
+            return Ok(bucket.clone());
+        }
+        let bucket: Arc<dyn BucketOps> = Arc::new(create_s3_bucket(
+            self.region.clone(),
+            name,
+            self.path_style,
+        )?);
+        let mut buckets = self.buckets.write().expect("bucket registry lock poisoned");
+        Ok(buckets.entry(name.to_owned()).or_insert(bucket).clone())
+    }
+
+    /// Local paths and HTTP urls are only resolved below the configured roots
+    /// and hosts, as callers pick the sources. Local paths resolve to the
+    /// checked canonical path, so a symlink swapped in later is not followed.
+    pub fn resolve(&self, uri: &str) -> Result<(Arc<dyn BucketOps>, String)> {
+        match SourceUri::parse(uri)? {
+            SourceUri::S3 { bucket, key } => Ok((self.bucket(&bucket)?, key)),
+            SourceUri::File { path } => {
+                let resolved = resolve_local(Path::new(&path))?;
+                if !self
+                    .file_roots
+                    .iter()
+                    .any(|root| resolved.starts_with(root))
+                {
+                    return Err(eyre!("Source {} is outside the allowed directories", uri));
+                }
+                let mut resolved = resolved.to_string_lossy().into_owned();
+                // A trailing separator marks a directory prefix to list.
+                if path.ends_with('/') && !resolved.ends_with('/') {
+                    resolved.push('/');
+                }
+                Ok((self.local.clone(), resolved))
+            }
+            SourceUri::Http { url } => {
+                let host = Url::parse(&url)?.host_str().map(str::to_lowercase);
+                if !host.is_some_and(|host| self.http_hosts.contains(&host)) {
+                    return Err(eyre!("Source {} is not on an allowed host", uri));
+                }
+                Ok((self.http.clone(), url))
+            }
+        }
+    }
+
+    pub async fn read_graph(&self, uri: &str) -> Result<GraphData> {
+        let (bucket_ops, key) = self.resolve(uri)?;
+        bucket_ops.get_object(&key).await
+    }
+
+    pub async fn read_graph_as(&self, uri: &str, format: InputFormat) -> Result<GraphData> {
+        if format == InputFormat::Json {
+            return self.read_graph(uri).await;
+        }
+        let (bucket_ops, key) = self.resolve(uri)?;
+        let reader = bucket_ops.get_reader(&key).await?;
+        tokio::task::spawn_blocking(move || format.read(reader)).await?
+    }
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+/// Makes `path` absolute and resolves symlinks and `..` in the part of it
+/// that exists, so neither can lead out of an allowed directory.
+fn resolve_local(path: &Path) -> Result<PathBuf> {
+    let mut existing = std::env::current_dir()?.join(path);
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(resolved) = existing.canonicalize() {
+            return Ok(missing
+                .into_iter()
+                .rev()
+                .fold(resolved, |path, name| path.join(name)));
+        }
+        let name = existing
+            .file_name()
+            .map(PathBuf::from)
+            .ok_or_else(|| eyre!("Invalid local path {}", path.display()))?;
+        missing.push(name);
+        if !existing.pop() {
+            return Err(eyre!("Invalid local path {}", path.display()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    fn test_registry() -> SourceRegistry {
+        let app = AppConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            parallel_files: 1,
+            chunk_size: 1000,
+            region: "eu-west-1".to_string(),
+            rust_log: "info".to_string(),
+        };
+        let source = SourceConfig {
+            s3_endpoint: Some("http://localhost:9000".to_string()),
+            s3_path_style: None,
+            source_file_roots: "tests/data".to_string(),
+            source_http_hosts: "Example.com".to_string(),
+        };
+        SourceRegistry::new(&app, &source).unwrap()
+    }
+
+    #[test]
+    fn test_parse_source_uri() {
+        assert_eq!(
+            SourceUri::parse("s3://my-bucket/exports/graph.json").unwrap(),
+            SourceUri::S3 {
+                bucket: "my-bucket".to_string(),
+                key: "exports/graph.json".to_string(),
+            }
+        );
+        assert_eq!(
+            SourceUri::parse("file:///tmp/graph.json").unwrap(),
+            SourceUri::File {
+                path: "/tmp/graph.json".to_string(),
+            }
+        );
+        assert_eq!(
+            SourceUri::parse("https://example.com/graph.json").unwrap(),
+            SourceUri::Http {
+                url: "https://example.com/graph.json".to_string(),
+            }
+        );
+        assert_eq!(
+            SourceUri::parse("tests/data/example.json").unwrap(),
+            SourceUri::File {
+                path: "tests/data/example.json".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_s3_keys_are_percent_decoded() {
+        let uri = SourceUri::parse("s3://my-bucket/exports/my%20file%C3%A9.json").unwrap();
+        let key = "exports/my fileé.json".to_string();
+        assert_eq!(
+            uri,
+            SourceUri::S3 {
+                bucket: "my-bucket".to_string(),
+                key: key.clone(),
+            }
+        );
+        let object = uri.object_uri(&key).unwrap();
+        assert_eq!(object, "s3://my-bucket/exports/my%20file%C3%A9.json");
+        assert_eq!(SourceUri::parse(&object).unwrap(), uri);
+
+        let dir = SourceUri::File {
+            path: "/data/".to_string(),
+        };
+        let object = dir.object_uri("/data/my file#1.json").unwrap();
+        assert_eq!(
+            SourceUri::parse(&object).unwrap(),
+            SourceUri::File {
+                path: "/data/my file#1.json".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_source_uri_invalid() {
+        assert!(SourceUri::parse("s3://my-bucket").is_err());
+        assert!(SourceUri::parse("ftp://example.com/graph.json").is_err());
+    }
+
+    #[test]
+    fn test_registry_only_resolves_allowed_sources() {
+        let registry = test_registry();
+        let data = std::env::current_dir().unwrap().join("tests/data");
+        let allowed = [
+            format!("file://{}", data.join("example.json").display()),
+            format!("file://{}", data.join("missing/graph.json").display()),
+            "tests/data/example.json".to_string(),
+            "https://example.com/graph.json".to_string(),
+            format!("file://{}", uploads_dir().join("job/graph.json").display()),
+        ];
+        for uri in allowed {
+            assert!(registry.resolve(&uri).is_ok(), "{}", uri);
+        }
+        let (_, path) = registry.resolve("tests/data/example.json").unwrap();
+        let canonical = data.join("example.json").canonicalize().unwrap();
+        assert_eq!(path, canonical.to_string_lossy());
+        let (_, path) = registry.resolve("tests/data/").unwrap();
+        assert!(path.ends_with("tests/data/"));
+        let rejected = [
+            "file:///etc/passwd".to_string(),
+            format!("file://{}", data.join("../../Cargo.toml").display()),
+            "tests/data/../../Cargo.toml".to_string(),
+            "src/main.rs".to_string(),
+            "http://169.254.169.254/latest/meta-data".to_string(),
+            "https://example.com.evil.test/graph.json".to_string(),
+        ];
+        for uri in rejected {
+            assert!(registry.resolve(&uri).is_err(), "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_resolves_registered_bucket() {
+        struct MockBucketOps {}
+
+        #[async_trait]
+        impl BucketOps for MockBucketOps {
+            async fn get_object(&self, path: &str) -> Result<GraphData> {
+                assert_eq!(path, "exports/graph.json");
+                Ok(GraphData::default())
+            }
+        }
+
+        let registry = test_registry();
+        registry.register_bucket("my-bucket", Arc::new(MockBucketOps {}));
+        let result = registry
+            .read_graph("s3://my-bucket/exports/graph.json")
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_registry_reads_ndjson_file() {
+        let registry = test_registry();
+        let path = std::env::current_dir()
+            .unwrap()
+            .join("tests/data/example.ndjson");
+        let uri = Url::from_file_path(path).unwrap().to_string();
+        let format = InputFormat::detect(&uri, None);
+        let data = registry.read_graph_as(&uri, format).await.unwrap();
+        assert_eq!(data.node_count(), 4);
+        assert_eq!(data.relations.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_registry_reads_local_file() {
+        let registry = test_registry();
+        let path = std::env::current_dir()
+            .unwrap()
+            .join("tests/data/example.json");
+        let uri = Url::from_file_path(path).unwrap().to_string();
+        let data = registry.read_graph(&uri).await.unwrap();
+        assert_eq!(data.nodes.len(), 2);
+    }
+}
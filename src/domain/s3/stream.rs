@@ -0,0 +1,411 @@
+use super::data::{RawNode, RawRelation, RawTag};
+use crate::{
+    db::model::{
+        child_row, node_rows, path_from_name, path_from_segments, path_to_uuid, relation_rows,
+        NodeModel,
+    },
+    domain::relation::process_relations,
+};
+use eyre::Result;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use std::{fmt, io::Read};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamSummary {
+    pub nodes: usize,
+    pub relations: usize,
+}
+
+/// Walks a `GraphData` document without materialising it, handing every
+/// flattened `NodeModel` row to `emit` as soon as the node it belongs to has
+/// been read.
+pub fn stream_graph<R: Read>(
+    reader: R,
+    ingestion_id: &str,
+    emit: &mut dyn FnMut(NodeModel) -> Result<()>,
+) -> Result<StreamSummary> {
+    let mut walker = Walker {
+        ingestion_id,
+        emit,
+        summary: StreamSummary::default(),
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    GraphSeed {
+        walker: &mut walker,
+    }
+    .deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(walker.summary)
+}
+
+struct Walker<'a> {
+    ingestion_id: &'a str,
+    emit: &'a mut dyn FnMut(NodeModel) -> Result<()>,
+    summary: StreamSummary,
+}
+
+impl Walker<'_> {
+    fn emit_rows<E: de::Error>(&mut self, rows: Vec<NodeModel>) -> Result<(), E> {
+        for row in rows {
+            (self.emit)(row).map_err(E::custom)?;
+        }
+        Ok(())
+    }
+
+    fn emit_node<E: de::Error>(
+        &mut self,
+        path: &str,
+        raw_node: &RawNode,
+        parent: &Option<(Uuid, String)>,
+    ) -> Result<(), E> {
+        self.summary.nodes += 1;
+        let mut rows = node_rows(self.ingestion_id, path, raw_node, parent);
+        if let Some((parent_id, _)) = parent {
+            let id = path_to_uuid(self.ingestion_id, path);
+            rows.push(child_row(self.ingestion_id, *parent_id, id, &raw_node.name));
+        }
+        self.emit_rows(rows)
+    }
+
+    fn emit_subtree<E: de::Error>(
+        &mut self,
+        parent_path: &str,
+        raw_node: &RawNode,
+        parent: &Option<(Uuid, String)>,
+    ) -> Result<(), E> {
+        let path = path_from_name(parent_path, &raw_node.name);
+        self.emit_node(&path, raw_node, parent)?;
+        let parent = Some((
+            path_to_uuid(self.ingestion_id, &path),
+            raw_node.name.clone(),
+        ));
+        for child in &raw_node.children {
+            self.emit_subtree(&path, child, &parent)?;
+        }
+        Ok(())
+    }
+
+    fn emit_relation<E: de::Error>(&mut self, relation: RawRelation) -> Result<(), E> {
+        self.summary.relations += 1;
+        let relations = process_relations(self.ingestion_id, vec![relation]);
+        let rows = relation_rows(self.ingestion_id, &relations);
+        self.emit_rows(rows)
+    }
+}
+
+struct GraphSeed<'w, 'a> {
+    walker: &'w mut Walker<'a>,
+}
+
+impl<'de> DeserializeSeed<'de> for GraphSeed<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for GraphSeed<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a graph document with nodes and relations")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut seen_nodes = false;
+        let mut seen_relations = false;
+        let mut parent_path: Vec<String> = Vec::new();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "parent" => {
+                    // The nodes are flattened as they are read, so their
+                    // parent has to be known by then.
+                    if seen_nodes {
+                        return Err(de::Error::custom("parent must come before nodes"));
+                    }
+                    parent_path = map.next_value::<Option<Vec<String>>>()?.unwrap_or_default();
+                }
+                "nodes" => {
+                    seen_nodes = true;
+                    let path = path_from_segments(&parent_path);
+                    let parent = parent_path
+                        .last()
+                        .map(|name| (path_to_uuid(self.walker.ingestion_id, &path), name.clone()));
+                    map.next_value_seed(NodesSeed {
+                        walker: &mut *self.walker,
+                        path,
+                        parent,
+                    })?;
+                }
+                "relations" => {
+                    seen_relations = true;
+                    map.next_value_seed(RelationsSeed {
+                        walker: &mut *self.walker,
+                    })?;
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        if !seen_nodes {
+            return Err(de::Error::missing_field("nodes"));
+        }
+        if !seen_relations {
+            return Err(de::Error::missing_field("relations"));
+        }
+        Ok(())
+    }
+}
+
+struct NodesSeed<'w, 'a> {
+    walker: &'w mut Walker<'a>,
+    path: String,
+    parent: Option<(Uuid, String)>,
+}
+
+impl<'de> DeserializeSeed<'de> for NodesSeed<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for NodesSeed<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of nodes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while seq
+            .next_element_seed(NodeSeed {
+                walker: &mut *self.walker,
+                parent_path: &self.path,
+                parent: &self.parent,
+            })?
+            .is_some()
+        {}
+        Ok(())
+    }
+}
+
+struct NodeSeed<'w, 'a, 'p> {
+    walker: &'w mut Walker<'a>,
+    parent_path: &'p str,
+    parent: &'p Option<(Uuid, String)>,
+}
+
+impl<'de> DeserializeSeed<'de> for NodeSeed<'_, '_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for NodeSeed<'_, '_, '_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a node")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut name: Option<String> = None;
+        let mut type_field: Option<String> = None;
+        let mut tags: Option<Vec<RawTag>> = None;
+        let mut total_children: Option<i64> = None;
+        let mut seen_children = false;
+        // Children that arrive before the node's name cannot be given a path
+        // yet, so they are buffered and flattened once the node is complete.
+        let mut pending_children: Vec<RawNode> = Vec::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "name" => name = Some(map.next_value()?),
+                "type" => type_field = Some(map.next_value()?),
+                "tags" => tags = map.next_value()?,
+                "totalChildren" => total_children = map.next_value()?,
+                "children" => {
+                    seen_children = true;
+                    match &name {
+                        Some(name) => {
+                            let path = path_from_name(self.parent_path, name);
+                            let id = path_to_uuid(self.walker.ingestion_id, &path);
+                            map.next_value_seed(NodesSeed {
+                                walker: &mut *self.walker,
+                                path,
+                                parent: Some((id, name.clone())),
+                            })?;
+                        }
+                        None => pending_children = map.next_value()?,
+                    }
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        let raw_node = RawNode {
+            name: name.ok_or_else(|| de::Error::missing_field("name"))?,
+            type_field: type_field.ok_or_else(|| de::Error::missing_field("type"))?,
+            children: pending_children,
+            tags,
+            total_children,
+        };
+        if !seen_children {
+            return Err(de::Error::missing_field("children"));
+        }
+
+        let path = path_from_name(self.parent_path, &raw_node.name);
+        self.walker.emit_node(&path, &raw_node, self.parent)?;
+        let parent = Some((
+            path_to_uuid(self.walker.ingestion_id, &path),
+            raw_node.name.clone(),
+        ));
+        for child in &raw_node.children {
+            self.walker.emit_subtree(&path, child, &parent)?;
+        }
+        Ok(())
+    }
+}
+
+struct RelationsSeed<'w, 'a> {
+    walker: &'w mut Walker<'a>,
+}
+
+impl<'de> DeserializeSeed<'de> for RelationsSeed<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for RelationsSeed<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of relations")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(relation) = seq.next_element::<RawRelation>()? {
+            self.walker.emit_relation(relation)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::model::process_nodes, domain::s3::data::GraphData};
+    use std::{
+        collections::{HashMap, HashSet},
+        fs::File,
+    };
+
+    fn row_key(row: &NodeModel) -> (Uuid, Option<String>, Option<String>, Option<String>) {
+        (
+            row.uuid,
+            row.direction.clone(),
+            row.relation.clone(),
+            row.relates_to.clone(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_stream_graph_matches_process_nodes() {
+        let path = std::env::current_dir()
+            .unwrap()
+            .join("tests/data/example.json");
+        let mut streamed = vec![];
+        let summary = stream_graph(File::open(&path).unwrap(), "test", &mut |row| {
+            streamed.push(row);
+            Ok(())
+        })
+        .unwrap();
+
+        let data: GraphData = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        assert_eq!(summary.nodes, data.node_count());
+        assert_eq!(summary.relations, data.relations.len());
+
+        let relations = process_relations("test", data.relations.clone());
+        let mut expected = process_nodes("test", &[], data.nodes, HashMap::new())
+            .await
+            .unwrap();
+        expected.extend(relation_rows("test", &relations));
+
+        let streamed: HashSet<_> = streamed.iter().map(row_key).collect();
+        let expected: HashSet<_> = expected.iter().map(row_key).collect();
+        assert_eq!(streamed, expected);
+    }
+
+    #[test]
+    fn test_stream_graph_children_before_name() {
+        let json = r#"{
+            "relations": [],
+            "nodes": [
+                {"children": [{"name": "b", "type": "t", "children": []}], "name": "a", "type": "t"}
+            ]
+        }"#;
+        let mut rows = vec![];
+        let summary = stream_graph(json.as_bytes(), "test", &mut |row| {
+            rows.push(row);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(summary.nodes, 2);
+        assert!(rows.iter().any(|row| row.path == "/a/b"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_graph_continuation() {
+        let json = r#"{
+            "parent": ["root", "a"],
+            "nodes": [{"name": "b", "type": "t", "children": [], "totalChildren": 0}],
+            "relations": []
+        }"#;
+        let mut streamed = vec![];
+        stream_graph(json.as_bytes(), "test", &mut |row| {
+            streamed.push(row);
+            Ok(())
+        })
+        .unwrap();
+
+        let data: GraphData = serde_json::from_str(json).unwrap();
+        let parent = data.parent.unwrap();
+        let expected = process_nodes("test", &parent, data.nodes, HashMap::new())
+            .await
+            .unwrap();
+        let streamed: HashSet<_> = streamed.iter().map(row_key).collect();
+        let expected: HashSet<_> = expected.iter().map(row_key).collect();
+        assert_eq!(streamed, expected);
+
+        let late_parent = r#"{"nodes": [], "parent": ["root"], "relations": []}"#;
+        assert!(stream_graph(late_parent.as_bytes(), "test", &mut |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_stream_graph_errors() {
+        let missing_name = r#"{"nodes": [{"type": "t", "children": []}], "relations": []}"#;
+        let result = stream_graph(missing_name.as_bytes(), "test", &mut |_| Ok(()));
+        assert!(result.is_err());
+
+        let result = stream_graph(r#"{"nodes": []}"#.as_bytes(), "test", &mut |_| Ok(()));
+        assert!(result.is_err());
+
+        let valid = r#"{"nodes": [{"name": "a", "type": "t", "children": []}], "relations": []}"#;
+        let result = stream_graph(valid.as_bytes(), "test", &mut |_| {
+            Err(eyre::eyre!("sink closed"))
+        });
+        assert!(result.unwrap_err().to_string().contains("sink closed"));
+    }
+}
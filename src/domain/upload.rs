@@ -0,0 +1,254 @@
+use crate::{application::AppState, domain::job::JobState};
+use actix_multipart::{Multipart, MultipartError};
+use actix_web::web::Data;
+use eyre::{eyre, Result};
+use futures::StreamExt;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::{fs, io::AsyncWriteExt, task::JoinHandle};
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Upper bound on a plain form field; only files are streamed to disk.
+const MAX_FIELD_BYTES: usize = 64 * 1024;
+
+/// Where uploaded files are staged, one directory per job. Sources below it
+/// are always readable.
+pub fn uploads_dir() -> PathBuf {
+    std::env::temp_dir().join("rustfastingest-uploads")
+}
+
+/// The parts of an upload: every file part written to the staging directory,
+/// in the order they were sent, and the plain fields by name.
+#[derive(Debug, Default)]
+pub struct Upload {
+    pub files: Vec<PathBuf>,
+    pub fields: HashMap<String, String>,
+}
+
+/// The form added up to more than the configured limit.
+#[derive(Debug)]
+pub struct PayloadTooLarge(pub u64);
+
+impl std::fmt::Display for PayloadTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Upload exceeds the limit of {} bytes", self.0)
+    }
+}
+
+impl std::error::Error for PayloadTooLarge {}
+
+fn invalid(err: MultipartError) -> eyre::Report {
+    eyre!("Invalid multipart form: {}", err)
+}
+
+/// Streams a multipart form into `dir`, one file per file part, giving up
+/// once the parts add up to more than `max_bytes`.
+pub async fn stage(mut form: Multipart, dir: &Path, max_bytes: u64) -> Result<Upload> {
+    fs::create_dir_all(dir).await?;
+    let mut upload = Upload::default();
+    let mut total: u64 = 0;
+    let mut count = |len: usize| {
+        total += len as u64;
+        match total > max_bytes {
+            true => Err(PayloadTooLarge(max_bytes)),
+            false => Ok(()),
+        }
+    };
+    while let Some(field) = form.next().await {
+        let mut field = field.map_err(invalid)?;
+        let name = field.name().unwrap_or_default().to_owned();
+        let filename = field
+            .content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .map(str::to_owned);
+        match filename {
+            // Browsers send an empty file part when nothing was picked.
+            Some(filename) if filename.is_empty() => {}
+            Some(filename) => {
+                let name = format!("{}-{}", upload.files.len(), sanitize_filename(&filename));
+                let path = dir.join(name);
+                let mut file = fs::File::create(&path).await?;
+                upload.files.push(path);
+                while let Some(chunk) = field.next().await {
+                    let chunk = chunk.map_err(invalid)?;
+                    count(chunk.len())?;
+                    file.write_all(&chunk).await?;
+                }
+                file.flush().await?;
+            }
+            None => {
+                let mut value = Vec::new();
+                while let Some(chunk) = field.next().await {
+                    let chunk = chunk.map_err(invalid)?;
+                    count(chunk.len())?;
+                    value.extend_from_slice(&chunk);
+                    if value.len() > MAX_FIELD_BYTES {
+                        return Err(eyre!("Form field '{}' is too large", name));
+                    }
+                }
+                let value = String::from_utf8(value)
+                    .map_err(|_| eyre!("Form field '{}' is not UTF-8", name))?;
+                upload.fields.insert(name, value);
+            }
+        }
+    }
+    Ok(upload)
+}
+
+/// Keeps only the last path component of an uploaded file name and drops
+/// anything that is not safe in a local file name.
+pub fn sanitize_filename(filename: &str) -> String {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match name.trim_start_matches('.') {
+        "" => "upload".to_owned(),
+        name => name.to_owned(),
+    }
+}
+
+/// Removes the staging directories in `dir` untouched for `max_age` or
+/// longer, except those `in_use` claims, and returns how many went.
+pub async fn remove_expired<F>(dir: &Path, max_age: Duration, in_use: F) -> Result<usize>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err.into()),
+    };
+    let mut removed = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        let modified = entry.metadata().await?.modified()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if age >= max_age && !in_use(&name) {
+            fs::remove_dir_all(entry.path()).await?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Periodically removes uploads kept for failed jobs once they expired.
+/// Uploads of jobs that are still queued or running are left alone.
+pub fn spawn_cleanup(state: Data<AppState>, max_age: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(
+            max_age.clamp(Duration::from_secs(60), Duration::from_secs(3600)),
+        );
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let in_use = |name: &str| {
+                Uuid::parse_str(name)
+                    .ok()
+                    .and_then(|job_id| state.jobs.get(&job_id))
+                    .is_some_and(|job| matches!(job.state, JobState::Queued | JobState::Running))
+            };
+            match remove_expired(&uploads_dir(), max_age, in_use).await {
+                Ok(0) => {}
+                Ok(removed) => info!("Removed {} expired uploads", removed),
+                Err(err) => error!("Error removing expired uploads: {}", err),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        http::header::{self, HeaderMap, HeaderValue},
+        web::Bytes,
+    };
+
+    const BODY: &str = "--XyZ\r\n\
+        Content-Disposition: form-data; name=\"ingestion_id\"\r\n\r\n\
+        upload-1\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"graph.json\"\r\n\
+        Content-Type: application/json\r\n\r\n\
+        {\"nodes\": [], \"relations\": []}\r\n--XyZ--\r\n";
+
+    fn form(body: &'static str) -> Multipart {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("multipart/form-data; boundary=XyZ"),
+        );
+        let chunks: Vec<Result<Bytes, actix_web::error::PayloadError>> = body
+            .as_bytes()
+            .chunks(5)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+        Multipart::new(&headers, futures::stream::iter(chunks))
+    }
+
+    #[actix_rt::test]
+    async fn test_stage_upload() {
+        let dir = tempfile::tempdir().unwrap();
+        let upload = stage(form(BODY), dir.path(), 1024).await.unwrap();
+        assert_eq!(upload.fields["ingestion_id"], "upload-1");
+        assert_eq!(upload.files, vec![dir.path().join("0-graph.json")]);
+        let content = std::fs::read_to_string(&upload.files[0]).unwrap();
+        assert_eq!(content, r#"{"nodes": [], "relations": []}"#);
+    }
+
+    #[actix_rt::test]
+    async fn test_stage_upload_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = stage(form(BODY), dir.path(), 16).await.unwrap_err();
+        assert!(err.downcast_ref::<PayloadTooLarge>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_remove_expired() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["done", "running"] {
+            std::fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        let removed = remove_expired(dir.path(), Duration::from_secs(3600), |_| false)
+            .await
+            .unwrap();
+        assert_eq!(removed, 0);
+
+        let removed = remove_expired(dir.path(), Duration::ZERO, |name| name == "running")
+            .await
+            .unwrap();
+        assert_eq!(removed, 1);
+        assert!(dir.path().join("running").exists());
+        assert!(!dir.path().join("done").exists());
+        let missing = dir.path().join("missing");
+        assert_eq!(
+            remove_expired(&missing, Duration::ZERO, |_| false)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+        assert_eq!(
+            sanitize_filename("C:\\data\\my graph.json"),
+            "my_graph.json"
+        );
+        assert_eq!(sanitize_filename(".."), "upload");
+    }
+}
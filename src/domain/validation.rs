@@ -0,0 +1,272 @@
+use super::{
+    path,
+    s3::data::{GraphData, RawNode, RawRelation},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Only the first issues are kept in the report so a badly broken file
+/// cannot blow up the job status payload; `issues_found` has the total.
+pub const MAX_REPORTED_ISSUES: usize = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strictness {
+    #[default]
+    Warn,
+    Skip,
+    Reject,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    DanglingRelation,
+    DuplicateSibling,
+    EmptyName,
+    TotalChildrenMismatch,
+    SelfLoop,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub kind: IssueKind,
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub issues_found: usize,
+    pub records_skipped: usize,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    fn push(&mut self, kind: IssueKind, path: &[String], message: String) {
+        self.issues_found += 1;
+        if self.issues.len() < MAX_REPORTED_ISSUES {
+            self.issues.push(ValidationIssue {
+                kind,
+                path: path::from_segments(path),
+                message,
+            });
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.issues_found == 0
+    }
+}
+
+/// Checks a parsed file before it is flattened. With `Strictness::Skip`
+/// offending nodes (and their subtrees) and relations are removed from
+/// `data`; the other modes leave it untouched. Relation endpoints are only
+/// checked when the file has nodes, since edge-only files legitimately point
+/// at nodes ingested from other files.
+pub fn validate(data: &mut GraphData, strictness: Strictness) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let mut paths = HashSet::new();
+    let skip = strictness == Strictness::Skip;
+    let has_nodes = !data.nodes.is_empty();
+    let parent = data.parent.clone().unwrap_or_default();
+    validate_nodes(&mut data.nodes, &parent, skip, &mut paths, &mut report);
+
+    data.relations.retain(|relation| {
+        let valid = validate_relation(relation, has_nodes, &paths, &mut report);
+        if !valid && skip {
+            report.records_skipped += 1;
+        }
+        valid || !skip
+    });
+    report
+}
+
+fn validate_nodes(
+    nodes: &mut Vec<RawNode>,
+    parent: &[String],
+    skip: bool,
+    paths: &mut HashSet<Vec<String>>,
+    report: &mut ValidationReport,
+) {
+    let mut siblings = HashSet::new();
+    nodes.retain_mut(|node| {
+        let mut path = parent.to_vec();
+        path.push(node.name.clone());
+
+        let mut valid = true;
+        if node.name.trim().is_empty() {
+            report.push(
+                IssueKind::EmptyName,
+                &path,
+                "Node has an empty name".to_owned(),
+            );
+            valid = false;
+        }
+        if !siblings.insert(node.name.clone()) {
+            let message = format!("Duplicate sibling name '{}'", node.name);
+            report.push(IssueKind::DuplicateSibling, &path, message);
+            valid = false;
+        }
+        // Fewer children than announced is a truncated subtree, whose other
+        // children may follow in continuation files.
+        if let Some(total) = node.total_children {
+            if total < node.children.len() as i64 {
+                let message = format!(
+                    "total_children is {} but {} children were found",
+                    total,
+                    node.children.len()
+                );
+                report.push(IssueKind::TotalChildrenMismatch, &path, message);
+            }
+        }
+
+        if !valid && skip {
+            report.records_skipped += 1 + count_descendants(&node.children);
+            return false;
+        }
+        validate_nodes(&mut node.children, &path, skip, paths, report);
+        paths.insert(path);
+        true
+    });
+}
+
+fn count_descendants(nodes: &[RawNode]) -> usize {
+    nodes
+        .iter()
+        .map(|node| 1 + count_descendants(&node.children))
+        .sum()
+}
+
+fn validate_relation(
+    relation: &RawRelation,
+    has_nodes: bool,
+    paths: &HashSet<Vec<String>>,
+    report: &mut ValidationReport,
+) -> bool {
+    let mut valid = true;
+    if relation.source == relation.target {
+        let message = format!("Relation '{}' points at itself", relation.type_field);
+        report.push(IssueKind::SelfLoop, &relation.source, message);
+        valid = false;
+    }
+    if has_nodes {
+        for (endpoint, path) in [("source", &relation.source), ("target", &relation.target)] {
+            if !paths.contains(path) {
+                let message = format!(
+                    "Relation '{}' {} '{}' is not a node in this file",
+                    relation.type_field,
+                    endpoint,
+                    path::from_segments(path)
+                );
+                report.push(IssueKind::DanglingRelation, path, message);
+                valid = false;
+            }
+        }
+    }
+    valid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, children: Vec<RawNode>) -> RawNode {
+        RawNode {
+            name: name.to_string(),
+            type_field: "type".to_string(),
+            total_children: Some(children.len() as i64),
+            children,
+            tags: None,
+        }
+    }
+
+    fn relation(source: &[&str], target: &[&str]) -> RawRelation {
+        RawRelation {
+            type_field: "uses".to_string(),
+            source: source.iter().map(|s| s.to_string()).collect(),
+            target: target.iter().map(|s| s.to_string()).collect(),
+            tags: None,
+        }
+    }
+
+    fn sample() -> GraphData {
+        let mut mismatch = node("c", vec![node("c1", vec![])]);
+        mismatch.total_children = Some(0);
+        GraphData {
+            nodes: vec![node(
+                "root",
+                vec![
+                    node("a", vec![node("a1", vec![])]),
+                    node("a", vec![]),
+                    node("", vec![]),
+                    node("b/c", vec![]),
+                    mismatch,
+                ],
+            )],
+            relations: vec![
+                relation(&["root", "a"], &["root", "c"]),
+                relation(&["root", "a"], &["root", "missing"]),
+                relation(&["root", "c"], &["root", "c"]),
+                relation(&["root", "b/c"], &["root", "c"]),
+            ],
+            parent: None,
+        }
+    }
+
+    fn kinds(report: &ValidationReport) -> Vec<IssueKind> {
+        report.issues.iter().map(|issue| issue.kind).collect()
+    }
+
+    #[test]
+    fn test_validate_warn() {
+        let mut data = sample();
+        let report = validate(&mut data, Strictness::Warn);
+        assert_eq!(
+            kinds(&report),
+            vec![
+                IssueKind::DuplicateSibling,
+                IssueKind::EmptyName,
+                IssueKind::TotalChildrenMismatch,
+                IssueKind::DanglingRelation,
+                IssueKind::SelfLoop,
+            ]
+        );
+        assert_eq!(report.records_skipped, 0);
+        assert_eq!(report.issues[0].path, "/root/a");
+        assert_eq!(report.issues[3].path, "/root/missing");
+        assert_eq!(data, sample());
+    }
+
+    #[test]
+    fn test_validate_skip() {
+        let mut data = sample();
+        let report = validate(&mut data, Strictness::Skip);
+        assert_eq!(report.issues_found, 5);
+        assert_eq!(report.records_skipped, 4);
+        assert_eq!(data.node_count(), 6);
+        assert_eq!(data.relations.len(), 2);
+    }
+
+    #[test]
+    fn test_validate_edges_only_file() {
+        let mut data = GraphData {
+            nodes: vec![],
+            relations: vec![relation(&["root", "a"], &["root", "b"])],
+            parent: None,
+        };
+        assert!(validate(&mut data, Strictness::Reject).is_clean());
+    }
+
+    #[test]
+    fn test_validate_truncated_continuation() {
+        let mut truncated = node("a", vec![node("a1", vec![])]);
+        truncated.total_children = Some(2);
+        let mut data = GraphData {
+            nodes: vec![truncated],
+            relations: vec![relation(&["root", "a"], &["root", "a", "a1"])],
+            parent: Some(vec!["root".to_string()]),
+        };
+        assert!(validate(&mut data, Strictness::Reject).is_clean());
+    }
+}
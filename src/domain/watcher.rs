@@ -0,0 +1,246 @@
+use crate::application::AppState;
+use crate::config::config::WatcherConfig;
+use crate::domain::job::FileState;
+use crate::domain::pipeline::{spawn_job, IngestionRequest};
+use crate::domain::s3::{download::ObjectInfo, source::SourceUri};
+use actix_web::web::Data;
+use eyre::{eyre, Result};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Matches `text` against a glob: `*` and `?` stay within one path segment,
+/// `**` spans segments.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(pattern: &[u8], text: &[u8]) -> bool {
+        match pattern {
+            [] => text.is_empty(),
+            [b'*', b'*', rest @ ..] => {
+                let rest = rest.strip_prefix(b"/").unwrap_or(rest);
+                (0..=text.len()).any(|i| matches(rest, &text[i..]))
+            }
+            [b'*', rest @ ..] => {
+                let segment = text.iter().position(|&c| c == b'/').unwrap_or(text.len());
+                (0..=segment).any(|i| matches(rest, &text[i..]))
+            }
+            [b'?', rest @ ..] => {
+                matches!(text.first(), Some(&c) if c != b'/') && matches(rest, &text[1..])
+            }
+            [c, rest @ ..] => text.first() == Some(c) && matches(rest, &text[1..]),
+        }
+    }
+    matches(pattern.as_bytes(), text.as_bytes())
+}
+
+/// Fills the ingestion id template for a key relative to the watched prefix:
+/// `{key}` is the whole relative key, `{dir}` its directory, `{name}` the
+/// file name and `{stem}` the file name without extensions.
+pub fn render_template(template: &str, key: &str) -> String {
+    let (dir, name) = key.rsplit_once('/').unwrap_or(("", key));
+    let stem = name.split('.').next().unwrap_or(name);
+    template
+        .replace("{key}", key)
+        .replace("{dir}", dir)
+        .replace("{name}", name)
+        .replace("{stem}", stem)
+}
+
+/// Polls a bucket prefix and ingests the objects that appeared or changed
+/// since the last poll. When one object of an ingestion id is new, all of
+/// its objects go into one incremental job, so unchanged rows are skipped and
+/// none of them are deleted as vanished. Objects are recorded in
+/// `graph.watched_objects` once their file was ingested; failed ones are
+/// picked up again by a later poll.
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    source: String,
+    pattern: String,
+    template: String,
+    interval: Duration,
+    /// Ingestion ids with a job in flight, left alone until it finished.
+    running: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Watcher {
+    /// `None` when no source is configured.
+    pub fn from_config(config: &WatcherConfig) -> Result<Option<Watcher>> {
+        let Some(source) = config.watch_source.clone().filter(|s| !s.is_empty()) else {
+            return Ok(None);
+        };
+        if let SourceUri::Http { .. } = SourceUri::parse(&source)? {
+            return Err(eyre!(
+                "Cannot watch {}: http sources cannot be listed",
+                source
+            ));
+        }
+        Ok(Some(Watcher {
+            source,
+            pattern: config.watch_pattern.clone(),
+            template: config.watch_ingestion_id.clone(),
+            interval: Duration::from_secs(config.watch_interval_secs.max(1)),
+            running: Arc::default(),
+        }))
+    }
+
+    pub fn spawn(self, state: Data<AppState>) -> JoinHandle<()> {
+        info!("Watching {} every {:?}", self.source, self.interval);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                if let Err(err) = self.poll(&state).await {
+                    error!("Error polling {}: {}", self.source, err);
+                }
+            }
+        })
+    }
+
+    /// Lists the source once and starts a job per ingestion id for the new
+    /// objects, returning the started jobs.
+    pub async fn poll(&self, state: &Data<AppState>) -> Result<Vec<Uuid>> {
+        let uri = SourceUri::parse(&self.source)?;
+        let (bucket_ops, prefix) = state.sources.resolve(&self.source)?;
+        let objects = bucket_ops.list_objects(&prefix).await?;
+        let processed: HashMap<String, Option<String>> = state
+            .db
+            .get_watched_objects(&self.source)
+            .await?
+            .into_iter()
+            .map(|object| (object.key, object.etag))
+            .collect();
+
+        let mut batches: BTreeMap<String, Vec<ObjectInfo>> = BTreeMap::new();
+        for object in objects {
+            let relative = object
+                .key
+                .strip_prefix(prefix.as_str())
+                .unwrap_or(&object.key);
+            let relative = relative.trim_start_matches('/');
+            if !glob_match(&self.pattern, relative) {
+                continue;
+            }
+            let ingestion_id = render_template(&self.template, relative);
+            if ingestion_id.is_empty() {
+                warn!("Skipping {}: empty ingestion id", object.key);
+                continue;
+            }
+            batches.entry(ingestion_id).or_default().push(object);
+        }
+
+        let mut jobs = Vec::new();
+        for (ingestion_id, objects) in batches {
+            let changed = objects
+                .iter()
+                .any(|object| processed.get(&object.key) != Some(&object.etag));
+            if !changed || !self.claim(&ingestion_id) {
+                continue;
+            }
+            let files = match objects
+                .iter()
+                .map(|object| uri.object_uri(&object.key))
+                .collect::<Result<Vec<_>>>()
+            {
+                Ok(files) => files,
+                Err(err) => {
+                    self.release(&ingestion_id);
+                    return Err(err);
+                }
+            };
+            let job = state.jobs.create(ingestion_id.clone(), files.clone());
+            info!(
+                "Ingesting {} objects of {} as {}",
+                files.len(),
+                self.source,
+                ingestion_id
+            );
+            let request = IngestionRequest::new(files, ingestion_id.clone()).with_incremental(true);
+            let handle = spawn_job(job.job_id, request, state.clone());
+            let (watcher, state) = (self.clone(), state.clone());
+            drop(tokio::spawn(async move {
+                if let Err(err) = handle.await {
+                    error!("Error joining task: {}", err);
+                }
+                if let Err(err) = watcher.record(&state, job.job_id, &objects).await {
+                    error!("Error recording objects of {}: {}", ingestion_id, err);
+                }
+                watcher.release(&ingestion_id);
+            }));
+            jobs.push(job.job_id);
+        }
+        Ok(jobs)
+    }
+
+    fn claim(&self, ingestion_id: &str) -> bool {
+        let mut running = self.running.lock().expect("watcher lock poisoned");
+        running.insert(ingestion_id.to_owned())
+    }
+
+    fn release(&self, ingestion_id: &str) {
+        let mut running = self.running.lock().expect("watcher lock poisoned");
+        running.remove(ingestion_id);
+    }
+
+    /// Records the objects whose file the finished job ingested.
+    async fn record(
+        &self,
+        state: &Data<AppState>,
+        job_id: Uuid,
+        objects: &[ObjectInfo],
+    ) -> Result<()> {
+        let job = state
+            .jobs
+            .get(&job_id)
+            .ok_or_else(|| eyre!("Job {} not found", job_id))?;
+        for (object, file) in objects.iter().zip(&job.files) {
+            if file.state != FileState::Succeeded {
+                warn!("Not recording {}: {:?}", object.key, file.error);
+                continue;
+            }
+            state
+                .db
+                .save_watched_object(
+                    &self.source,
+                    &object.key,
+                    object.etag.as_deref(),
+                    &job.ingestion_id,
+                    job_id,
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.json", "graph.json"));
+        assert!(!glob_match("*.json", "nested/graph.json"));
+        assert!(glob_match("**/*.json", "nested/graph.json"));
+        assert!(glob_match("**/*.json", "graph.json"));
+        assert!(glob_match("**", "a/b/c.csv"));
+        assert!(glob_match("day-??/*.ndjson*", "day-01/export.ndjson.gz"));
+        assert!(!glob_match("day-??/*.ndjson", "day-1/export.ndjson"));
+    }
+
+    #[test]
+    fn test_render_template() {
+        let key = "customer-a/2024/graph.json.gz";
+        assert_eq!(render_template("{stem}", key), "graph");
+        assert_eq!(render_template("{dir}", key), "customer-a/2024");
+        assert_eq!(
+            render_template("{dir}-{name}", key),
+            "customer-a/2024-graph.json.gz"
+        );
+        assert_eq!(render_template("s3-{key}", "a.json"), "s3-a.json");
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{config::config::WebhookConfig, db::retry::backoff, domain::job::Job};
+use eyre::{eyre, Result};
+use hmac::{Hmac, Mac};
+use reqwest::{Client, StatusCode};
+use sha2::Sha256;
+use std::time::Duration;
+use tracing::warn;
+use url::Url;
+
+/// Carries `sha256=<hex HMAC of signed_payload>`.
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+/// Unix seconds of the delivery, covered by the signature so receivers can
+/// turn away replayed deliveries.
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const JOB_ID_HEADER: &str = "X-Job-Id";
+
+/// Accepts only absolute http(s) URLs as callback targets.
+pub fn validate_callback_url(url: &str) -> Result<()> {
+    let parsed = Url::parse(url).map_err(|err| eyre!("Invalid callback_url '{}': {}", url, err))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(()),
+        scheme => Err(eyre!("Unsupported callback_url scheme '{}'", scheme)),
+    }
+}
+
+/// What the signature covers: the timestamp, a dot, then the body.
+pub fn signed_payload(timestamp: i64, body: &[u8]) -> Vec<u8> {
+    let mut payload = format!("{}.", timestamp).into_bytes();
+    payload.extend_from_slice(body);
+    payload
+}
+
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Worth another try: the receiver was unreachable, overwhelmed or down.
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
+/// Posts the final state of a job to its `callback_url`.
+#[derive(Debug)]
+pub struct Notifier {
+    client: Client,
+    secret: Option<String>,
+    hosts: Vec<String>,
+    max_attempts: usize,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Notifier {
+    pub fn new(config: &WebhookConfig) -> Result<Self> {
+        // A redirect could lead anywhere, past the host check.
+        let client = Client::builder()
+            .timeout(Duration::from_millis(config.webhook_timeout_ms))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let hosts = config
+            .webhook_hosts
+            .split(',')
+            .map(|host| host.trim().to_lowercase())
+            .filter(|host| !host.is_empty())
+            .collect();
+        Ok(Self {
+            client,
+            secret: config.webhook_secret.clone().filter(|s| !s.is_empty()),
+            hosts,
+            max_attempts: config.webhook_max_attempts.max(1),
+            base_delay: Duration::from_millis(config.webhook_base_delay_ms),
+            max_delay: Duration::from_millis(config.webhook_max_delay_ms),
+        })
+    }
+
+    /// Callbacks are only sent signed, and only to the configured hosts, so
+    /// callers cannot point the service at internal addresses.
+    pub fn check_callback(&self, url: Option<&str>) -> Result<()> {
+        let Some(url) = url else {
+            return Ok(());
+        };
+        validate_callback_url(url)?;
+        if self.secret.is_none() {
+            return Err(eyre!("callback_url requires WEBHOOK_SECRET to be set"));
+        }
+        let host = Url::parse(url)?.host_str().map(str::to_lowercase);
+        if !host.is_some_and(|host| self.hosts.contains(&host)) {
+            return Err(eyre!("callback_url {} is not on an allowed host", url));
+        }
+        Ok(())
+    }
+
+    /// Delivers the job summary, retrying with backoff while the receiver
+    /// fails or answers 5xx, 408 or 429. Returns the last error once the
+    /// attempts are exhausted.
+    pub async fn notify(&self, url: &str, job: &Job) -> Result<()> {
+        // Checked again, as a resumed job may predate the current settings.
+        self.check_callback(Some(url))?;
+        let secret = self.secret.as_deref().unwrap_or_default();
+        let body = serde_json::to_vec(job)?;
+        let mut attempt = 1;
+        loop {
+            let timestamp = chrono::Utc::now().timestamp();
+            let signature = sign(secret, &signed_payload(timestamp, &body));
+            let request = self
+                .client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(JOB_ID_HEADER, job.job_id.to_string())
+                .header(TIMESTAMP_HEADER, timestamp.to_string())
+                .header(SIGNATURE_HEADER, signature)
+                .body(body.clone());
+            let (error, retryable) = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => {
+                    let status = response.status();
+                    (eyre!("callback answered {}", status), is_retryable(status))
+                }
+                Err(err) => (err.into(), true),
+            };
+            if !retryable || attempt >= self.max_attempts {
+                return Err(error);
+            }
+            let delay = backoff(self.base_delay, self.max_delay, attempt);
+            warn!(
+                "Callback of job {} failed on attempt {}, retrying in {:?}: {}",
+                job.job_id, attempt, delay, error
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_check_callback() {
+        let config = |secret: Option<&str>| WebhookConfig {
+            webhook_secret: secret.map(str::to_owned),
+            webhook_hosts: "Hooks.example.com".to_string(),
+            webhook_max_attempts: 1,
+            webhook_base_delay_ms: 0,
+            webhook_max_delay_ms: 0,
+            webhook_timeout_ms: 1000,
+        };
+        let notifier = Notifier::new(&config(Some("secret"))).unwrap();
+        assert!(notifier.check_callback(None).is_ok());
+        assert!(notifier
+            .check_callback(Some("https://hooks.example.com/ingest"))
+            .is_ok());
+        for url in [
+            "http://169.254.169.254/latest/meta-data",
+            "http://localhost:8080/admin",
+            "https://hooks.example.com.evil.test/ingest",
+            "ftp://hooks.example.com",
+        ] {
+            assert!(notifier.check_callback(Some(url)).is_err(), "{}", url);
+        }
+
+        let unsigned = Notifier::new(&config(None)).unwrap();
+        assert_eq!(
+            unsigned
+                .check_callback(Some("https://hooks.example.com/ingest"))
+                .unwrap_err()
+                .to_string(),
+            "callback_url requires WEBHOOK_SECRET to be set"
+        );
+        assert_eq!(signed_payload(1700000000, b"{}"), b"1700000000.{}");
+    }
+
+    #[test]
+    fn test_validate_callback_url() {
+        assert!(validate_callback_url("https://example.com/hooks/ingest").is_ok());
+        assert!(validate_callback_url("ftp://example.com").is_err());
+        assert!(validate_callback_url("not a url").is_err());
+    }
+}
@@ -5,11 +5,10 @@ use elasticsearch::{
     BulkOperation, BulkParts, Elasticsearch, SearchParts,
 };
 use eyre::{eyre, Result};
+use serde_json::json;
 use serde_json::Value;
-use serde_json::{from_value, json};
 use std::sync::Arc;
 use tracing::{error, info};
-use uuid::Uuid;
 
 use super::model::{IndexNode, SearchQueryParams};
 
@@ -65,13 +64,11 @@ impl ElasticService {
     }
 
     fn get_indexes(&self) -> Vec<IndexConfig> {
-        let mut indexes = vec![];
-        indexes.push(IndexConfig {
+        vec![IndexConfig {
             name: "graph".to_string(),
             mapping: self.get_node_index_mapping(),
             overwrite: false,
-        });
-        indexes
+        }]
     }
 
     async fn create_index(
@@ -89,7 +86,7 @@ impl ElasticService {
             .await?;
         if response.status_code() == 404 {
             info!("Index '{}' does not exist, creating it", index_name);
-            let res = self
+            let _res = self
                 .client
                 .indices()
                 .create(IndicesCreateParts::Index(index_name))
@@ -3,5 +3,6 @@ pub mod config;
 pub mod db;
 pub mod domain;
 pub mod elastic;
+pub mod queue;
 pub mod routes;
 pub mod telemetry;
@@ -0,0 +1,158 @@
+use super::{Delivery, MessageQueue};
+use async_trait::async_trait;
+use eyre::{eyre, Result};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::Duration,
+};
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+#[derive(Debug, Default)]
+struct Messages {
+    ready: VecDeque<Delivery>,
+    pending: HashMap<String, Delivery>,
+    acked: usize,
+}
+
+/// In-process queue, for tests and single-node setups; nothing survives a
+/// restart.
+#[derive(Debug)]
+pub struct MemoryQueue {
+    name: String,
+    messages: Mutex<Messages>,
+    notify: Notify,
+}
+
+impl MemoryQueue {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            messages: Mutex::new(Messages::default()),
+            notify: Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Messages> {
+        self.messages.lock().expect("memory queue lock poisoned")
+    }
+
+    /// Messages received but neither acked nor nacked yet.
+    pub fn pending(&self) -> usize {
+        self.lock().pending.len()
+    }
+
+    pub fn ready(&self) -> usize {
+        self.lock().ready.len()
+    }
+
+    pub fn acked(&self) -> usize {
+        self.lock().acked
+    }
+}
+
+#[async_trait]
+impl MessageQueue for MemoryQueue {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn publish(&self, payload: &[u8]) -> Result<String> {
+        let id = Uuid::new_v4().to_string();
+        self.lock().ready.push_back(Delivery {
+            id: id.clone(),
+            payload: payload.to_vec(),
+        });
+        self.notify.notify_one();
+        Ok(id)
+    }
+
+    async fn receive(&self, max: usize, block: Duration) -> Result<Vec<Delivery>> {
+        let deadline = tokio::time::Instant::now() + block;
+        loop {
+            // Registered before checking, so a publish in between is not missed.
+            let notified = self.notify.notified();
+            {
+                let mut messages = self.lock();
+                let count = max.min(messages.ready.len());
+                if count > 0 {
+                    let deliveries: Vec<Delivery> = messages.ready.drain(..count).collect();
+                    for delivery in &deliveries {
+                        messages
+                            .pending
+                            .insert(delivery.id.clone(), delivery.clone());
+                    }
+                    return Ok(deliveries);
+                }
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return Ok(vec![]);
+            }
+        }
+    }
+
+    async fn ack(&self, id: &str) -> Result<()> {
+        let mut messages = self.lock();
+        messages
+            .pending
+            .remove(id)
+            .ok_or_else(|| eyre!("Message {} is not pending", id))?;
+        messages.acked += 1;
+        Ok(())
+    }
+
+    async fn nack(&self, id: &str) -> Result<()> {
+        let mut messages = self.lock();
+        let delivery = messages
+            .pending
+            .remove(id)
+            .ok_or_else(|| eyre!("Message {} is not pending", id))?;
+        messages.ready.push_front(delivery);
+        drop(messages);
+        self.notify.notify_one();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_queue() {
+        let queue = MemoryQueue::new("test");
+        let block = Duration::from_millis(10);
+        assert!(queue.receive(1, block).await.unwrap().is_empty());
+
+        let first = queue.publish(b"one").await.unwrap();
+        queue.publish(b"two").await.unwrap();
+        let deliveries = queue.receive(1, block).await.unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].id, first);
+        assert_eq!(queue.pending(), 1);
+
+        queue.nack(&first).await.unwrap();
+        let deliveries = queue.receive(5, block).await.unwrap();
+        assert_eq!(deliveries.len(), 2);
+        assert_eq!(deliveries[0].payload, b"one");
+        for delivery in deliveries {
+            queue.ack(&delivery.id).await.unwrap();
+        }
+        assert_eq!((queue.pending(), queue.ready(), queue.acked()), (0, 0, 2));
+        assert!(queue.ack(&first).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_memory_queue_wakes_receiver() {
+        let queue = std::sync::Arc::new(MemoryQueue::new("test"));
+        let receiver = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.receive(1, Duration::from_secs(5)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        queue.publish(b"late").await.unwrap();
+        let deliveries = receiver.await.unwrap().unwrap();
+        assert_eq!(deliveries[0].payload, b"late");
+    }
+}
@@ -0,0 +1,43 @@
+pub mod memory;
+pub mod redis_streams;
+
+use async_trait::async_trait;
+use eyre::Result;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    /// Unique within the queue; redeliveries keep the same id.
+    pub id: String,
+    pub payload: Vec<u8>,
+}
+
+/// An at-least-once queue: a received message stays pending until it is
+/// acked, and comes back if it is nacked or its consumer goes away.
+#[async_trait]
+pub trait MessageQueue: Send + Sync {
+    /// Identifies the queue, e.g. the stream key.
+    fn name(&self) -> &str;
+
+    async fn publish(&self, payload: &[u8]) -> Result<String>;
+
+    /// Waits up to `block` for messages and returns at most `max` of them,
+    /// or none when the wait runs out.
+    async fn receive(&self, max: usize, block: Duration) -> Result<Vec<Delivery>>;
+
+    async fn ack(&self, id: &str) -> Result<()>;
+
+    /// Gives the message back for redelivery.
+    async fn nack(&self, id: &str) -> Result<()>;
+
+    /// How long a received message may go without `touch` before the queue
+    /// hands it to another consumer; `None` when it never does.
+    fn lease(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Keeps a message that is still being handled with this consumer.
+    async fn touch(&self, _id: &str) -> Result<()> {
+        Ok(())
+    }
+}
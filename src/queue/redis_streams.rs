@@ -0,0 +1,241 @@
+use super::{Delivery, MessageQueue};
+use async_trait::async_trait;
+use eyre::{eyre, Result};
+use redis::{
+    aio::MultiplexedConnection,
+    streams::{StreamPendingCountReply, StreamRangeReply, StreamReadOptions, StreamReadReply},
+    AsyncCommands, Value,
+};
+use std::time::Duration;
+use tracing::warn;
+
+/// Field of the stream entry holding the message.
+pub const PAYLOAD_FIELD: &str = "payload";
+/// Fields of a dead stream entry besides the payload.
+pub const MESSAGE_ID_FIELD: &str = "message_id";
+pub const DELIVERIES_FIELD: &str = "deliveries";
+
+/// Stream that messages delivered too often are moved to.
+pub fn dead_stream(stream: &str) -> String {
+    format!("{}:dead", stream)
+}
+
+/// A Redis stream read through a consumer group. Entries stay in the
+/// group's pending list until acked; entries left pending longer than
+/// `claim_idle`, nacked ones included, are claimed again by whichever
+/// consumer polls next. A consumer keeps the entries it is still handling
+/// by touching them. Entries are delivered at most `max_deliveries` times;
+/// after that they are moved to the dead stream.
+pub struct RedisStreamQueue {
+    connection: MultiplexedConnection,
+    /// Blocking reads hold up every command queued behind them, so they get
+    /// a connection of their own.
+    reader: MultiplexedConnection,
+    stream: String,
+    group: String,
+    consumer: String,
+    claim_idle: Duration,
+    max_deliveries: usize,
+    dead_stream: String,
+}
+
+impl std::fmt::Debug for RedisStreamQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisStreamQueue")
+            .field("stream", &self.stream)
+            .field("group", &self.group)
+            .field("consumer", &self.consumer)
+            .finish()
+    }
+}
+
+impl RedisStreamQueue {
+    /// Connects and creates the stream and the group if they do not exist.
+    pub async fn connect(
+        url: &str,
+        stream: &str,
+        group: &str,
+        consumer: &str,
+        claim_idle: Duration,
+        max_deliveries: usize,
+    ) -> Result<Self> {
+        let client = redis::Client::open(url)?;
+        let mut connection = client.get_multiplexed_tokio_connection().await?;
+        let reader = client.get_multiplexed_tokio_connection().await?;
+        let created: redis::RedisResult<()> =
+            connection.xgroup_create_mkstream(stream, group, "0").await;
+        match created {
+            Err(err) if err.code() == Some("BUSYGROUP") => {}
+            other => other?,
+        }
+        Ok(Self {
+            connection,
+            reader,
+            stream: stream.to_owned(),
+            group: group.to_owned(),
+            consumer: consumer.to_owned(),
+            claim_idle,
+            max_deliveries: max_deliveries.max(1),
+            dead_stream: dead_stream(stream),
+        })
+    }
+
+    fn delivery(id: String, map: &std::collections::HashMap<String, Value>) -> Result<Delivery> {
+        let payload = map
+            .get(PAYLOAD_FIELD)
+            .map(redis::from_redis_value::<Vec<u8>>)
+            .transpose()?
+            .unwrap_or_default();
+        Ok(Delivery { id, payload })
+    }
+
+    /// Takes over entries other consumers (or this one) left pending for
+    /// longer than `claim_idle`.
+    async fn claim(&self, max: usize) -> Result<Vec<Delivery>> {
+        let mut connection = self.connection.clone();
+        let reply: Vec<Value> = redis::cmd("XAUTOCLAIM")
+            .arg(&self.stream)
+            .arg(&self.group)
+            .arg(&self.consumer)
+            .arg(self.claim_idle.as_millis() as u64)
+            .arg("0-0")
+            .arg("COUNT")
+            .arg(max)
+            .query_async(&mut connection)
+            .await?;
+        let entries = reply
+            .get(1)
+            .ok_or_else(|| eyre!("Unexpected XAUTOCLAIM reply"))?;
+        let entries: StreamRangeReply = redis::from_redis_value(entries)?;
+        let mut deliveries = Vec::with_capacity(entries.ids.len());
+        for entry in entries.ids {
+            let delivery = Self::delivery(entry.id, &entry.map)?;
+            // Over the limit only when its consumers kept dying on it.
+            let delivered = self.times_delivered(&delivery.id).await?;
+            if delivered > self.max_deliveries {
+                self.bury(&delivery, delivered).await?;
+            } else {
+                deliveries.push(delivery);
+            }
+        }
+        Ok(deliveries)
+    }
+
+    /// How often the entry was delivered, 0 when it is not pending.
+    async fn times_delivered(&self, id: &str) -> Result<usize> {
+        let mut connection = self.connection.clone();
+        let reply: StreamPendingCountReply = connection
+            .xpending_count(&self.stream, &self.group, id, id, 1)
+            .await?;
+        Ok(reply
+            .ids
+            .first()
+            .map(|pending| pending.times_delivered)
+            .unwrap_or_default())
+    }
+
+    /// Moves the entry to the dead stream and acks it.
+    async fn bury(&self, delivery: &Delivery, delivered: usize) -> Result<()> {
+        let mut connection = self.connection.clone();
+        let _: String = redis::cmd("XADD")
+            .arg(&self.dead_stream)
+            .arg("*")
+            .arg(PAYLOAD_FIELD)
+            .arg(&delivery.payload)
+            .arg(MESSAGE_ID_FIELD)
+            .arg(&delivery.id)
+            .arg(DELIVERIES_FIELD)
+            .arg(delivered)
+            .query_async(&mut connection)
+            .await?;
+        let _: usize = connection
+            .xack(&self.stream, &self.group, &[&delivery.id])
+            .await?;
+        warn!(
+            "Moved message {} to {} after {} deliveries",
+            delivery.id, self.dead_stream, delivered
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageQueue for RedisStreamQueue {
+    fn name(&self) -> &str {
+        &self.stream
+    }
+
+    async fn publish(&self, payload: &[u8]) -> Result<String> {
+        let mut connection = self.connection.clone();
+        let id: String = connection
+            .xadd(&self.stream, "*", &[(PAYLOAD_FIELD, payload)])
+            .await?;
+        Ok(id)
+    }
+
+    async fn receive(&self, max: usize, block: Duration) -> Result<Vec<Delivery>> {
+        let claimed = self.claim(max).await?;
+        if !claimed.is_empty() {
+            return Ok(claimed);
+        }
+        let mut reader = self.reader.clone();
+        let options = StreamReadOptions::default()
+            .group(&self.group, &self.consumer)
+            .count(max)
+            .block(block.as_millis() as usize);
+        let reply: Option<StreamReadReply> = reader
+            .xread_options(&[&self.stream], &[">"], &options)
+            .await?;
+        reply
+            .into_iter()
+            .flat_map(|reply| reply.keys)
+            .flat_map(|key| key.ids)
+            .map(|entry| Self::delivery(entry.id, &entry.map))
+            .collect()
+    }
+
+    async fn ack(&self, id: &str) -> Result<()> {
+        let mut connection = self.connection.clone();
+        let _: usize = connection.xack(&self.stream, &self.group, &[id]).await?;
+        Ok(())
+    }
+
+    /// Leaves the entry pending; it is claimed again once `claim_idle` has
+    /// passed, which spaces out retries of a failing request. An entry that
+    /// used up its deliveries is moved to the dead stream instead.
+    async fn nack(&self, id: &str) -> Result<()> {
+        let delivered = self.times_delivered(id).await?;
+        if delivered < self.max_deliveries {
+            return Ok(());
+        }
+        let mut connection = self.connection.clone();
+        let entries: StreamRangeReply = connection.xrange_count(&self.stream, id, id, 1).await?;
+        match entries.ids.first() {
+            Some(entry) => {
+                let delivery = Self::delivery(entry.id.clone(), &entry.map)?;
+                self.bury(&delivery, delivered).await
+            }
+            // Trimmed from the stream; nothing is left to redeliver.
+            None => self.ack(id).await,
+        }
+    }
+
+    fn lease(&self) -> Option<Duration> {
+        Some(self.claim_idle)
+    }
+
+    /// Claims the entry for this consumer again, which resets its idle time.
+    async fn touch(&self, id: &str) -> Result<()> {
+        let mut connection = self.connection.clone();
+        let _: Vec<String> = redis::cmd("XCLAIM")
+            .arg(&self.stream)
+            .arg(&self.group)
+            .arg(&self.consumer)
+            .arg(0)
+            .arg(id)
+            .arg("JUSTID")
+            .query_async(&mut connection)
+            .await?;
+        Ok(())
+    }
+}
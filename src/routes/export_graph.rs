@@ -0,0 +1,76 @@
+use crate::{application::AppState, db::model::NodeModel, domain::s3::graphml::write_graphml};
+use actix_web::{
+    error::{ErrorBadRequest, ErrorInternalServerError},
+    get,
+    web::{self, Data},
+    Error, HttpResponse,
+};
+use eyre::Result;
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Clone, Deserialize, Default)]
+pub struct ExportQuery {
+    pub max_depth: Option<usize>,
+}
+
+#[get("/nodes/{id}/graphml")]
+async fn export_graphml(
+    path: web::Path<String>,
+    query: web::Query<ExportQuery>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let uuid = Uuid::parse_str(&path.into_inner()).map_err(ErrorBadRequest)?;
+    let rows = collect_subgraph(uuid, query.max_depth, &state)
+        .await
+        .map_err(|err| {
+            tracing::error!("Error collecting subgraph: {:?}", err);
+            ErrorInternalServerError(err)
+        })?;
+    if rows.is_empty() {
+        return Ok(HttpResponse::NoContent().finish());
+    }
+    let document = write_graphml(&rows).map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/graphml+xml")
+        .body(document))
+}
+
+/// Walks the `Out`/`Child` rows breadth first and returns every row of the
+/// visited nodes, stopping after `max_depth` levels when one is given.
+async fn collect_subgraph(
+    root: Uuid,
+    max_depth: Option<usize>,
+    state: &AppState,
+) -> Result<Vec<NodeModel>> {
+    let mut rows = vec![];
+    let mut visited = HashSet::from([root]);
+    let mut level = vec![root];
+    let mut depth = 0;
+    while !level.is_empty() {
+        let results = try_join_all(level.iter().map(|id| state.db.get_node_rows(*id))).await?;
+        let mut next = vec![];
+        for row in results.into_iter().flatten() {
+            let is_child =
+                row.direction.as_deref() == Some("Out") && row.relation.as_deref() == Some("Child");
+            let within_depth = !matches!(max_depth, Some(max) if depth >= max);
+            if is_child && within_depth {
+                if let Some(child) = row
+                    .relates_to
+                    .as_deref()
+                    .and_then(|id| Uuid::parse_str(id).ok())
+                {
+                    if visited.insert(child) {
+                        next.push(child);
+                    }
+                }
+            }
+            rows.push(row);
+        }
+        level = next;
+        depth += 1;
+    }
+    Ok(rows)
+}
@@ -1,6 +1,9 @@
-use crate::{application::AppState, domain::node::Node};
+use crate::{
+    application::AppState,
+    domain::{node::Node, relation::TagFilter},
+};
 use actix_web::{
-    error::ErrorInternalServerError,
+    error::{ErrorBadRequest, ErrorInternalServerError},
     get,
     web::{self, Data},
     Error, HttpResponse, Result,
@@ -12,6 +15,7 @@ use uuid::Uuid;
 struct NodeQuery {
     tags: Option<bool>,
     relations: Option<bool>,
+    relation_tag: Option<String>,
 }
 
 #[get("/nodes/{id}")]
@@ -29,7 +33,13 @@ async fn get_node_by_id(
         }
     };
     let query_data = query_data.into_inner();
-    let result = get_node_by_id_internal(uuid, query_data, state).await;
+    let tag_filter = query_data
+        .relation_tag
+        .as_deref()
+        .map(TagFilter::parse)
+        .transpose()
+        .map_err(ErrorBadRequest)?;
+    let result = get_node_by_id_internal(uuid, query_data, tag_filter, state).await;
     match result {
         Ok(node) => {
             if let Some(node) = node {
@@ -48,10 +58,26 @@ async fn get_node_by_id(
 async fn get_node_by_id_internal(
     uuid: Uuid,
     query: NodeQuery,
+    tag_filter: Option<TagFilter>,
     state: Data<AppState>,
 ) -> eyre::Result<Option<Node>> {
     let tags = query.tags.unwrap_or(true);
     let relations = query.relations.unwrap_or(true);
+    if relations {
+        let rows = state.db.get_node_rows(uuid).await?;
+        let mut node = match Node::from_rows(rows) {
+            Some(node) => node,
+            None => return Ok(None),
+        };
+        if !tags {
+            node.tags.clear();
+        }
+        if let Some(filter) = tag_filter {
+            node.relations
+                .retain(|relation| filter.matches(&relation.tags));
+        }
+        return Ok(Some(node));
+    }
     let result = state.db.get_node(uuid, tags, relations).await?;
     match result {
         Some(node) => Ok(Some(Node::from(node))),
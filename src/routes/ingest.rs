@@ -1,70 +1,380 @@
 use crate::application::AppState;
-use crate::db::model::process_nodes;
-use crate::domain::relation::process_relations;
-use crate::domain::s3::download::read_from_local_file;
+use crate::domain::checkpoint::Resume;
+use crate::domain::job::{FailureKind, Job, JobEvent, JobState};
+use crate::domain::pipeline::{resume_job, spawn_job};
+use crate::domain::preview::{preview, PreviewRequest};
+use crate::domain::s3::format::InputFormat;
+use crate::domain::upload::{self, PayloadTooLarge, Upload};
+use crate::domain::validation::Strictness;
+use actix_multipart::Multipart;
 use actix_web::{
+    error::{
+        ErrorBadRequest, ErrorInternalServerError, ErrorPayloadTooLarge, ErrorUnprocessableEntity,
+    },
+    get,
+    http::{header, StatusCode},
     post,
-    web::{self, Data},
+    web::{self, Bytes, Data},
     Error, HttpResponse,
 };
+use futures::{
+    future::ready,
+    stream::{self, Stream},
+    StreamExt,
+};
 use serde::{Deserialize, Serialize};
-use tracing::{error, info};
+use std::time::Duration;
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    task::JoinHandle,
+};
+use tracing::error;
+use uuid::Uuid;
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct IngestionRequest {
-    files: Vec<String>,
-    ingestion_id: String,
+pub use crate::domain::pipeline::IngestionRequest;
+
+#[derive(Debug, Serialize, Clone, Deserialize, Default)]
+struct IngestQuery {
+    wait: Option<bool>,
 }
 
-impl IngestionRequest {
-    pub fn new(files: Vec<String>, ingestion_id: String) -> Self {
-        Self {
-            files,
-            ingestion_id,
-        }
-    }
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IngestionAccepted {
+    pub job_id: Uuid,
+    pub status_url: String,
 }
 
 #[post("/ingest")]
 async fn ingest(
     payload: web::Json<IngestionRequest>,
+    query: web::Query<IngestQuery>,
     state: Data<AppState>,
 ) -> Result<HttpResponse, Error> {
-    let mut handles = vec![];
-    for file in payload.files.iter() {
-        let ingestion_id = payload.ingestion_id.clone();
-        let file = file.clone();
-        let state = state.clone();
-        let handle = tokio::spawn(async move { process_file(ingestion_id, file, state).await });
-        handles.push(handle);
-    }
+    let payload = payload.into_inner();
+    payload.validate().map_err(ErrorBadRequest)?;
+    state
+        .notifier
+        .check_callback(payload.callback_url.as_deref())
+        .map_err(ErrorBadRequest)?;
+    let job = state
+        .jobs
+        .create(payload.ingestion_id.clone(), payload.files.clone());
+    let job_id = job.job_id;
+    let handle = spawn_job(job_id, payload, state.clone());
+    respond(job_id, handle, query.wait.unwrap_or(false), &state).await
+}
 
-    for handle in handles {
-        match handle.await {
-            Ok(res) => match res {
-                Ok(_) => info!("The record save successfully"),
-                Err(err) => error!("Error occured when record saved: {}", err),
-            },
-            Err(err) => error!("Error joining task: {}", err),
+/// Ingests GraphData files sent as `multipart/form-data`. Every file part
+/// is streamed to a staging directory and ingested from there; the form
+/// also carries `ingestion_id` and, optionally, `format`, `validation`,
+/// `streaming`, `incremental` and `callback_url`. The staged files are
+/// removed once the job succeeds and kept for a while otherwise, so the job
+/// can still be resumed.
+#[post("/ingest/upload")]
+async fn ingest_upload(
+    form: Multipart,
+    query: web::Query<IngestQuery>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let job_id = Uuid::new_v4();
+    let dir = upload::uploads_dir().join(job_id.to_string());
+    let payload = match upload::stage(form, &dir, state.upload_max_bytes)
+        .await
+        .and_then(upload_request)
+        .and_then(|payload| {
+            state
+                .notifier
+                .check_callback(payload.callback_url.as_deref())
+                .map(|_| payload)
+        }) {
+        Ok(payload) => payload,
+        Err(err) => {
+            let _ = tokio::fs::remove_dir_all(&dir).await;
+            if err.downcast_ref::<PayloadTooLarge>().is_some() {
+                return Err(ErrorPayloadTooLarge(err));
+            }
+            return Err(ErrorBadRequest(err));
         }
+    };
+
+    state
+        .jobs
+        .create_with_id(job_id, payload.ingestion_id.clone(), payload.files.clone());
+    let job = spawn_job(job_id, payload, state.clone());
+    let jobs = state.clone();
+    let handle = tokio::spawn(async move {
+        if let Err(err) = job.await {
+            error!("Error joining task: {}", err);
+        }
+        let succeeded = jobs
+            .jobs
+            .get(&job_id)
+            .is_some_and(|job| job.state == JobState::Succeeded);
+        if succeeded {
+            if let Err(err) = tokio::fs::remove_dir_all(&dir).await {
+                error!("Error removing uploads of job {}: {}", job_id, err);
+            }
+        }
+    });
+    respond(job_id, handle, query.wait.unwrap_or(false), &state).await
+}
+
+fn upload_request(upload: Upload) -> eyre::Result<IngestionRequest> {
+    let Upload { files, mut fields } = upload;
+    if files.is_empty() {
+        return Err(eyre::eyre!("No file was uploaded"));
+    }
+    let ingestion_id = fields
+        .remove("ingestion_id")
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| eyre::eyre!("Missing ingestion_id field"))?;
+    let files = files
+        .iter()
+        .map(|path| format!("file://{}", path.display()))
+        .collect();
+    let mut request = IngestionRequest::new(files, ingestion_id);
+    if let Some(format) = fields.remove("format") {
+        request = request.with_format(serde_json::from_value::<InputFormat>(format.into())?);
+    }
+    if let Some(validation) = fields.remove("validation") {
+        request = request.with_validation(serde_json::from_value::<Strictness>(validation.into())?);
+    }
+    if let Some(streaming) = fields.remove("streaming") {
+        request = request.with_streaming(streaming.parse()?);
     }
-    Ok(HttpResponse::Ok().json(r#"{ "status": "OK"}"#))
+    if let Some(incremental) = fields.remove("incremental") {
+        request = request.with_incremental(incremental.parse()?);
+    }
+    if let Some(callback_url) = fields.remove("callback_url") {
+        request = request.with_callback_url(callback_url);
+    }
+    request.validate()?;
+    Ok(request)
 }
 
-async fn process_file(
-    ingestion_id: String,
-    file: String,
+/// Answers 202 right away, or waits for the job and answers with its final
+/// status.
+async fn respond(
+    job_id: Uuid,
+    handle: JoinHandle<()>,
+    wait: bool,
+    state: &AppState,
+) -> Result<HttpResponse, Error> {
+    if !wait {
+        return Ok(HttpResponse::Accepted().json(IngestionAccepted {
+            job_id,
+            status_url: format!("/ingest/{}", job_id),
+        }));
+    }
+
+    if let Err(err) = handle.await {
+        error!("Error joining task: {}", err);
+    }
+    let job = state
+        .jobs
+        .get(&job_id)
+        .ok_or_else(|| ErrorInternalServerError("ingestion job disappeared"))?;
+    Ok(HttpResponse::build(job_status_code(&job)).json(job))
+}
+
+#[post("/ingest/{job_id}/resume")]
+async fn resume_ingestion(
+    path: web::Path<String>,
+    query: web::Query<IngestQuery>,
     state: Data<AppState>,
-) -> eyre::Result<()> {
-    info!(
-        "Processing file {} with ingestion id {}",
-        file, ingestion_id
+) -> Result<HttpResponse, Error> {
+    let job_id = Uuid::parse_str(&path.into_inner()).map_err(ErrorBadRequest)?;
+    let rows = state.db.get_checkpoints(job_id).await.map_err(|err| {
+        error!("Error loading checkpoints: {:?}", err);
+        ErrorInternalServerError(err)
+    })?;
+    let Some(resume) = Resume::from_rows(rows).map_err(ErrorInternalServerError)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let created = state.jobs.try_create_with_id(
+        job_id,
+        resume.request.ingestion_id.clone(),
+        resume.request.files.clone(),
     );
-    // let bucket_ops = create_bucket_ops("test_region", "test_bucket")?;
-    // let contents = read_graph_from_s3(bucket_ops, &file).await?;
-    let contents = read_from_local_file(&file).await?;
-    let relations = process_relations(&ingestion_id, contents.relations);
-    let nodes = process_nodes(&ingestion_id, contents.nodes, relations).await?;
-    let _result = state.db.insert_nodes(nodes).await?;
-    Ok(())
+    if let Err(running) = created {
+        return Ok(HttpResponse::Conflict().json(running));
+    }
+    let handle = resume_job(job_id, resume, state.clone());
+    respond(job_id, handle, query.wait.unwrap_or(false), &state).await
+}
+
+#[post("/ingest/preview")]
+async fn preview_ingestion(
+    payload: web::Json<PreviewRequest>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let payload = payload.into_inner();
+    payload.validate().map_err(ErrorBadRequest)?;
+    let data = match (payload.data, payload.file) {
+        (Some(data), _) => data,
+        (None, Some(file)) => {
+            let format = InputFormat::detect(&file, payload.format);
+            state
+                .sources
+                .read_graph_as(&file, format)
+                .await
+                .map_err(ErrorUnprocessableEntity)?
+        }
+        (None, None) => return Err(ErrorBadRequest("missing file or data")),
+    };
+    let preview = preview(&payload.ingestion_id, data, payload.validation)
+        .await
+        .map_err(ErrorUnprocessableEntity)?;
+    Ok(HttpResponse::Ok().json(preview))
+}
+
+#[get("/ingest/{job_id}")]
+async fn get_ingestion_job(
+    path: web::Path<String>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let job_id = Uuid::parse_str(&path.into_inner()).map_err(ErrorBadRequest)?;
+    match state.jobs.get(&job_id) {
+        Some(job) => Ok(HttpResponse::Ok().json(job)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// Streams the progress of a job as Server-Sent Events. The first event is a
+/// `snapshot` of the job; the stream ends with `job_finished`.
+#[get("/ingest/{job_id}/events")]
+async fn ingestion_events(
+    path: web::Path<String>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let job_id = Uuid::parse_str(&path.into_inner()).map_err(ErrorBadRequest)?;
+    // Subscribe before reading the snapshot so no event falls in between.
+    let receiver = state.jobs.subscribe(&job_id);
+    let Some(job) = state.jobs.get(&job_id) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let snapshot = sse_frame("snapshot", &job);
+    let events = match receiver {
+        Some(receiver) => live_events(receiver).left_stream(),
+        None => {
+            let finished = JobEvent::JobFinished { job: Box::new(job) };
+            stream::once(ready(sse_frame(finished.name(), &finished))).right_stream()
+        }
+    };
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, "text/event-stream"))
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(stream::once(ready(snapshot)).chain(events)))
+}
+
+fn sse_frame<T: Serialize>(name: &str, data: &T) -> Result<Bytes, serde_json::Error> {
+    let data = serde_json::to_string(data)?;
+    Ok(Bytes::from(format!("event: {}\ndata: {}\n\n", name, data)))
+}
+
+/// Idle streams get a comment this often so proxies keep them open.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+fn live_events(
+    receiver: broadcast::Receiver<JobEvent>,
+) -> impl Stream<Item = Result<Bytes, serde_json::Error>> {
+    stream::unfold(Some(receiver), |receiver| async move {
+        let mut receiver = receiver?;
+        let frame = match tokio::time::timeout(KEEP_ALIVE, receiver.recv()).await {
+            Err(_) => Ok(Bytes::from_static(b": keep-alive\n\n")),
+            Ok(Ok(event)) => {
+                let frame = sse_frame(event.name(), &event);
+                if matches!(event, JobEvent::JobFinished { .. }) {
+                    return Some((frame, None));
+                }
+                frame
+            }
+            Ok(Err(RecvError::Lagged(missed))) => {
+                Ok(Bytes::from(format!(": missed {} events\n\n", missed)))
+            }
+            Ok(Err(RecvError::Closed)) => return None,
+        };
+        Some((frame, Some(receiver)))
+    })
+}
+
+pub fn job_status_code(job: &Job) -> StatusCode {
+    match job.state {
+        JobState::Queued | JobState::Running => StatusCode::ACCEPTED,
+        JobState::Succeeded => StatusCode::OK,
+        JobState::Partial => StatusCode::MULTI_STATUS,
+        JobState::Failed => {
+            let input_only = job
+                .files
+                .iter()
+                .all(|f| f.failure == Some(FailureKind::Input));
+            if input_only {
+                StatusCode::UNPROCESSABLE_ENTITY
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::job_status_code;
+    use crate::domain::job::{FailureKind, FileCounts, JobRegistry};
+    use actix_web::http::StatusCode;
+
+    #[test]
+    fn test_job_status_code() {
+        let registry = JobRegistry::default();
+        let files = vec!["a.json".to_string(), "b.json".to_string()];
+        let job = registry.create("ingestion".to_string(), files.clone());
+        assert_eq!(job_status_code(&job), StatusCode::ACCEPTED);
+
+        registry.file_finished(&job.job_id, 0, FileCounts::default(), None);
+        registry.file_failed(
+            &job.job_id,
+            1,
+            FailureKind::Input,
+            "bad".to_string(),
+            FileCounts::default(),
+        );
+        let partial = registry.get(&job.job_id).unwrap();
+        assert_eq!(job_status_code(&partial), StatusCode::MULTI_STATUS);
+
+        let job = registry.create("ingestion".to_string(), files.clone());
+        registry.file_failed(
+            &job.job_id,
+            0,
+            FailureKind::Input,
+            "bad".to_string(),
+            FileCounts::default(),
+        );
+        registry.file_failed(
+            &job.job_id,
+            1,
+            FailureKind::Input,
+            "bad".to_string(),
+            FileCounts::default(),
+        );
+        let failed = registry.get(&job.job_id).unwrap();
+        assert_eq!(job_status_code(&failed), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let job = registry.create("ingestion".to_string(), files);
+        registry.file_failed(
+            &job.job_id,
+            0,
+            FailureKind::Input,
+            "bad".to_string(),
+            FileCounts::default(),
+        );
+        registry.file_failed(
+            &job.job_id,
+            1,
+            FailureKind::Storage,
+            "down".to_string(),
+            FileCounts::default(),
+        );
+        let failed = registry.get(&job.job_id).unwrap();
+        assert_eq!(job_status_code(&failed), StatusCode::INTERNAL_SERVER_ERROR);
+    }
 }
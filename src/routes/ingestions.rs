@@ -0,0 +1,193 @@
+use crate::{
+    application::AppState,
+    db::dead_letter::DeadLetterSink,
+    domain::{
+        deletion::spawn_deletion,
+        ingestion::Ingestion,
+        path::{uuid_changes, UuidChange},
+        s3::format::InputFormat,
+    },
+};
+use actix_web::{
+    delete,
+    error::{ErrorBadRequest, ErrorInternalServerError},
+    get, post,
+    web::{self, Data},
+    Error, HttpResponse,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeletionAccepted {
+    pub job_id: Uuid,
+    pub status_url: String,
+}
+
+#[get("/ingestions")]
+async fn list_ingestions(state: Data<AppState>) -> Result<HttpResponse, Error> {
+    let mut ingestions: Vec<Ingestion> = state
+        .db
+        .list_ingestions()
+        .await
+        .and_then(|rows| rows.into_iter().map(Ingestion::try_from).collect())
+        .map_err(|err| {
+            tracing::error!("Error listing ingestions: {:?}", err);
+            ErrorInternalServerError(err)
+        })?;
+    ingestions.sort_by_key(|ingestion| std::cmp::Reverse(ingestion.started_at));
+    Ok(HttpResponse::Ok().json(ingestions))
+}
+
+#[get("/ingestions/{ingestion_id}")]
+async fn get_ingestion(
+    path: web::Path<String>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let ingestion_id = path.into_inner();
+    let ingestion = state
+        .db
+        .get_ingestion(&ingestion_id)
+        .await
+        .and_then(|row| row.map(Ingestion::try_from).transpose())
+        .map_err(|err| {
+            tracing::error!("Error fetching ingestion: {:?}", err);
+            ErrorInternalServerError(err)
+        })?;
+    match ingestion {
+        Some(ingestion) => Ok(HttpResponse::Ok().json(ingestion)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// The files an ingestion was (or would be) written from, to look for
+/// stored rows under the UUIDs of the legacy path encoding.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PathMigrationRequest {
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub format: Option<InputFormat>,
+}
+
+/// The stored UUIDs of an ingestion that the canonical path encoding moves.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PathMigration {
+    pub ingestion_id: String,
+    pub files: Vec<String>,
+    pub changes: Vec<UuidChange>,
+}
+
+#[post("/ingestions/{ingestion_id}/path-migration")]
+async fn plan_path_migration(
+    path: web::Path<String>,
+    request: web::Json<PathMigrationRequest>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let ingestion_id = path.into_inner();
+    let request = request.into_inner();
+    if request.files.is_empty() {
+        return Err(ErrorBadRequest("files must not be empty"));
+    }
+    match path_migration(&ingestion_id, request, &state).await {
+        Ok(migration) => Ok(HttpResponse::Ok().json(migration)),
+        Err(err) => {
+            tracing::error!("Error planning path migration: {:?}", err);
+            Err(ErrorInternalServerError(err))
+        }
+    }
+}
+
+/// Keeps the changes whose legacy UUID still has rows in `graph.nodes`,
+/// whatever run or version wrote them.
+async fn path_migration(
+    ingestion_id: &str,
+    request: PathMigrationRequest,
+    state: &AppState,
+) -> eyre::Result<PathMigration> {
+    let mut changes = Vec::new();
+    for file in &request.files {
+        let format = InputFormat::detect(file, request.format);
+        let data = state.sources.read_graph_as(file, format).await?;
+        changes.extend(uuid_changes(ingestion_id, &data));
+    }
+    // Relation endpoints in particular recur across the files.
+    changes.sort_by(|a, b| (a.kind, &a.path).cmp(&(b.kind, &b.path)));
+    changes.dedup_by(|a, b| a.kind == b.kind && a.path == b.path);
+
+    let mut checks = stream::iter(changes)
+        .map(|change| async move {
+            let stored = state.db.node_exists(change.old_uuid).await?;
+            eyre::Ok(stored.then_some(change))
+        })
+        .buffered(state.db.concurrency_limit.max(1));
+    let mut stored = Vec::new();
+    while let Some(change) = checks.try_next().await? {
+        stored.extend(change);
+    }
+    Ok(PathMigration {
+        ingestion_id: ingestion_id.to_owned(),
+        files: request.files,
+        changes: stored,
+    })
+}
+
+/// What replaying the dead-lettered rows of an ingestion wrote.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ReplayReport {
+    pub ingestion_id: String,
+    pub rows_written: usize,
+    pub rows_failed: usize,
+    pub rows_dead_lettered: usize,
+}
+
+#[post("/ingestions/{ingestion_id}/failed-rows/replay")]
+async fn replay_failed_rows(
+    path: web::Path<String>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let ingestion_id = path.into_inner();
+    if let DeadLetterSink::Discard = state.db.dead_letter {
+        return Err(ErrorBadRequest("No dead-letter sink is configured"));
+    }
+    let summary = state
+        .db
+        .replay_failed_rows(&ingestion_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("Error replaying failed rows: {:?}", err);
+            ErrorInternalServerError(err)
+        })?;
+    Ok(HttpResponse::Ok().json(ReplayReport {
+        ingestion_id,
+        rows_written: summary.rows_written,
+        rows_failed: summary.rows_failed,
+        rows_dead_lettered: summary.rows_dead_lettered,
+    }))
+}
+
+#[delete("/ingestions/{ingestion_id}")]
+async fn delete_ingestion(
+    path: web::Path<String>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let ingestion_id = path.into_inner();
+    let job = state.deletions.create(ingestion_id.clone());
+    spawn_deletion(job.job_id, ingestion_id, state.clone());
+    Ok(HttpResponse::Accepted().json(DeletionAccepted {
+        job_id: job.job_id,
+        status_url: format!("/deletions/{}", job.job_id),
+    }))
+}
+
+#[get("/deletions/{job_id}")]
+async fn get_deletion_job(
+    path: web::Path<String>,
+    state: Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let job_id = Uuid::parse_str(&path.into_inner()).map_err(ErrorBadRequest)?;
+    match state.deletions.get(&job_id) {
+        Some(job) => Ok(HttpResponse::Ok().json(job)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
@@ -1,4 +1,6 @@
+pub mod export_graph;
 pub mod fetch_node;
 pub mod health_check;
 pub mod ingest;
+pub mod ingestions;
 pub mod traverse_node;
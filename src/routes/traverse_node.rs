@@ -1,4 +1,7 @@
-use crate::{application::AppState, domain::node::TraversalNode};
+use crate::{
+    application::AppState,
+    domain::{node::TraversalNode, relation::TagFilter},
+};
 use actix_web::{
     error::ErrorInternalServerError,
     get,
@@ -8,6 +11,7 @@ use actix_web::{
 use eyre::{eyre, Result};
 use futures::{future::BoxFuture, FutureExt};
 use serde::{Deserialize, Serialize};
+use url::form_urlencoded::byte_serialize;
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Clone, Deserialize, Default)]
@@ -15,6 +19,7 @@ pub struct TraversalNodeQuery {
     pub direction: String,
     pub relation_type: Option<String>,
     pub max_depth: usize,
+    pub relation_tag: Option<String>,
 }
 
 impl TraversalNodeQuery {
@@ -37,15 +42,31 @@ impl TraversalNodeQuery {
             }
         }
 
+        if let Some(relation_tag) = &self.relation_tag {
+            TagFilter::parse(relation_tag)?;
+        }
+
         Ok(())
     }
 
     pub fn convert_to_query_parameter(&self) -> String {
         let relation_type = self.relation_type.as_deref().unwrap_or("");
-        format!(
+        let mut query = format!(
             "direction={}&relation_type={}&max_depth={}",
             self.direction, relation_type, self.max_depth
-        )
+        );
+        if let Some(relation_tag) = &self.relation_tag {
+            let relation_tag: String = byte_serialize(relation_tag.as_bytes()).collect();
+            query.push_str(&format!("&relation_tag={}", relation_tag));
+        }
+        query
+    }
+
+    fn tag_filter(&self) -> Result<Option<TagFilter>> {
+        self.relation_tag
+            .as_deref()
+            .map(TagFilter::parse)
+            .transpose()
     }
 }
 
@@ -98,20 +119,20 @@ fn traverse_node_by_id_internal<'a>(
     async move {
         let direction = query.direction.clone();
         let relation_type = query.relation_type.clone();
-        let result = state
+        let rows = state
             .db
             .get_node_traversal(node_id, direction, relation_type)
             .await?;
-        match result {
-            Some(relation) => {
-                let mut node = TraversalNode::from(relation, query.max_depth);
-                if depth < query.max_depth && node.relation_ids.len() > 0 {
+        let filter = query.tag_filter()?;
+        match TraversalNode::from_rows(rows, depth, filter.as_ref()) {
+            Some(mut node) => {
+                if depth < query.max_depth && !node.relation_ids.is_empty() {
                     let mut handles = vec![];
                     for relation_id in node.relation_ids.iter() {
                         let query = query.clone();
                         let state = state.clone();
                         let depth = depth + 1;
-                        let node_id = Uuid::parse_str(&relation_id).unwrap();
+                        let node_id = Uuid::parse_str(relation_id)?;
                         let handle = tokio::spawn(async move {
                             traverse_node_by_id_internal(node_id, query, state, depth).await
                         });
@@ -143,6 +164,7 @@ mod tests {
             direction: String::new(),
             relation_type: None,
             max_depth: 4,
+            relation_tag: None,
         };
         let result = query.validate();
         assert!(result.is_err());
@@ -160,6 +182,7 @@ mod tests {
             direction: "invalid".to_string(),
             relation_type: None,
             max_depth: 4,
+            relation_tag: None,
         };
         assert_eq!(
             query.validate().unwrap_err().to_string(),
@@ -173,6 +196,7 @@ mod tests {
             direction: "in".to_string(),
             relation_type: None,
             max_depth: 4,
+            relation_tag: None,
         };
         assert!(query.validate().is_ok());
     }
@@ -183,6 +207,7 @@ mod tests {
             direction: "out".to_string(),
             relation_type: Some("invalid".to_string()),
             max_depth: 4,
+            relation_tag: None,
         };
         assert_eq!(
             query.validate().unwrap_err().to_string(),
@@ -196,7 +221,28 @@ mod tests {
             direction: "out".to_string(),
             relation_type: Some("parent".to_string()),
             max_depth: 4,
+            relation_tag: None,
+        };
+        assert!(query.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_relation_tag() {
+        let mut query = TraversalNodeQuery {
+            direction: "out".to_string(),
+            relation_type: None,
+            max_depth: 4,
+            relation_tag: Some("confidence:high".to_string()),
         };
         assert!(query.validate().is_ok());
+        assert!(query
+            .convert_to_query_parameter()
+            .ends_with("&relation_tag=confidence%3Ahigh"));
+        query.relation_tag = Some("team:a&b c".to_string());
+        assert!(query
+            .convert_to_query_parameter()
+            .ends_with("&relation_tag=team%3Aa%26b+c"));
+        query.relation_tag = Some(":high".to_string());
+        assert!(query.validate().is_err());
     }
 }
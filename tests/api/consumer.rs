@@ -0,0 +1,106 @@
+use crate::api::helpers::{spawn_app, spawn_app_with};
+use rustfastingest::{
+    config::config::GeneralConfig,
+    domain::{
+        consumer::{message_job_id, Consumer},
+        job::{FailureKind, JobState},
+    },
+    queue::{memory::MemoryQueue, redis_streams::RedisStreamQueue, MessageQueue},
+    routes::ingest::IngestionRequest,
+};
+use std::{sync::Arc, time::Duration};
+
+fn example_file() -> String {
+    let example = std::env::current_dir()
+        .unwrap()
+        .join("tests/data/example.json");
+    format!("file://{}", example.display())
+}
+
+#[actix_rt::test]
+async fn test_consume_memory_queue() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let queue = Arc::new(MemoryQueue::new("test-requests"));
+    let consumer = Consumer::new(queue.clone(), 3, Duration::from_millis(100));
+
+    let request = IngestionRequest::new(vec![example_file()], "test_ingestion_queue".to_string());
+    let valid = queue
+        .publish(&serde_json::to_vec(&request).unwrap())
+        .await
+        .unwrap();
+    queue.publish(b"not a request").await.unwrap();
+    let missing = IngestionRequest::new(
+        vec!["file:///does/not/exist.json".to_string()],
+        "test_ingestion_queue_missing".to_string(),
+    );
+    let missing = queue
+        .publish(&serde_json::to_vec(&missing).unwrap())
+        .await
+        .unwrap();
+
+    let handled = consumer.run_once(&app.state).await.unwrap();
+    assert_eq!(handled, 3);
+    consumer.idle().await;
+    assert_eq!(queue.acked(), 3);
+    assert_eq!(queue.pending(), 0);
+
+    let job = app
+        .state
+        .jobs
+        .get(&message_job_id("test-requests", &valid))
+        .unwrap();
+    assert_eq!(job.state, JobState::Succeeded);
+    assert!(job.files[0].counts.rows_written > 0);
+
+    // Redelivery cannot fix a missing file, so it was acked as well.
+    let job = app
+        .state
+        .jobs
+        .get(&message_job_id("test-requests", &missing))
+        .unwrap();
+    assert_eq!(job.state, JobState::Failed);
+    assert_eq!(job.files[0].failure, Some(FailureKind::Input));
+
+    assert_eq!(consumer.run_once(&app.state).await.unwrap(), 0);
+}
+
+#[actix_rt::test]
+#[ignore = "needs Redis at QUEUE_URL"]
+async fn test_consume_redis_stream() {
+    let configuration = GeneralConfig::from_env().unwrap();
+    let url = configuration
+        .queue
+        .queue_url
+        .clone()
+        .expect("QUEUE_URL is not set");
+    let app = spawn_app_with(configuration)
+        .await
+        .expect("test app initialization failed!");
+    let stream = format!("test-requests-{}", uuid::Uuid::new_v4());
+    let queue = Arc::new(
+        RedisStreamQueue::connect(
+            &url,
+            &stream,
+            "test",
+            "consumer-1",
+            Duration::from_secs(60),
+            3,
+        )
+        .await
+        .unwrap(),
+    );
+    let request = IngestionRequest::new(vec![example_file()], "test_ingestion_redis".to_string());
+    let id = queue
+        .publish(&serde_json::to_vec(&request).unwrap())
+        .await
+        .unwrap();
+
+    let consumer = Consumer::new(queue.clone(), 1, Duration::from_millis(500));
+    assert_eq!(consumer.run_once(&app.state).await.unwrap(), 1);
+    consumer.idle().await;
+    let job = app.state.jobs.get(&message_job_id(&stream, &id)).unwrap();
+    assert_eq!(job.state, JobState::Succeeded);
+
+    // Acked: nothing is pending or left to read.
+    assert_eq!(consumer.run_once(&app.state).await.unwrap(), 0);
+}
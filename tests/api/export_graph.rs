@@ -0,0 +1,60 @@
+use crate::api::helpers::spawn_app;
+use reqwest::Client;
+use rustfastingest::{
+    db::model::path_to_uuid,
+    domain::{job::Job, s3::graphml::read_graphml},
+    routes::ingest::IngestionRequest,
+};
+
+#[actix_rt::test]
+async fn test_ingest_and_export_graphml() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let file = std::env::current_dir()
+        .unwrap()
+        .join("tests/data/example.graphml");
+    let ingestion_id = "test_ingestion_graphml".to_string();
+    let payload = IngestionRequest::new(
+        vec![format!("file://{}", file.display())],
+        ingestion_id.clone(),
+    );
+
+    let job = client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Job>()
+        .await
+        .expect("failed to get payload");
+    assert_eq!(job.files[0].counts.nodes_parsed, 3);
+
+    let root = path_to_uuid(&ingestion_id, "/root");
+    let response = client
+        .get(format!("{}/nodes/{}/graphml", &app.address, root))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let document = response.text().await.expect("failed to get payload");
+    let child = path_to_uuid(&ingestion_id, "/root/children1");
+    assert!(document.contains(&child.to_string()));
+    let exported = read_graphml(document.as_bytes()).expect("invalid GraphML");
+    assert_eq!(exported.node_count(), 3);
+}
+
+#[actix_rt::test]
+async fn test_export_graphml_unknown_node() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let node_id = "550e8400-e29b-41d4-a716-446655440000";
+
+    let response = client
+        .get(format!("{}/nodes/{}/graphml", &app.address, node_id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+}
@@ -3,7 +3,8 @@ use crate::{
     db::helpers::{create_test_nodes, get_random_node},
 };
 use reqwest::Client;
-use rustfastingest::domain::node::Node;
+use rustfastingest::{db::model::NodeModel, domain::node::Node};
+use uuid::Uuid;
 
 #[actix_rt::test]
 async fn test_get_node_by_id() {
@@ -13,14 +14,14 @@ async fn test_get_node_by_id() {
     let nodes = create_test_nodes(10);
     let node = get_random_node(&nodes).unwrap();
     let node_id = node.uuid.to_owned();
-    let _ = app
+    app
         .db
         .insert_nodes(nodes)
         .await
         .expect("insert nodes failed");
 
     let initial_response = client
-        .get(&format!("{}/nodes/{}?{}", &app.address, node_id, query))
+        .get(format!("{}/nodes/{}?{}", &app.address, node_id, query))
         .send()
         .await
         .expect("Failed to execute request.");
@@ -43,7 +44,7 @@ async fn test_get_node_by_id_non_exist() {
     let query = "tags=true&relations=true";
 
     let response = client
-        .get(&format!("{}/nodes/{}?{}", &app.address, node_id, query))
+        .get(format!("{}/nodes/{}?{}", &app.address, node_id, query))
         .send()
         .await
         .expect("Failed to execute request.");
@@ -71,7 +72,7 @@ async fn test_get_node_by_id_query_parameters() {
 
     for (query_params, description) in test_cases {
         let response = client
-            .get(&format!(
+            .get(format!(
                 "{}/nodes/{}?{}",
                 &app.address, node_id, query_params
             ))
@@ -88,7 +89,7 @@ async fn test_get_node_by_id_handler_exist() {
     let client = Client::new();
     let node_id = 123;
     let response = client
-        .get(&format!("{}/nodes/{}", &app.address, node_id))
+        .get(format!("{}/nodes/{}", &app.address, node_id))
         .send()
         .await
         .expect("Failed to execute request.");
@@ -97,3 +98,61 @@ async fn test_get_node_by_id_handler_exist() {
         reqwest::StatusCode::INTERNAL_SERVER_ERROR
     );
 }
+
+#[actix_rt::test]
+async fn test_get_node_relation_tags() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let node_id = Uuid::new_v4();
+    let edge = |relates_to: Uuid, confidence: &str| NodeModel {
+        uuid: node_id,
+        direction: Some("Out".to_string()),
+        relation: Some("depends_on".to_string()),
+        relates_to: Some(relates_to.to_string()),
+        name: "target".to_string(),
+        ingestion_id: "relation_tags".to_string(),
+        tags: Some(vec![("confidence".to_string(), confidence.to_string())]),
+        ..NodeModel::default()
+    };
+    let nodes = vec![
+        NodeModel {
+            uuid: node_id,
+            name: "source".to_string(),
+            ingestion_id: "relation_tags".to_string(),
+            path: "/source".to_string(),
+            node_type: "service".to_string(),
+            ..NodeModel::default()
+        },
+        edge(Uuid::new_v4(), "high"),
+        edge(Uuid::new_v4(), "low"),
+    ];
+    app.db.insert_nodes(nodes).await.expect("insert nodes failed");
+
+    let node = client
+        .get(format!("{}/nodes/{}", &app.address, node_id))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Node>()
+        .await
+        .expect("failed to get payload");
+    assert_eq!(node.name, "source");
+    assert_eq!(node.relations.len(), 2);
+
+    let node = client
+        .get(format!(
+            "{}/nodes/{}?relation_tag=confidence:high",
+            &app.address, node_id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Node>()
+        .await
+        .expect("failed to get payload");
+    assert_eq!(node.relations.len(), 1);
+    assert_eq!(
+        node.relations[0].tags,
+        vec![("confidence".to_string(), "high".to_string())]
+    );
+}
@@ -6,7 +6,7 @@ async fn test_health_check() {
     let app = spawn_app().await.expect("test app initialization failed!");
     let client = Client::new();
     let response = client
-        .get(&format!("{}/healthcheck", &app.address))
+        .get(format!("{}/healthcheck", &app.address))
         .send()
         .await
         .expect("Failed to execute request.");
@@ -1,21 +1,35 @@
+use actix_web::web::Data;
 use rustfastingest::{
-    application::Application, config::config::GeneralConfig, db::syclla::ScyllaService,
+    application::{AppState, Application},
+    config::config::GeneralConfig,
+    db::syclla::ScyllaService,
     routes::traverse_node::TraversalNodeQuery,
 };
 
 pub struct TestApp {
     pub address: String,
     pub db: ScyllaService,
+    pub state: Data<AppState>,
 }
 
 pub async fn spawn_app() -> eyre::Result<TestApp> {
-    let mut configuration = GeneralConfig::from_env()?;
+    spawn_app_with(GeneralConfig::from_env()?).await
+}
+
+pub async fn spawn_app_with(mut configuration: GeneralConfig) -> eyre::Result<TestApp> {
     configuration.app.port = 0;
+    // Tests ingest the checked in data and files written to temp directories.
+    configuration.source.source_file_roots = format!(
+        "{},{}",
+        std::env::current_dir()?.join("tests/data").display(),
+        std::env::temp_dir().display()
+    );
     let application = Application::build(configuration.clone())
         .await
         .expect("Failed to build application");
     let address = format!("http://localhost:{}", application.port());
-    let _ = tokio::spawn(application.run());
+    let state = application.state();
+    drop(tokio::spawn(application.run()));
 
     let service = ScyllaService::init(&configuration.db)
         .await
@@ -24,6 +38,7 @@ pub async fn spawn_app() -> eyre::Result<TestApp> {
     Ok(TestApp {
         address,
         db: service,
+        state,
     })
 }
 
@@ -32,7 +47,8 @@ pub fn create_traversal_query() -> String {
         direction: "In".to_string(),
         relation_type: Some("Child".to_string()),
         max_depth: 2,
+        relation_tag: None,
     };
-    let query = traversal_node_query.convert_to_query_parameter();
-    query
+
+    traversal_node_query.convert_to_query_parameter()
 }
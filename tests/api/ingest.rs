@@ -1,6 +1,21 @@
-use crate::api::helpers::spawn_app;
+use crate::api::helpers::{spawn_app, spawn_app_with};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
 use reqwest::Client;
-use rustfastingest::routes::ingest::IngestionRequest;
+use rustfastingest::{
+    config::config::GeneralConfig,
+    db::model::path_to_uuid,
+    domain::{
+        job::{FailureKind, FileState, Job, JobState},
+        node::Node,
+        preview::{Preview, PreviewRequest},
+        s3::data::GraphData,
+        validation::{IssueKind, Strictness},
+        webhook::{sign, signed_payload, SIGNATURE_HEADER, TIMESTAMP_HEADER},
+    },
+    routes::ingest::{IngestionAccepted, IngestionRequest},
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::mpsc;
 
 #[actix_rt::test]
 async fn test_ingest() {
@@ -12,7 +27,7 @@ async fn test_ingest() {
     let start = std::time::Instant::now();
 
     let response = client
-        .post(&format!("{}/ingest", &app.address))
+        .post(format!("{}/ingest", &app.address))
         .json(&payload)
         .send()
         .await
@@ -22,13 +37,698 @@ async fn test_ingest() {
     println!("Ingest request took: {:?}", duration);
     println!("{:?}", response);
 
-    assert!(response.status().is_success());
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+}
+
+#[actix_rt::test]
+async fn test_ingest_job_status() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let test_files = create_test_files(2);
+    let payload = IngestionRequest::new(test_files, "test_ingestion_job".to_string());
+
+    let accepted = client
+        .post(format!("{}/ingest", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<IngestionAccepted>()
+        .await
+        .expect("failed to get payload");
+
+    let mut job = None;
+    for _ in 0..50 {
+        let status = client
+            .get(format!("{}{}", &app.address, accepted.status_url))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .json::<Job>()
+            .await
+            .expect("failed to get payload");
+        if matches!(
+            status.state,
+            JobState::Succeeded | JobState::Partial | JobState::Failed
+        ) {
+            job = Some(status);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    let job = job.expect("ingestion job did not finish");
+    assert_eq!(job.job_id, accepted.job_id);
+    assert_eq!(job.files.len(), 2);
+    assert_eq!(job.state, JobState::Succeeded);
+}
+
+#[actix_rt::test]
+async fn test_ingest_wait_reports_per_file_results() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let mut test_files = create_test_files(1);
+    test_files.push("file:///does/not/exist.json".to_string());
+    let payload = IngestionRequest::new(test_files, "test_ingestion_wait".to_string());
+
+    let response = client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::MULTI_STATUS);
+
+    let job = response.json::<Job>().await.expect("failed to get payload");
+    assert_eq!(job.state, JobState::Partial);
+    assert_eq!(job.files[0].state, FileState::Succeeded);
+    assert_eq!(job.files[0].counts.nodes_parsed, 18);
+    assert!(job.files[0].counts.rows_written > 0);
+    assert_eq!(job.files[0].counts.rows_failed, 0);
+    assert_eq!(job.files[1].state, FileState::Failed);
+    assert_eq!(job.files[1].failure, Some(FailureKind::Input));
+}
+
+#[actix_rt::test]
+async fn test_ingest_streaming() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let payload = IngestionRequest::new(create_test_files(1), "test_ingestion_stream".to_string())
+        .with_streaming(true);
+
+    let response = client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let job = response.json::<Job>().await.expect("failed to get payload");
+    assert_eq!(job.files[0].state, FileState::Succeeded);
+    assert_eq!(job.files[0].counts.nodes_parsed, 18);
+    assert_eq!(job.files[0].counts.relations_parsed, 3);
+    assert!(job.files[0].counts.rows_written > 0);
+}
+
+#[actix_rt::test]
+async fn test_ingest_ndjson() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let example = std::env::current_dir()
+        .unwrap()
+        .join("tests/data/example.ndjson");
+    let files = vec![format!("file://{}", example.display())];
+    let payload = IngestionRequest::new(files, "test_ingestion_ndjson".to_string());
+
+    let response = client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let job = response.json::<Job>().await.expect("failed to get payload");
+    assert_eq!(job.files[0].counts.nodes_parsed, 4);
+    assert_eq!(job.files[0].counts.relations_parsed, 2);
+}
+
+#[actix_rt::test]
+async fn test_ingest_continuation() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let data_dir = std::env::current_dir().unwrap().join("tests/data");
+    let files = vec![
+        format!(
+            "file://{}",
+            data_dir.join("example_truncated.json").display()
+        ),
+        format!(
+            "file://{}",
+            data_dir.join("example_continuation.json").display()
+        ),
+    ];
+    let ingestion_id = "test_ingestion_continuation".to_string();
+    let payload = IngestionRequest::new(files, ingestion_id.clone());
+
+    let response = client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let job = response.json::<Job>().await.expect("failed to get payload");
+    assert_eq!(job.state, JobState::Succeeded);
+
+    let root = path_to_uuid(&ingestion_id, "/root");
+    let node = client
+        .get(format!("{}/nodes/{}?relations=true", &app.address, root))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Node>()
+        .await
+        .expect("failed to get payload");
+    assert_eq!(node.total_children, Some(3));
+    let children = node
+        .relations
+        .iter()
+        .filter(|relation| relation.rel_type == "Child")
+        .count();
+    assert_eq!(children, 3);
+}
+
+#[actix_rt::test]
+async fn test_ingest_csv() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let data_dir = std::env::current_dir().unwrap().join("tests/data");
+    let files = vec![
+        format!("file://{}", data_dir.join("example_nodes.csv").display()),
+        format!("file://{}", data_dir.join("example_edges.csv").display()),
+    ];
+    let payload = IngestionRequest::new(files, "test_ingestion_csv".to_string());
+
+    let job = client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload.clone().with_streaming(true))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Job>()
+        .await
+        .expect("failed to get payload");
+    assert_eq!(job.files[0].state, FileState::Failed);
+    assert_eq!(job.files[0].failure, Some(FailureKind::Input));
+
+    let response = client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let job = response.json::<Job>().await.expect("failed to get payload");
+    assert_eq!(job.files[0].counts.nodes_parsed, 3);
+    assert_eq!(job.files[1].counts.relations_parsed, 1);
+    assert_eq!(job.files[1].counts.rows_written, 2);
+}
+
+#[actix_rt::test]
+async fn test_ingest_compressed() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let file = std::env::current_dir()
+        .unwrap()
+        .join("tests/data/example.json.gz");
+    let files = vec![format!("file://{}", file.display())];
+    let payload = IngestionRequest::new(files.clone(), "test_ingestion_gzip".to_string());
+
+    for payload in [payload.clone(), payload.with_streaming(true)] {
+        let job = client
+            .post(format!("{}/ingest?wait=true", &app.address))
+            .json(&payload)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .json::<Job>()
+            .await
+            .expect("failed to get payload");
+        assert_eq!(job.files[0].state, FileState::Succeeded);
+        assert_eq!(job.files[0].counts.nodes_parsed, 18);
+    }
+}
+
+#[actix_rt::test]
+async fn test_ingest_preview() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let file = create_test_files(1).remove(0);
+    let payload = PreviewRequest::from_file(file, "test_ingestion_preview".to_string());
+
+    let response = client
+        .post(format!("{}/ingest/preview", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let preview = response
+        .json::<Preview>()
+        .await
+        .expect("failed to get payload");
+    assert_eq!(preview.uuids.len(), 18);
+    assert_eq!(preview.summary.rows, preview.rows.len());
+    let root = preview.uuids["/root"];
+    let stored = app
+        .db
+        .get_node(root, true, false)
+        .await
+        .expect("query failed");
+    assert!(stored.is_none());
+
+    let payload = PreviewRequest::from_data(GraphData::default(), "test".to_string());
+    let response = client
+        .post(format!("{}/ingest/preview", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let mut payload = PreviewRequest::from_data(GraphData::default(), "test".to_string());
+    payload.file = Some("graph.json".to_string());
+    let response = client
+        .post(format!("{}/ingest/preview", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn test_ingest_validation() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let file = std::env::current_dir()
+        .unwrap()
+        .join("tests/data/example_invalid.json");
+    let files = vec![format!("file://{}", file.display())];
+    let payload = IngestionRequest::new(files, "test_ingestion_invalid".to_string());
+
+    let response = client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload.clone().with_validation(Strictness::Reject))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let job = response.json::<Job>().await.expect("failed to get payload");
+    assert_eq!(job.files[0].counts.issues_found, 2);
+    assert_eq!(job.files[0].counts.rows_written, 0);
+
+    let job = client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload.with_validation(Strictness::Skip))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Job>()
+        .await
+        .expect("failed to get payload");
+    assert_eq!(job.files[0].state, FileState::Succeeded);
+    assert_eq!(job.files[0].counts.records_skipped, 2);
+    let kinds: Vec<IssueKind> = job.files[0].counts.issues.iter().map(|i| i.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![IssueKind::DuplicateSibling, IssueKind::DanglingRelation]
+    );
+    assert_eq!(job.files[0].counts.issues[1].path, "/root/missing");
+
+    let streaming = IngestionRequest::new(
+        vec![job.files[0].file.clone()],
+        "test_ingestion_invalid".to_string(),
+    )
+    .with_streaming(true)
+    .with_validation(Strictness::Reject);
+    let response = client
+        .post(format!("{}/ingest", &app.address))
+        .json(&streaming)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn test_ingest_incremental() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let example = std::env::current_dir()
+        .unwrap()
+        .join("tests/data/example.json");
+    let ingestion_id = format!("test_incremental_{}", uuid::Uuid::new_v4());
+    let payload = IngestionRequest::new(
+        vec![format!("file://{}", example.display())],
+        ingestion_id.clone(),
+    );
+    client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    let job = client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload.clone().with_incremental(true))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Job>()
+        .await
+        .expect("failed to get payload");
+    let diff = job.diff.expect("incremental job has no diff");
+    assert_eq!(job.files[0].counts.rows_written, 0);
+    assert_eq!(
+        diff.rows_inserted + diff.rows_updated + diff.rows_deleted,
+        0
+    );
+    assert!(diff.rows_unchanged > 0);
+
+    // Drop the first subtree of the root and retype the root itself.
+    let mut data: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&example).unwrap()).unwrap();
+    let root = &mut data["nodes"][0];
+    root["type"] = "changed_type".into();
+    root["children"].as_array_mut().unwrap().remove(0);
+    let file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+    std::fs::write(file.path(), data.to_string()).unwrap();
+
+    let payload = IngestionRequest::new(
+        vec![format!("file://{}", file.path().display())],
+        ingestion_id.clone(),
+    )
+    .with_incremental(true);
+    let job = client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Job>()
+        .await
+        .expect("failed to get payload");
+    assert_eq!(job.state, JobState::Succeeded);
+    let diff = job.diff.expect("incremental job has no diff");
+    assert_eq!(diff.nodes_added, 0);
+    assert_eq!(diff.nodes_updated, 1);
+    assert!(diff.nodes_removed > 0);
+    assert!(diff.rows_deleted > 0);
+    assert!(!diff.deletions_skipped);
+}
+
+#[actix_rt::test]
+async fn test_ingest_resume() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let payload = IngestionRequest::new(create_test_files(2), "test_resume".to_string());
+    let job = client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Job>()
+        .await
+        .expect("failed to get payload");
+    assert_eq!(job.state, JobState::Succeeded);
+
+    let resumed = client
+        .post(format!(
+            "{}/ingest/{}/resume?wait=true",
+            &app.address, job.job_id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Job>()
+        .await
+        .expect("failed to get payload");
+    assert_eq!(resumed.job_id, job.job_id);
+    assert_eq!(resumed.state, JobState::Succeeded);
+    for (file, previous) in resumed.files.iter().zip(job.files.iter()) {
+        assert_eq!(file.counts.rows_written, previous.counts.rows_written);
+    }
+
+    let response = client
+        .post(format!(
+            "{}/ingest/{}/resume",
+            &app.address,
+            uuid::Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_ingest_events() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let payload = IngestionRequest::new(create_test_files(2), "test_events".to_string());
+    let accepted = client
+        .post(format!("{}/ingest", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<IngestionAccepted>()
+        .await
+        .expect("failed to get payload");
+
+    let response = client
+        .get(format!(
+            "{}/ingest/{}/events",
+            &app.address, accepted.job_id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(
+        response.headers()["content-type"].to_str().unwrap(),
+        "text/event-stream"
+    );
+    let body = response.text().await.expect("failed to read events");
+    let events: Vec<&str> = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("event: "))
+        .collect();
+    assert_eq!(events.first(), Some(&"snapshot"));
+    assert_eq!(events.last(), Some(&"job_finished"));
+
+    let response = client
+        .get(format!(
+            "{}/ingest/{}/events",
+            &app.address,
+            uuid::Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_ingest_upload() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let example = std::fs::read(
+        std::env::current_dir()
+            .unwrap()
+            .join("tests/data/example.json"),
+    )
+    .unwrap();
+
+    let mut body = Vec::new();
+    body.extend_from_slice(
+        b"--boundary\r\nContent-Disposition: form-data; name=\"ingestion_id\"\r\n\r\n\
+          test_ingestion_upload\r\n",
+    );
+    for name in ["a.json", "b.json"] {
+        body.extend_from_slice(
+            format!(
+                "--boundary\r\nContent-Disposition: form-data; name=\"files\"; \
+                 filename=\"{}\"\r\nContent-Type: application/json\r\n\r\n",
+                name
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(&example);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(b"--boundary--\r\n");
+
+    let response = client
+        .post(format!("{}/ingest/upload?wait=true", &app.address))
+        .header("Content-Type", "multipart/form-data; boundary=boundary")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let job = response.json::<Job>().await.expect("failed to get payload");
+    assert_eq!(job.ingestion_id, "test_ingestion_upload");
+    assert_eq!(job.files.len(), 2);
+    assert!(job
+        .files
+        .iter()
+        .all(|file| file.state == FileState::Succeeded && file.counts.nodes_parsed == 18));
+
+    let response = client
+        .post(format!("{}/ingest/upload", &app.address))
+        .header("Content-Type", "multipart/form-data; boundary=boundary")
+        .body("--boundary--\r\n")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn test_ingest_upload_limit() {
+    let mut configuration = GeneralConfig::from_env().unwrap();
+    configuration.upload.upload_max_bytes = 64;
+    let app = spawn_app_with(configuration)
+        .await
+        .expect("test app initialization failed!");
+    let client = Client::new();
+    let body = "--boundary\r\nContent-Disposition: form-data; name=\"files\"; \
+                filename=\"a.json\"\r\n\r\n{\"nodes\": [], \"relations\": [], \
+                \"padding\": \"0123456789012345678901234567890123456789\"}\r\n\
+                --boundary--\r\n";
+
+    let response = client
+        .post(format!("{}/ingest/upload", &app.address))
+        .header("Content-Type", "multipart/form-data; boundary=boundary")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[actix_rt::test]
+async fn test_ingest_callback() {
+    let mut configuration = GeneralConfig::from_env().unwrap();
+    configuration.webhook.webhook_secret = Some("test-secret".to_string());
+    configuration.webhook.webhook_hosts = "127.0.0.1".to_string();
+    configuration.webhook.webhook_base_delay_ms = 10;
+    let app = spawn_app_with(configuration)
+        .await
+        .expect("test app initialization failed!");
+    let client = Client::new();
+
+    // Fails the first delivery so the callback has to be retried.
+    let (sender, mut deliveries) =
+        mpsc::unbounded_channel::<(Option<String>, Option<i64>, web::Bytes)>();
+    let attempts = web::Data::new(AtomicUsize::new(0));
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let receiver = HttpServer::new(move || {
+        let sender = sender.clone();
+        App::new().app_data(attempts.clone()).route(
+            "/callback",
+            web::post().to(
+                move |request: HttpRequest, body: web::Bytes, attempts: web::Data<AtomicUsize>| {
+                    let sender = sender.clone();
+                    async move {
+                        if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                            return HttpResponse::ServiceUnavailable().finish();
+                        }
+                        let signature = request
+                            .headers()
+                            .get(SIGNATURE_HEADER)
+                            .and_then(|value| value.to_str().ok())
+                            .map(str::to_owned);
+                        let timestamp = request
+                            .headers()
+                            .get(TIMESTAMP_HEADER)
+                            .and_then(|value| value.to_str().ok())
+                            .and_then(|value| value.parse().ok());
+                        let _ = sender.send((signature, timestamp, body));
+                        HttpResponse::Ok().finish()
+                    }
+                },
+            ),
+        )
+    })
+    .listen(listener)
+    .unwrap()
+    .run();
+    drop(tokio::spawn(receiver));
+
+    let payload =
+        IngestionRequest::new(create_test_files(1), "test_ingestion_callback".to_string())
+            .with_callback_url(format!("http://127.0.0.1:{}/callback", port));
+    let accepted = client
+        .post(format!("{}/ingest", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<IngestionAccepted>()
+        .await
+        .expect("failed to get payload");
+
+    let (signature, timestamp, body) =
+        tokio::time::timeout(std::time::Duration::from_secs(30), deliveries.recv())
+            .await
+            .expect("callback was not delivered")
+            .unwrap();
+    let timestamp = timestamp.expect("callback carries no timestamp");
+    assert_eq!(
+        signature,
+        Some(sign("test-secret", &signed_payload(timestamp, &body)))
+    );
+    let job: Job = serde_json::from_slice(&body).unwrap();
+    assert_eq!(job.job_id, accepted.job_id);
+    assert_eq!(job.ingestion_id, "test_ingestion_callback");
+    assert_eq!(job.state, JobState::Succeeded);
+    assert_eq!(job.files[0].counts.nodes_parsed, 18);
+
+    let payload =
+        IngestionRequest::new(create_test_files(1), "test_ingestion_callback".to_string())
+            .with_callback_url("ftp://example.com".to_string());
+    let response = client
+        .post(format!("{}/ingest", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // Hosts outside WEBHOOK_HOSTS are refused.
+    let payload =
+        IngestionRequest::new(create_test_files(1), "test_ingestion_callback".to_string())
+            .with_callback_url("http://169.254.169.254/latest/meta-data".to_string());
+    let response = client
+        .post(format!("{}/ingest", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn test_ingest_job_status_unknown() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let job_id = "550e8400-e29b-41d4-a716-446655440000";
+
+    let response = client
+        .get(format!("{}/ingest/{}", &app.address, job_id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
 }
 
 fn create_test_files(count: usize) -> Vec<String> {
+    let example = std::env::current_dir()
+        .unwrap()
+        .join("tests/data/example.json");
     let mut test_files = Vec::new();
-    for i in 0..count {
-        test_files.push(format!("test_files/test_file_{}.txt", i));
+    for _ in 0..count {
+        test_files.push(format!("file://{}", example.display()));
     }
     test_files
 }
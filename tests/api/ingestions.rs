@@ -0,0 +1,276 @@
+use crate::{
+    api::helpers::{spawn_app, spawn_app_with},
+    db::helpers::create_sample_node,
+};
+use reqwest::Client;
+use rustfastingest::{
+    config::config::GeneralConfig,
+    db::{
+        dead_letter::{append_file, read_file, FailedRow},
+        model::path_to_uuid,
+    },
+    domain::{
+        deletion::DeletionJob,
+        ingestion::Ingestion,
+        job::JobState,
+        path::{legacy, PathKind},
+    },
+    routes::{
+        ingest::IngestionRequest,
+        ingestions::{DeletionAccepted, PathMigration, PathMigrationRequest, ReplayReport},
+    },
+};
+
+#[actix_rt::test]
+async fn test_delete_ingestion() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let file = std::env::current_dir()
+        .unwrap()
+        .join("tests/data/example.json");
+    let ingestion_id = format!("test_delete_{}", uuid::Uuid::new_v4());
+    let payload = IngestionRequest::new(
+        vec![format!("file://{}", file.display())],
+        ingestion_id.clone(),
+    );
+    client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let root = path_to_uuid(&ingestion_id, "/root");
+    assert!(!app.db.get_node_rows(root).await.unwrap().is_empty());
+
+    let response = client
+        .delete(format!("{}/ingestions/{}", &app.address, ingestion_id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    let accepted = response
+        .json::<DeletionAccepted>()
+        .await
+        .expect("failed to get payload");
+
+    let mut job = None;
+    for _ in 0..50 {
+        let status = client
+            .get(format!("{}{}", &app.address, accepted.status_url))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .json::<DeletionJob>()
+            .await
+            .expect("failed to get payload");
+        if status.finished_at.is_some() {
+            job = Some(status);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let job = job.expect("deletion did not finish");
+    assert_eq!(job.state, JobState::Succeeded);
+    assert!(job.nodes_found >= 18);
+    assert_eq!(job.nodes_deleted, job.nodes_found);
+    assert!(app.db.get_node_rows(root).await.unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn test_get_ingestion() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let client = Client::new();
+    let file = std::env::current_dir()
+        .unwrap()
+        .join("tests/data/example.json");
+    let ingestion_id = format!("test_registry_{}", uuid::Uuid::new_v4());
+    let payload = IngestionRequest::new(
+        vec![format!("file://{}", file.display())],
+        ingestion_id.clone(),
+    );
+    client
+        .post(format!("{}/ingest?wait=true", &app.address))
+        .json(&payload)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    let ingestion = client
+        .get(format!("{}/ingestions/{}", &app.address, ingestion_id))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Ingestion>()
+        .await
+        .expect("failed to get payload");
+    assert_eq!(ingestion.status, JobState::Succeeded);
+    assert_eq!(ingestion.files, payload.files);
+    assert!(ingestion.finished_at.is_some());
+    assert!(ingestion.rows_written > 0);
+
+    let ingestions = client
+        .get(format!("{}/ingestions", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<Ingestion>>()
+        .await
+        .expect("failed to get payload");
+    assert!(ingestions.iter().any(|i| i.ingestion_id == ingestion_id));
+
+    let response = client
+        .get(format!(
+            "{}/ingestions/missing_{}",
+            &app.address, ingestion_id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn test_path_migration() {
+    let app = spawn_app().await.expect("test app initialization failed!");
+    let ingestion_id = format!("test_migration_{}", uuid::Uuid::new_v4());
+    let data = serde_json::json!({
+        "nodes": [{
+            "name": "root",
+            "type": "t",
+            "children": [
+                {"name": "a/b", "type": "t", "children": []},
+                {"name": "c", "type": "t", "children": []}
+            ]
+        }],
+        "relations": [
+            {"type": "uses", "tags": [], "source": ["root", "c"], "target": ["root", "a/b"]}
+        ]
+    });
+    let file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+    std::fs::write(file.path(), data.to_string()).unwrap();
+
+    // Rows as an earlier version wrote them: raw names joined by '/', with a
+    // leading one for node paths but not for relation endpoints.
+    let legacy_node = legacy::node_path(&["root", "a/b"]);
+    let legacy_endpoint = legacy::relation_path(&["root", "c"]);
+    assert_eq!(
+        (legacy_node.as_str(), legacy_endpoint.as_str()),
+        ("/root/a/b", "root/c")
+    );
+    let rows = [&legacy_node, &legacy_endpoint]
+        .into_iter()
+        .map(|path| {
+            let mut node = create_sample_node();
+            node.uuid = path_to_uuid(&ingestion_id, path);
+            node.ingestion_id = ingestion_id.clone();
+            node.path = path.to_string();
+            node
+        })
+        .collect();
+    app.db
+        .insert_nodes(rows)
+        .await
+        .expect("insert nodes failed");
+
+    let request = PathMigrationRequest {
+        files: vec![format!("file://{}", file.path().display())],
+        format: None,
+    };
+    let migration = Client::new()
+        .post(format!(
+            "{}/ingestions/{}/path-migration",
+            &app.address, ingestion_id
+        ))
+        .json(&request)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<PathMigration>()
+        .await
+        .expect("failed to get payload");
+    assert_eq!(migration.files, request.files);
+    let changes: Vec<(PathKind, &str, uuid::Uuid, uuid::Uuid)> = migration
+        .changes
+        .iter()
+        .map(|change| {
+            (
+                change.kind,
+                change.path.as_str(),
+                change.old_uuid,
+                change.new_uuid,
+            )
+        })
+        .collect();
+    // The endpoint `root/a/b` moves too, but nothing is stored under it.
+    assert_eq!(
+        changes,
+        vec![
+            (
+                PathKind::Node,
+                "/root/a\\/b",
+                path_to_uuid(&ingestion_id, &legacy_node),
+                path_to_uuid(&ingestion_id, "/root/a\\/b"),
+            ),
+            (
+                PathKind::RelationEndpoint,
+                "/root/c",
+                path_to_uuid(&ingestion_id, &legacy_endpoint),
+                path_to_uuid(&ingestion_id, "/root/c"),
+            ),
+        ]
+    );
+
+    let response = Client::new()
+        .post(format!(
+            "{}/ingestions/{}/path-migration",
+            &app.address, ingestion_id
+        ))
+        .json(&PathMigrationRequest {
+            files: vec![],
+            format: None,
+        })
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn test_replay_failed_rows() {
+    let dir = tempfile::tempdir().unwrap();
+    let sink = dir.path().join("failed.ndjson");
+    let ingestion_id = format!("test_replay_{}", uuid::Uuid::new_v4());
+    let mut node = create_sample_node();
+    node.uuid = uuid::Uuid::new_v4();
+    node.ingestion_id = ingestion_id.clone();
+    let failed = FailedRow {
+        row: node.clone(),
+        error: "Timeout Error".to_string(),
+        attempts: 3,
+        failed_at: chrono::Utc::now(),
+    };
+    append_file(&sink, &Default::default(), &[failed])
+        .await
+        .unwrap();
+
+    let mut configuration = GeneralConfig::from_env().unwrap();
+    configuration.db.dead_letter = Some(sink.display().to_string());
+    let app = spawn_app_with(configuration)
+        .await
+        .expect("test app initialization failed!");
+    let report = Client::new()
+        .post(format!(
+            "{}/ingestions/{}/failed-rows/replay",
+            &app.address, ingestion_id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<ReplayReport>()
+        .await
+        .expect("failed to get payload");
+    assert_eq!(report.rows_written, 1);
+    assert_eq!(report.rows_failed, 0);
+    assert!(!app.db.get_node_rows(node.uuid).await.unwrap().is_empty());
+    assert!(read_file(&sink).await.unwrap().is_empty());
+}
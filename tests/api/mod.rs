@@ -1,5 +1,9 @@
+pub mod consumer;
+pub mod export_graph;
 pub mod fetch_node;
 pub mod healthcheck;
 pub mod helpers;
 pub mod ingest;
+pub mod ingestions;
 pub mod traverse_node;
+pub mod watcher;
@@ -14,14 +14,14 @@ async fn test_get_traversal_node() {
     let node = get_random_node(&nodes).unwrap();
     println!("traverse node = {:#?}", node);
     let node_id = node.uuid.to_owned();
-    let _ = app
+    app
         .db
         .insert_nodes(nodes)
         .await
         .expect("insert nodes failed");
 
     let initial_response = client
-        .get(&format!("{}/traversal/{}?{}", &app.address, node_id, query))
+        .get(format!("{}/traversal/{}?{}", &app.address, node_id, query))
         .send()
         .await
         .expect("Failed to execute request.");
@@ -43,7 +43,7 @@ async fn test_traversal_node_handler_nonexist_node() {
     let node_id = "550e8400-e29b-41d4-a716-446655440000";
     let query = create_traversal_query();
     let response = client
-        .get(&format!("{}/traversal/{}?{}", &app.address, node_id, query))
+        .get(format!("{}/traversal/{}?{}", &app.address, node_id, query))
         .send()
         .await
         .expect("Failed to execute request.");
@@ -58,7 +58,7 @@ async fn test_traversal_node_handler_query_parameters() {
     let test_cases = create_query_parameter_test_cases();
     for (name, query, expected_code) in test_cases {
         let response = client
-            .get(&format!(
+            .get(format!(
                 "{}/traversal/{}?{}",
                 &app.address,
                 node_id,
@@ -82,7 +82,7 @@ async fn test_traversal_node_handler_exist() {
     let client = Client::new();
     let node_id = "1";
     let response = client
-        .get(&format!("{}/traversal/{}", &app.address, node_id))
+        .get(format!("{}/traversal/{}", &app.address, node_id))
         .send()
         .await
         .expect("Failed to execute request.");
@@ -97,6 +97,7 @@ fn create_query_parameter_test_cases() -> Vec<(String, TraversalNodeQuery, u16)>
                 direction: String::new(),
                 relation_type: None,
                 max_depth: 0,
+                relation_tag: None,
             },
             500,
         ),
@@ -106,6 +107,7 @@ fn create_query_parameter_test_cases() -> Vec<(String, TraversalNodeQuery, u16)>
                 direction: "invalid".to_string(),
                 relation_type: None,
                 max_depth: 0,
+                relation_tag: None,
             },
             500,
         ),
@@ -115,6 +117,7 @@ fn create_query_parameter_test_cases() -> Vec<(String, TraversalNodeQuery, u16)>
                 direction: "in".to_string(),
                 relation_type: None,
                 max_depth: 0,
+                relation_tag: None,
             },
             500,
         ),
@@ -124,6 +127,7 @@ fn create_query_parameter_test_cases() -> Vec<(String, TraversalNodeQuery, u16)>
                 direction: "out".to_string(),
                 relation_type: Some("invalid".to_string()),
                 max_depth: 0,
+                relation_tag: None,
             },
             500,
         ),
@@ -133,6 +137,7 @@ fn create_query_parameter_test_cases() -> Vec<(String, TraversalNodeQuery, u16)>
                 direction: "out".to_string(),
                 relation_type: Some("parent".to_string()),
                 max_depth: 0,
+                relation_tag: None,
             },
             200,
         ),
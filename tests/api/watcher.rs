@@ -0,0 +1,128 @@
+use crate::api::helpers::spawn_app_with;
+use reqwest::Client;
+use rustfastingest::{
+    config::config::GeneralConfig,
+    domain::{ingestion::Ingestion, job::JobState},
+};
+use s3::{bucket_ops::BucketConfiguration, creds::Credentials, Bucket, Region};
+use std::time::Duration;
+
+/// Waits for the watcher to pick up and finish `ingestion_id`.
+async fn wait_for_ingestion(client: &Client, address: &str, ingestion_id: &str) -> Ingestion {
+    for _ in 0..100 {
+        let response = client
+            .get(format!("{}/ingestions/{}", address, ingestion_id))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        if response.status() == reqwest::StatusCode::OK {
+            let ingestion = response
+                .json::<Ingestion>()
+                .await
+                .expect("failed to get payload");
+            if ingestion.finished_at.is_some() {
+                return ingestion;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    panic!("{} was not ingested", ingestion_id);
+}
+
+#[actix_rt::test]
+async fn test_watch_local_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let example = std::env::current_dir()
+        .unwrap()
+        .join("tests/data/example.json");
+    let suffix = uuid::Uuid::new_v4();
+    std::fs::create_dir(dir.path().join("incoming")).unwrap();
+    let graph = dir.path().join(format!("incoming/watched_{}.json", suffix));
+    std::fs::copy(&example, &graph).unwrap();
+    std::fs::write(dir.path().join("incoming/notes.txt"), "skip me").unwrap();
+    let broken_id = format!("broken_{}", suffix);
+    let broken = dir.path().join(format!("incoming/{}.json", broken_id));
+    std::fs::write(&broken, "not json").unwrap();
+
+    let mut configuration = GeneralConfig::from_env().unwrap();
+    configuration.watcher.watch_source = Some(format!("file://{}/", dir.path().display()));
+    configuration.watcher.watch_pattern = "incoming/*.json".to_string();
+    configuration.watcher.watch_ingestion_id = "{stem}".to_string();
+    configuration.watcher.watch_interval_secs = 1;
+    let app = spawn_app_with(configuration)
+        .await
+        .expect("test app initialization failed!");
+    let client = Client::new();
+
+    let ingestion_id = format!("watched_{}", suffix);
+    let ingestion = wait_for_ingestion(&client, &app.address, &ingestion_id).await;
+    assert_eq!(ingestion.status, JobState::Succeeded);
+    let graph = graph.canonicalize().unwrap();
+    assert_eq!(ingestion.files, vec![format!("file://{}", graph.display())]);
+
+    // Already processed: the next polls leave it alone.
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let again = wait_for_ingestion(&client, &app.address, &ingestion_id).await;
+    assert_eq!(again.job_id, ingestion.job_id);
+
+    // Failed objects are not recorded, so they are tried again.
+    let failed = wait_for_ingestion(&client, &app.address, &broken_id).await;
+    assert_eq!(failed.status, JobState::Failed);
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let retried = wait_for_ingestion(&client, &app.address, &broken_id).await;
+    assert_ne!(retried.job_id, failed.job_id);
+}
+
+#[actix_rt::test]
+#[ignore = "needs MinIO at S3_ENDPOINT with AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY"]
+async fn test_watch_s3_prefix() {
+    let mut configuration = GeneralConfig::from_env().unwrap();
+    let endpoint = configuration
+        .source
+        .s3_endpoint
+        .clone()
+        .expect("S3_ENDPOINT is not set");
+    let region = Region::Custom {
+        region: configuration.app.region.clone(),
+        endpoint,
+    };
+    let credentials = Credentials::from_env().unwrap();
+    let name = format!("watched-{}", uuid::Uuid::new_v4().simple());
+    Bucket::create_with_path_style(
+        &name,
+        region.clone(),
+        credentials.clone(),
+        BucketConfiguration::default(),
+    )
+    .await
+    .expect("Failed to create bucket");
+    let bucket = Bucket::new(&name, region, credentials)
+        .unwrap()
+        .with_path_style();
+    let example = std::fs::read(
+        std::env::current_dir()
+            .unwrap()
+            .join("tests/data/example.json"),
+    )
+    .unwrap();
+    for key in [
+        "incoming/customer-a/part-1.json",
+        "incoming/customer-a/part-2.json",
+    ] {
+        bucket.put_object(key, &example).await.unwrap();
+    }
+
+    configuration.watcher.watch_source = Some(format!("s3://{}/incoming/", name));
+    configuration.watcher.watch_pattern = "**/*.json".to_string();
+    configuration.watcher.watch_ingestion_id = format!("{{dir}}-{}", name);
+    configuration.watcher.watch_interval_secs = 1;
+    let app = spawn_app_with(configuration)
+        .await
+        .expect("test app initialization failed!");
+    let client = Client::new();
+
+    let ingestion_id = format!("customer-a-{}", name);
+    let ingestion = wait_for_ingestion(&client, &app.address, &ingestion_id).await;
+    assert_eq!(ingestion.status, JobState::Succeeded);
+    assert_eq!(ingestion.files.len(), 2);
+}
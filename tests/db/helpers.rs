@@ -12,6 +12,12 @@ pub fn create_test_database_config() -> DatabaseConfig {
         schema_file,
         concurrency_limit: 1,
         datacenter: "dt".to_string(),
+        max_batch_rows: 100,
+        retry_max_attempts: 3,
+        retry_base_delay_ms: 10,
+        retry_max_delay_ms: 100,
+        retry_on: "overloaded,timeout,unavailable,io".to_string(),
+        dead_letter: None,
     }
 }
 
@@ -39,6 +45,7 @@ pub fn create_sample_node() -> NodeModel {
         path,
         node_type,
         tags,
+        total_children: None,
     }
 }
 
@@ -87,13 +94,14 @@ pub fn create_test_nodes(count: usize) -> Vec<NodeModel> {
             path,
             node_type,
             tags,
+            total_children: None,
         };
         nodes.push(node);
     }
     nodes
 }
 
-pub fn get_random_node(nodes: &Vec<NodeModel>) -> Option<&NodeModel> {
+pub fn get_random_node(nodes: &[NodeModel]) -> Option<&NodeModel> {
     if nodes.is_empty() {
         return None;
     }
@@ -1,7 +1,8 @@
 use crate::db::helpers::{
     cleanup_database, create_sample_node, create_test_database_config, create_test_nodes,
 };
-use rustfastingest::db::syclla::ScyllaService;
+use rustfastingest::db::{dead_letter::FailedRow, syclla::ScyllaService};
+use uuid::Uuid;
 
 #[tokio::test]
 async fn test_get_node_by_id() {
@@ -36,6 +37,10 @@ async fn test_insert_nodes() {
         .expect("Initialization database failed.");
     let result = service.insert_nodes(nodes).await;
     assert!(result.is_ok());
+    let summary = result.unwrap();
+    assert_eq!(summary.rows_written, 10);
+    assert_eq!(summary.rows_failed, 0);
+    assert_eq!(summary.nodes_unindexed, 0);
     let session = ScyllaService::new_session("127.0.0.1:9042".to_string())
         .await
         .expect("failed connection to scylladb");
@@ -79,3 +84,118 @@ async fn test_insert_node() {
         .await
         .expect("error occured.");
 }
+
+#[tokio::test]
+async fn test_delete_ingestion_nodes() {
+    let mut nodes = create_test_nodes(5);
+    let ingestion_id = format!("delete-{}", Uuid::new_v4());
+    for node in nodes.iter_mut() {
+        node.ingestion_id = ingestion_id.clone();
+    }
+    let config = create_test_database_config();
+    let service = ScyllaService::init(&config)
+        .await
+        .expect("Initialization database failed.");
+    service.insert_nodes(nodes).await.expect("insert failed");
+
+    let ids = service
+        .get_ingestion_node_ids(&ingestion_id)
+        .await
+        .expect("listing ingestion nodes failed");
+    assert_eq!(ids.len(), 5);
+
+    for id in ids.iter() {
+        service.delete_node(*id).await.expect("delete failed");
+        let rows = service.get_node_rows(*id).await.expect("query failed");
+        assert!(rows.is_empty());
+    }
+    service
+        .delete_ingestion_index(&ingestion_id)
+        .await
+        .expect("delete index failed");
+    let ids = service
+        .get_ingestion_node_ids(&ingestion_id)
+        .await
+        .expect("listing ingestion nodes failed");
+    assert!(ids.is_empty());
+}
+
+#[tokio::test]
+async fn test_delete_ingestion_records() {
+    let ingestion_id = format!("records-{}", Uuid::new_v4());
+    let job_id = Uuid::new_v4();
+    let config = create_test_database_config();
+    let service = ScyllaService::init(&config)
+        .await
+        .expect("Initialization database failed.");
+    service
+        .save_checkpoint_request(job_id, &ingestion_id, "{}".to_string())
+        .await
+        .expect("saving request failed");
+    service
+        .save_checkpoint(job_id, 0, "a.json", 10, true)
+        .await
+        .expect("saving checkpoint failed");
+    service
+        .save_watched_object("s3://bucket/", "a.json", None, &ingestion_id, job_id)
+        .await
+        .expect("saving watched object failed");
+
+    service
+        .delete_ingestion_records(&ingestion_id)
+        .await
+        .expect("deleting records failed");
+    let checkpoints = service
+        .get_checkpoints(job_id)
+        .await
+        .expect("listing checkpoints failed");
+    assert!(checkpoints.is_empty());
+    let objects = service
+        .get_watched_objects("s3://bucket/")
+        .await
+        .expect("listing watched objects failed");
+    assert!(objects.iter().all(|object| object.key != "a.json"));
+    let ingestion = service
+        .get_ingestion(&ingestion_id)
+        .await
+        .expect("fetching ingestion failed");
+    assert!(ingestion.is_none());
+}
+
+#[tokio::test]
+async fn test_dead_letter_table() {
+    let ingestion_id = format!("failed-{}", Uuid::new_v4());
+    let mut node = create_sample_node();
+    node.ingestion_id = ingestion_id.clone();
+    let mut config = create_test_database_config();
+    config.dead_letter = Some("table".to_string());
+    let service = ScyllaService::init(&config)
+        .await
+        .expect("Initialization database failed.");
+
+    let failed = FailedRow {
+        row: node.clone(),
+        error: "Timeout Error".to_string(),
+        attempts: 3,
+        failed_at: chrono::Utc::now(),
+    };
+    let kept = service
+        .dead_letter(&[failed])
+        .await
+        .expect("dead-lettering failed");
+    assert_eq!(kept, 1);
+
+    let rows = service
+        .get_failed_rows(&ingestion_id)
+        .await
+        .expect("listing failed rows failed");
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].row.uuid, node.uuid);
+    assert_eq!(rows[0].attempts, 3);
+
+    let summary = service
+        .insert_nodes(rows.into_iter().map(|failed| failed.row).collect())
+        .await
+        .expect("replay failed");
+    assert_eq!(summary.rows_written, 1);
+}
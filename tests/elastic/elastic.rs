@@ -67,7 +67,7 @@ async fn test_search() {
         .await;
     println!("{:?}", result);
     assert!(result.is_ok());
-    let _res = elastic_service
+    elastic_service
         .delete_all_record(index_name)
         .await
         .expect("deleting all records failed!");
@@ -83,7 +83,7 @@ async fn test_batch_indexing() {
     let index_name = "graph";
     let result = elastic_service.index_nodes(test_nodes, index_name).await;
     assert!(result.is_ok());
-    let _res = elastic_service
+    elastic_service
         .delete_all_record(index_name)
         .await
         .expect("deleting all records failed!");
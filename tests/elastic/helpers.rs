@@ -5,7 +5,7 @@ use rustfastingest::{
 use uuid::Uuid;
 
 pub fn get_test_configuration() -> ElasticSearchConfig {
-    let config = ElasticSearchConfig {
+    ElasticSearchConfig {
         url: "http://localhost:9200".to_string(),
         enabled: true,
         batch_size: 10,
@@ -16,8 +16,7 @@ pub fn get_test_configuration() -> ElasticSearchConfig {
         concurrency_limit: 10,
         refresh_interval: "20s".to_string(),
         source_enabled: true,
-    };
-    config
+    }
 }
 
 pub fn create_test_nodes(count: usize) -> Vec<IndexNode> {